@@ -0,0 +1,44 @@
+//! Cost of `collect` per retired pointer as the number of hazards grows.
+//!
+//! For each count, that many shields protect distinct live objects while a thread-local list of
+//! unprotected pointers is collected. Run with `cargo bench --bench collect_scaling`.
+
+use std::time::{Duration, Instant};
+
+use hazard::{Domain, RetiredSet, Shield};
+
+const RETIRED: usize = 1024;
+const ROUNDS: usize = 32;
+
+/// Returns the mean cost of `collect` per retired pointer with `hazards` protected objects.
+fn collect_cost(hazards: usize) -> Duration {
+    let domain = Domain::builder().threshold(usize::MAX).build();
+    let objects = (0..hazards).map(Box::new).collect::<Vec<_>>();
+    let shields = objects
+        .iter()
+        .map(|object| {
+            let shield = Shield::new(domain.hazards());
+            let _ = shield.set(&**object as *const usize as *mut usize);
+            shield
+        })
+        .collect::<Vec<_>>();
+    let mut retires = RetiredSet::new(&domain);
+    let mut total = Duration::ZERO;
+    for _ in 0..ROUNDS {
+        for i in 0..RETIRED {
+            unsafe { retires.retire(Box::into_raw(Box::new(i))) };
+        }
+        let start = Instant::now();
+        retires.collect();
+        total += start.elapsed();
+    }
+    drop(shields);
+    total / (ROUNDS * RETIRED) as u32
+}
+
+fn main() {
+    println!("{:>8} {:>14}", "hazards", "per retired");
+    for hazards in [10, 100, 1_000, 10_000] {
+        println!("{hazards:>8} {:>14?}", collect_cost(hazards));
+    }
+}
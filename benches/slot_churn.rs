@@ -0,0 +1,54 @@
+//! Latency of acquiring shields from short-lived threads, as the slot list grows.
+//!
+//! For each size, the main thread holds that many shields so that every acquisition has to skip
+//! them, while short-lived threads repeatedly create and drop shields. Run with
+//! `cargo bench --bench slot_churn`.
+
+use std::hint::black_box;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use hazard::{HAZARDS, HazardBag, Shield};
+
+const THREADS: usize = 8;
+const ROUNDS: usize = 16;
+const SHIELDS_PER_THREAD: usize = 256;
+
+/// Returns the mean latency of `Shield::new` in threads spawned `ROUNDS` times.
+fn churn(hazards: &'static HazardBag) -> Duration {
+    let mut total = Duration::ZERO;
+    for _ in 0..ROUNDS {
+        let handles = (0..THREADS)
+            .map(|_| {
+                thread::spawn(move || {
+                    let start = Instant::now();
+                    for _ in 0..SHIELDS_PER_THREAD {
+                        drop(black_box(Shield::new(hazards)));
+                    }
+                    start.elapsed()
+                })
+            })
+            .collect::<Vec<_>>();
+        total += handles.into_iter().map(|h| h.join().unwrap()).sum();
+    }
+    total / (ROUNDS * THREADS * SHIELDS_PER_THREAD) as u32
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>8} {:>14} {:>14}",
+        "held", "slots", "private bag", "default bag"
+    );
+    for held in [0, 64, 256, 1024, 4096] {
+        let private: &'static HazardBag = Box::leak(Box::new(HazardBag::new()));
+        let private_held = (0..held).map(|_| Shield::new(private)).collect::<Vec<_>>();
+        let default_held = (0..held).map(|_| Shield::default()).collect::<Vec<_>>();
+        let private_latency = churn(private);
+        let default_latency = churn(HAZARDS.hazards());
+        println!(
+            "{held:>8} {:>8} {private_latency:>14?} {default_latency:>14?}",
+            private.slots().count(),
+        );
+        drop((private_held, default_held));
+    }
+}
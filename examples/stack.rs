@@ -0,0 +1,106 @@
+//! Concurrent pushes and pops on a `Stack`, printing the throughput and checking at the end that
+//! every pushed value is popped or left exactly once.
+//!
+//! ```text
+//! cargo run --release --example stack -- --threads 8 --ops 1000000 --push 50 --reclaimer hp
+//! ```
+//!
+//! `--reclaimer` is one of `hp` (default), `ebr`, `ibr` and `hyaline`.
+
+use std::collections::HashSet;
+use std::env;
+use std::ops::Deref;
+use std::thread::scope;
+use std::time::Instant;
+
+use hazard::collections::Stack;
+use hazard::{HAZARDS, Reclaimer, ebr, hyaline, ibr};
+
+struct Options {
+    threads: usize,
+    ops: usize,
+    push: usize,
+    reclaimer: String,
+}
+
+fn parse() -> Options {
+    let mut options = Options {
+        threads: 8,
+        ops: 1_000_000,
+        push: 50,
+        reclaimer: "hp".into(),
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .unwrap_or_else(|| panic!("missing value of {arg}"));
+        match arg.as_str() {
+            "--threads" => options.threads = value.parse().expect("invalid --threads"),
+            "--ops" => options.ops = value.parse().expect("invalid --ops"),
+            "--push" => options.push = value.parse().expect("invalid --push"),
+            "--reclaimer" => options.reclaimer = value,
+            _ => panic!("unknown option {arg}"),
+        }
+    }
+    assert!(options.push <= 100, "--push is a percentage");
+    options
+}
+
+fn run<R: Deref<Target: Reclaimer> + Sync>(stack: Stack<u64, R>, options: &Options) {
+    let ops_per_thread = options.ops / options.threads;
+    let start = Instant::now();
+    let (pushed, popped): (Vec<_>, Vec<_>) = scope(|s| {
+        let handles = (0..options.threads)
+            .map(|t| {
+                let stack = &stack;
+                s.spawn(move || {
+                    let (mut pushed, mut popped) = (Vec::new(), Vec::new());
+                    for i in 0..ops_per_thread {
+                        // deterministic mix of the operations.
+                        if (i * 37 + t) % 100 < options.push {
+                            let value = ((t as u64) << 32) | i as u64;
+                            stack.push(value);
+                            pushed.push(value);
+                        } else if let Some(value) = stack.try_pop() {
+                            popped.push(value);
+                        }
+                    }
+                    (pushed, popped)
+                })
+            })
+            .collect::<Vec<_>>();
+        handles.into_iter().map(|h| h.join().unwrap()).unzip()
+    });
+    let elapsed = start.elapsed();
+
+    let pushed = pushed.into_iter().flatten().collect::<HashSet<_>>();
+    let mut seen = HashSet::new();
+    let popped = popped.into_iter().flatten();
+    let left = std::iter::from_fn(|| stack.try_pop());
+    for value in popped.chain(left) {
+        assert!(seen.insert(value), "{value:#x} popped twice");
+    }
+    assert_eq!(seen, pushed, "values lost or invented");
+    println!(
+        "{} ops in {elapsed:?}: {:.2} Mops/s, {} values checked",
+        ops_per_thread * options.threads,
+        (ops_per_thread * options.threads) as f64 / elapsed.as_secs_f64() / 1e6,
+        pushed.len(),
+    );
+}
+
+fn main() {
+    let options = parse();
+    println!(
+        "stack: {} threads, {}% pushes, {}",
+        options.threads, options.push, options.reclaimer
+    );
+    match options.reclaimer.as_str() {
+        "hp" => run(Stack::with_reclaimer(&HAZARDS), &options),
+        "ebr" => run(Stack::with_reclaimer(&ebr::Collector::new()), &options),
+        "ibr" => run(Stack::with_reclaimer(&ibr::Collector::new()), &options),
+        "hyaline" => run(Stack::with_reclaimer(&hyaline::Collector::new()), &options),
+        other => panic!("unknown reclaimer {other}"),
+    }
+}
@@ -0,0 +1,47 @@
+//! Interprets the input as operations on the collections and checks them against sequential
+//! models.
+
+#![no_main]
+
+use std::collections::{HashMap, VecDeque};
+
+use hazard::Domain;
+use hazard::collections::{HpCowMap, Queue, Stack};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // a small threshold to reclaim often.
+    let domain = Domain::builder().threshold(4).build();
+    let stack = Stack::with_reclaimer(&domain);
+    let queue = Queue::with_reclaimer(&domain);
+    let map = HpCowMap::with_reclaimer(&domain);
+    let mut stack_model = Vec::new();
+    let mut queue_model = VecDeque::new();
+    let mut map_model = HashMap::new();
+
+    for op in data.chunks(2) {
+        let value = op.get(1).copied().unwrap_or_default();
+        match op[0] % 8 {
+            0 => {
+                stack.push(Box::new(value));
+                stack_model.push(Box::new(value));
+            }
+            1 => assert_eq!(stack.try_pop(), stack_model.pop()),
+            2 => {
+                queue.push(Box::new(value));
+                queue_model.push_back(Box::new(value));
+            }
+            3 => assert_eq!(queue.try_pop(), queue_model.pop_front()),
+            4 => assert_eq!(
+                map.insert(value % 16, value),
+                map_model.insert(value % 16, value)
+            ),
+            5 => assert_eq!(map.remove(&(value % 16)), map_model.remove(&(value % 16))),
+            6 => assert_eq!(map.get(&(value % 16)), map_model.get(&(value % 16)).copied()),
+            _ => domain.collect(),
+        }
+        assert_eq!(stack.is_empty(), stack_model.is_empty());
+    }
+    // the collections must be dropped before their domain.
+    drop((stack, queue, map));
+});
@@ -0,0 +1,75 @@
+//! Interprets the input as raw uses of `Shield` and `RetiredSet`, and checks with a shadow model
+//! that a protected pointer is never freed.
+
+#![no_main]
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use hazard::{Domain, RetiredSet, Shield};
+use libfuzzer_sys::fuzz_target;
+
+const SOURCES: usize = 4;
+const SHIELDS: usize = 4;
+
+/// An object that records whether it is alive.
+struct Tracked {
+    id: usize,
+    alive: Rc<RefCell<HashSet<usize>>>,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        assert!(self.alive.borrow_mut().remove(&self.id), "double free");
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let domain = Domain::builder().threshold(usize::MAX).build();
+    let alive = Rc::new(RefCell::new(HashSet::new()));
+    let mut next_id = 0;
+    let mut new = || {
+        let id = next_id;
+        next_id += 1;
+        let _ = alive.borrow_mut().insert(id);
+        Box::into_raw(Box::new(Tracked {
+            id,
+            alive: alive.clone(),
+        }))
+    };
+    let sources: [AtomicPtr<Tracked>; SOURCES] = std::array::from_fn(|_| AtomicPtr::new(new()));
+    let shields: [Shield; SHIELDS] = std::array::from_fn(|_| Shield::new(domain.hazards()));
+    // the shadow model: the pointer each shield protects, if any.
+    let mut protected = [None; SHIELDS];
+    let mut retires = RetiredSet::new(&domain);
+
+    for op in data.chunks(2) {
+        let arg = op.get(1).copied().unwrap_or_default() as usize;
+        let (shield, source) = (arg % SHIELDS, arg / SHIELDS % SOURCES);
+        match op[0] % 4 {
+            0 => protected[shield] = Some(shields[shield].protect(&sources[source])),
+            1 => {
+                shields[shield].clear();
+                protected[shield] = None;
+            }
+            2 => {
+                let old = sources[source].swap(new(), Ordering::Relaxed);
+                unsafe { retires.retire(old) };
+            }
+            _ => retires.collect(),
+        }
+        for pointer in protected.iter().flatten() {
+            let id = unsafe { (**pointer).id };
+            assert!(alive.borrow().contains(&id), "protected pointer freed");
+        }
+    }
+
+    drop(shields);
+    drop(retires);
+    for source in &sources {
+        drop(unsafe { Box::from_raw(source.load(Ordering::Relaxed)) });
+    }
+    assert!(alive.borrow().is_empty(), "leak");
+});
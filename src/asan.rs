@@ -0,0 +1,31 @@
+//! AddressSanitizer manual poisoning, enabled by the `asan` feature.
+//!
+//! The crate must be built with `-Zsanitizer=address` for the interface to be linked.
+
+use core::ffi::c_void;
+
+unsafe extern "C" {
+    fn __asan_poison_memory_region(addr: *const c_void, size: usize);
+    fn __asan_unpoison_memory_region(addr: *const c_void, size: usize);
+    #[cfg(test)]
+    fn __asan_address_is_poisoned(addr: *const c_void) -> i32;
+}
+
+/// Marks the `size` bytes at `addr` as unaddressable. Any access before `unpoison` is reported.
+pub(crate) fn poison(addr: *const (), size: usize) {
+    if size != 0 {
+        unsafe { __asan_poison_memory_region(addr.cast(), size) }
+    }
+}
+
+/// Marks the `size` bytes at `addr` as addressable again.
+pub(crate) fn unpoison(addr: *const (), size: usize) {
+    if size != 0 {
+        unsafe { __asan_unpoison_memory_region(addr.cast(), size) }
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn is_poisoned(addr: *const ()) -> bool {
+    unsafe { __asan_address_is_poisoned(addr.cast()) != 0 }
+}
@@ -0,0 +1,388 @@
+//! Atomic pointers to objects reclaimed with hazard pointers.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::AtomicPtr;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicPtr;
+
+use super::ordering::Ordering;
+use super::{Domain, RetiredSet, Shield};
+
+/// An atomic pointer to `T` whose loads are protected with shields and whose overwritten
+/// pointers are retired, instead of a raw `AtomicPtr` with manual shield bookkeeping.
+///
+/// ```
+/// use hazard::{Atomic, Domain, RetiredSet, Shield};
+///
+/// let domain = Domain::new();
+/// let mut retired = RetiredSet::new(&domain);
+/// let atomic = Atomic::new(Box::into_raw(Box::new(1)));
+///
+/// let shield = Shield::new(domain.hazards());
+/// assert_eq!(unsafe { *atomic.load_protected(&shield) }, 1);
+/// drop(shield);
+///
+/// unsafe { atomic.store_retire(Box::into_raw(Box::new(2)), &mut retired) };
+/// unsafe { atomic.store_retire(std::ptr::null_mut(), &mut retired) };
+/// ```
+#[derive(Debug)]
+pub struct Atomic<T> {
+    inner: AtomicPtr<T>,
+}
+
+impl<T> Atomic<T> {
+    #[cfg(not(feature = "check-loom"))]
+    /// Creates a new atomic pointer.
+    pub const fn new(pointer: *mut T) -> Self {
+        Self {
+            inner: AtomicPtr::new(pointer),
+        }
+    }
+
+    #[cfg(feature = "check-loom")]
+    /// Creates a new atomic pointer.
+    pub fn new(pointer: *mut T) -> Self {
+        Self {
+            inner: AtomicPtr::new(pointer),
+        }
+    }
+
+    #[cfg(not(feature = "check-loom"))]
+    /// Creates a new null atomic pointer.
+    pub const fn null() -> Self {
+        Self::new(ptr::null_mut())
+    }
+
+    #[cfg(feature = "check-loom")]
+    /// Creates a new null atomic pointer.
+    pub fn null() -> Self {
+        Self::new(ptr::null_mut())
+    }
+
+    /// Returns the underlying `AtomicPtr`, e.g. for `Shield::validate`.
+    pub fn as_atomic_ptr(&self) -> &AtomicPtr<T> {
+        &self.inner
+    }
+
+    /// Loads the pointer and protects it with `shield`. See `Shield::protect`.
+    pub fn load_protected(&self, shield: &Shield<'_>) -> *mut T {
+        shield.protect(&self.inner)
+    }
+
+    /// Stores `new` if the current pointer is `current`, and returns `current` on success.
+    /// Otherwise, returns the current pointer protected by `shield`, to retry with.
+    ///
+    /// `shield` may be the one protecting `current`, as `current` is no longer needed on failure.
+    pub fn compare_exchange_protected(
+        &self,
+        current: *mut T,
+        new: *mut T,
+        shield: &Shield<'_>,
+    ) -> Result<*mut T, *mut T> {
+        self.inner
+            .compare_exchange(current, new, Ordering::AcqRel, Ordering::Relaxed)
+            .map_err(|_| shield.protect(&self.inner))
+    }
+
+    /// Stores `new` and retires the replaced pointer to `retired`, unless it is null.
+    ///
+    /// # Safety
+    ///
+    /// * `new` must be valid or null, and must be retired only once it is unlinked again.
+    /// * The replaced pointer must not be reachable from shared memory other than this atomic, and
+    ///   must not be retired elsewhere.
+    /// * The pointers must be protected by the domain of `retired`.
+    pub unsafe fn store_retire<D: Deref<Target = Domain>>(
+        &self,
+        new: *mut T,
+        retired: &mut RetiredSet<D>,
+    ) {
+        let old = self.inner.swap(new, Ordering::AcqRel);
+        if !old.is_null() {
+            unsafe { retired.retire(old) };
+        }
+    }
+
+    /// Loads the pointer and protects it with `shield`, for as long as `shield` is borrowed. The
+    /// shield is borrowed mutably, so that it cannot protect another pointer meanwhile.
+    pub fn load_shared<'g>(&self, shield: &'g mut Shield<'_>) -> Shared<'g, T> {
+        Shared::from_raw(shield.protect(&self.inner))
+    }
+
+    /// Publishes `new` if the current pointer is `current`, e.g. `Shared::as_raw`, and returns
+    /// `current` on success, which may be retired if it is no longer reachable. It is not protected
+    /// by `shield`, so it is returned as a raw pointer. Otherwise, returns the current pointer
+    /// protected by `shield` with `new` back, to retry with.
+    pub fn compare_exchange_owned<'g>(
+        &self,
+        current: *mut T,
+        new: Owned<T>,
+        shield: &'g mut Shield<'_>,
+    ) -> Result<*mut T, (Shared<'g, T>, Owned<T>)> {
+        let pointer = new.pointer.as_ptr();
+        match self.compare_exchange_protected(current, pointer, shield) {
+            Ok(old) => {
+                // `new` is now owned by this atomic.
+                let _ = new.into_raw();
+                Ok(old)
+            }
+            Err(observed) => Err((Shared::from_raw(observed), new)),
+        }
+    }
+
+    /// Consumes the atomic pointer and returns the contained pointer.
+    pub fn into_inner(self) -> *mut T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T> Default for Atomic<T> {
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T> From<*mut T> for Atomic<T> {
+    fn from(pointer: *mut T) -> Self {
+        Self::new(pointer)
+    }
+}
+
+impl<T> From<Owned<T>> for Atomic<T> {
+    fn from(owned: Owned<T>) -> Self {
+        Self::new(owned.into_raw())
+    }
+}
+
+/// An object that is owned by the current thread, i.e. not published to shared memory yet.
+///
+/// An `Owned` is consumed when it is published, e.g. by `Atomic::compare_exchange_owned`, so a
+/// node is published at most once.
+pub struct Owned<T> {
+    pointer: NonNull<T>,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for Owned<T> {}
+unsafe impl<T: Sync> Sync for Owned<T> {}
+
+impl<T> Owned<T> {
+    /// Allocates `value` as a new owned object.
+    pub fn new(value: T) -> Self {
+        Self::from(Box::new(value))
+    }
+
+    /// Creates an owned object from a pointer allocated with `Box`.
+    ///
+    /// # Safety
+    ///
+    /// `pointer` must be allocated with `Box` and uniquely owned by the caller.
+    pub unsafe fn from_raw(pointer: *mut T) -> Self {
+        Self {
+            pointer: NonNull::new(pointer).expect("null owned pointer"),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the pointer, leaking the object.
+    pub fn into_raw(self) -> *mut T {
+        let pointer = self.pointer.as_ptr();
+        mem::forget(self);
+        pointer
+    }
+
+    /// Returns the object as a `Box`.
+    pub fn into_box(self) -> Box<T> {
+        unsafe { Box::from_raw(self.into_raw()) }
+    }
+}
+
+impl<T> From<Box<T>> for Owned<T> {
+    fn from(value: Box<T>) -> Self {
+        unsafe { Self::from_raw(Box::into_raw(value)) }
+    }
+}
+
+impl<T> Deref for Owned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.pointer.as_ref() }
+    }
+}
+
+impl<T> DerefMut for Owned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.pointer.as_mut() }
+    }
+}
+
+impl<T> Drop for Owned<T> {
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(self.pointer.as_ptr()) });
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Owned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Owned").field(&**self).finish()
+    }
+}
+
+/// A pointer loaded from an `Atomic` and protected by a shield borrowed mutably for `'g`, so that
+/// the shield protects no other pointer while the `Shared` is alive.
+pub struct Shared<'g, T> {
+    pointer: *mut T,
+    _marker: PhantomData<(&'g (), *const T)>,
+}
+
+impl<T> Clone for Shared<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Shared<'_, T> {}
+
+impl<T> PartialEq for Shared<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.pointer == other.pointer
+    }
+}
+
+impl<T> Eq for Shared<'_, T> {}
+
+impl<T> fmt::Debug for Shared<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Shared").field(&self.pointer).finish()
+    }
+}
+
+impl<'g, T> Shared<'g, T> {
+    fn from_raw(pointer: *mut T) -> Self {
+        Self {
+            pointer,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a null pointer, e.g. as the expected pointer of an empty `Atomic`.
+    pub fn null() -> Self {
+        Self::from_raw(ptr::null_mut())
+    }
+
+    /// Returns the raw pointer.
+    pub fn as_raw(self) -> *mut T {
+        self.pointer
+    }
+
+    /// Returns `true` if the pointer is null.
+    pub fn is_null(self) -> bool {
+        self.pointer.is_null()
+    }
+
+    /// Returns a reference to the protected object, or `None` if the pointer is null.
+    ///
+    /// # Safety
+    ///
+    /// * The pointer must be protected for `'g`. This holds for the pointers returned by
+    ///   `Atomic::load_shared` and `Atomic::compare_exchange_owned`, as their shield is borrowed
+    ///   mutably for `'g` and cannot be set to another pointer meanwhile. A `Shared` created
+    ///   otherwise, e.g. `null`, must not alias a shield that may be re-protected.
+    /// * The atomic must point only to valid objects that are retired before freed.
+    pub unsafe fn as_ref(self) -> Option<&'g T> {
+        unsafe { self.pointer.as_ref() }
+    }
+
+    /// Takes the ownership of the object, e.g. to drop the nodes of a data structure being
+    /// dropped.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be non-null, removed from shared memory, not retired, and not protected by
+    /// other threads.
+    pub unsafe fn into_owned(self) -> Owned<T> {
+        unsafe { Owned::from_raw(self.pointer) }
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::ptr;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+    use super::{Atomic, Owned};
+    use crate::test::common::Tester;
+    use crate::{Domain, RetiredSet, Shield};
+
+    // stored pointers are retired once replaced, and freed once no longer protected.
+    #[test]
+    fn store_retire() {
+        let freed = Arc::new(AtomicUsize::new(0));
+        let domain = Domain::new();
+        let mut retired = RetiredSet::new(&domain);
+        let atomic = Atomic::new(Box::into_raw(Box::new(Tester(freed.clone()))));
+        let shield = Shield::new(domain.hazards());
+        let _ = atomic.load_protected(&shield);
+
+        unsafe {
+            atomic.store_retire(Box::into_raw(Box::new(Tester(freed.clone()))), &mut retired)
+        };
+        retired.collect();
+        assert_eq!(freed.load(Relaxed), 0);
+        drop(shield);
+        retired.collect();
+        assert_eq!(freed.load(Relaxed), 1);
+        drop(unsafe { Box::from_raw(atomic.into_inner()) });
+    }
+
+    // a published `Owned` is taken by the atomic, and a rejected one is returned back.
+    #[test]
+    fn compare_exchange_owned() {
+        let domain = Domain::new();
+        let atomic = Atomic::null();
+        let mut shield = Shield::new(domain.hazards());
+        let first = atomic
+            .compare_exchange_owned(ptr::null_mut(), Owned::new(1), &mut shield)
+            .unwrap();
+        assert!(first.is_null());
+
+        let (current, rejected) = atomic
+            .compare_exchange_owned(ptr::null_mut(), Owned::new(2), &mut shield)
+            .unwrap_err();
+        assert_eq!(unsafe { current.as_ref() }, Some(&1));
+        assert_eq!(*rejected, 2);
+        let current = current.as_raw();
+        assert_eq!(atomic.load_shared(&mut shield).as_raw(), current);
+        drop(shield);
+        drop(unsafe { Box::from_raw(atomic.into_inner()) });
+    }
+
+    // a failed compare-exchange returns the current pointer, protected by the shield.
+    #[test]
+    fn compare_exchange_protected() {
+        let domain = Domain::new();
+        let (first, second) = (Box::into_raw(Box::new(1)), Box::into_raw(Box::new(2)));
+        let atomic = Atomic::new(first);
+        let shield = Shield::new(domain.hazards());
+        let current = atomic.load_protected(&shield);
+        assert_eq!(
+            atomic.compare_exchange_protected(current, second, &shield),
+            Ok(first)
+        );
+        assert_eq!(
+            atomic.compare_exchange_protected(first, first, &shield),
+            Err(second)
+        );
+        assert!(domain.hazards().all_hazards().contains(&second.cast()));
+        drop(shield);
+        drop(unsafe { (Box::from_raw(first), Box::from_raw(second)) });
+    }
+}
@@ -0,0 +1,157 @@
+//! Backoff for retry loops.
+
+#[cfg(not(feature = "check-loom"))]
+use core::hint;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::AtomicPtr;
+#[cfg(not(feature = "check-loom"))]
+use std::thread;
+#[cfg(not(feature = "check-loom"))]
+use std::time::Duration;
+
+#[cfg(not(feature = "check-loom"))]
+use super::ordering::Ordering;
+
+/// How the retry loops of the crate wait, e.g. for another thread to finish an update or to
+/// release a pointer. See `set_wait_strategy`.
+#[derive(Debug, Clone, Copy, Default)]
+#[non_exhaustive]
+pub enum WaitStrategy {
+    /// Spin with exponential backoff, then yield the thread, and park it in loops that may wait
+    /// for long.
+    #[default]
+    Backoff,
+    /// Only spin, e.g. on targets without threads.
+    Spin,
+    /// Spin with exponential backoff, then call the function instead of yielding or parking, e.g.
+    /// to execute `wfe` on Arm.
+    Wait(fn()),
+    /// Call the function on each retry with the number of retries so far, instead of spinning.
+    Custom(fn(u32)),
+}
+
+#[cfg(not(feature = "check-loom"))]
+/// The strategy set by `set_wait_strategy`, or null for the default.
+static STRATEGY: AtomicPtr<WaitStrategy> = AtomicPtr::new(core::ptr::null_mut());
+
+#[cfg(not(feature = "check-loom"))]
+/// Sets how the retry loops of the crate wait, for all threads. This is intended to be called at
+/// the start of the program, e.g. on targets where yielding and parking a thread are not
+/// available. Each call leaks a small allocation.
+///
+/// ```
+/// hazard::set_wait_strategy(hazard::WaitStrategy::Spin);
+/// # hazard::set_wait_strategy(hazard::WaitStrategy::Backoff);
+/// ```
+pub fn set_wait_strategy(strategy: WaitStrategy) {
+    let _ = STRATEGY.swap(Box::leak(Box::new(strategy)), Ordering::AcqRel);
+}
+
+#[cfg(not(feature = "check-loom"))]
+/// Returns the strategy set by `set_wait_strategy`.
+pub fn wait_strategy() -> WaitStrategy {
+    // # Safety
+    // `STRATEGY` is null or a leaked allocation.
+    unsafe { STRATEGY.load(Ordering::Acquire).as_ref() }
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Exponential backoff: spins with `spin_loop` hints, then yields, then parks if enabled, unless
+/// another `WaitStrategy` is set.
+#[derive(Debug, Default)]
+pub(crate) struct Backoff {
+    step: u32,
+    park: bool,
+}
+
+impl Backoff {
+    /// The last step that spins. Each step spins twice as long as the previous one.
+    const SPIN_LIMIT: u32 = 6;
+    /// The last step that yields. Later steps park if enabled, and yield otherwise.
+    const YIELD_LIMIT: u32 = 10;
+    /// The longest park, in microseconds.
+    const PARK_LIMIT: u64 = 1 << 10;
+
+    /// Creates a backoff that spins and then yields.
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a backoff that spins, yields, and then parks the thread with increasing timeouts.
+    /// For loops that may wait for other threads for long.
+    pub(crate) fn parking() -> Self {
+        Self {
+            step: 0,
+            park: true,
+        }
+    }
+
+    /// Backs off once before retrying.
+    pub(crate) fn snooze(&mut self) {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "check-loom")] {
+                loom::sync::atomic::spin_loop_hint();
+            } else {
+                let spin = |step: u32| {
+                    for _ in 0..1 << step.min(Self::SPIN_LIMIT) {
+                        hint::spin_loop();
+                    }
+                };
+                match wait_strategy() {
+                    WaitStrategy::Custom(wait) => wait(self.step),
+                    _ if self.step <= Self::SPIN_LIMIT => spin(self.step),
+                    WaitStrategy::Spin => spin(self.step),
+                    WaitStrategy::Wait(wait) => wait(),
+                    WaitStrategy::Backoff if self.step <= Self::YIELD_LIMIT || !self.park => {
+                        thread::yield_now();
+                    }
+                    WaitStrategy::Backoff => {
+                        let micros =
+                            1 << (self.step - Self::YIELD_LIMIT).min(Self::PARK_LIMIT.ilog2());
+                        thread::park_timeout(Duration::from_micros(micros));
+                    }
+                }
+                self.step = self.step.saturating_add(1);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use super::Backoff;
+
+    // the steps keep escalating without overflowing.
+    #[test]
+    fn escalate() {
+        let mut backoff = Backoff::parking();
+        for _ in 0..Backoff::YIELD_LIMIT + 4 {
+            backoff.snooze();
+        }
+        assert_eq!(backoff.step, Backoff::YIELD_LIMIT + 4);
+    }
+
+    // a custom strategy is called on each retry with the number of retries so far.
+    #[test]
+    fn custom_strategy() {
+        use std::cell::Cell;
+
+        use super::{WaitStrategy, set_wait_strategy};
+
+        thread_local! {
+            static RETRIES: Cell<u32> = const { Cell::new(0) };
+        }
+        fn wait(step: u32) {
+            RETRIES.with(|retries| retries.set(step + 1));
+        }
+
+        set_wait_strategy(WaitStrategy::Custom(wait));
+        let mut backoff = Backoff::new();
+        for _ in 0..3 {
+            backoff.snooze();
+        }
+        set_wait_strategy(WaitStrategy::Backoff);
+        assert_eq!(RETRIES.with(Cell::get), 3);
+    }
+}
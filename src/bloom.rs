@@ -0,0 +1,80 @@
+//! Bloom filter over a snapshot of hazards, used to cheaply rule out unprotected pointers before
+//! the exact membership test.
+
+/// Bloom filter of pointers with two hash functions.
+#[derive(Debug)]
+pub(crate) struct BloomFilter {
+    bits: Vec<u64>,
+    // log2 of the number of bits.
+    shift: u32,
+}
+
+impl BloomFilter {
+    /// The minimum number of hazards for which the filter pays off.
+    pub(crate) const MIN_HAZARDS: usize = 32;
+
+    /// The number of bits per pointer in the filter.
+    const BITS_PER_POINTER: usize = 8;
+
+    /// Creates a filter containing `len` pointers of `pointers`.
+    pub(crate) fn new(len: usize, pointers: impl Iterator<Item = *mut ()>) -> Self {
+        let len = (len * Self::BITS_PER_POINTER)
+            .next_power_of_two()
+            .max(u64::BITS as usize);
+        let mut filter = Self {
+            bits: vec![0; len / u64::BITS as usize],
+            shift: len.trailing_zeros(),
+        };
+        for pointer in pointers {
+            for index in filter.indices(pointer) {
+                filter.bits[index / u64::BITS as usize] |= 1 << (index % u64::BITS as usize);
+            }
+        }
+        filter
+    }
+
+    /// Returns `false` if `pointer` is definitely not in the filter.
+    pub(crate) fn may_contain(&self, pointer: *mut ()) -> bool {
+        self.indices(pointer).into_iter().all(|index| {
+            self.bits[index / u64::BITS as usize] & (1 << (index % u64::BITS as usize)) != 0
+        })
+    }
+
+    /// Returns the bit indices of `pointer`.
+    fn indices(&self, pointer: *mut ()) -> [usize; 2] {
+        let hash = hash(pointer);
+        let mask = (1 << self.shift) - 1;
+        [
+            (hash >> (u64::BITS - self.shift)) as usize,
+            ((hash >> 16) & mask) as usize,
+        ]
+    }
+}
+
+/// Hashes a pointer. The high bits of the result are the most uniformly distributed.
+pub(crate) fn hash(pointer: *mut ()) -> u64 {
+    // Fibonacci hashing. The low bits of pointers are mostly zero due to alignment.
+    ((pointer as usize as u64) >> 3).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::BloomFilter;
+
+    // the filter should never rule out a pointer it contains, and should rule out most of the
+    // others.
+    #[test]
+    fn no_false_negatives() {
+        let pointers = (1..1024usize)
+            .map(|i| (i * 16) as *mut ())
+            .collect::<HashSet<_>>();
+        let filter = BloomFilter::new(pointers.len(), pointers.iter().copied());
+        assert!(pointers.iter().all(|&p| filter.may_contain(p)));
+        let false_positives = (1024..2048usize)
+            .filter(|i| filter.may_contain((i * 16) as *mut ()))
+            .count();
+        assert!(false_positives < 1024 / 4);
+    }
+}
@@ -0,0 +1,312 @@
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ops::Deref;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize};
+use core::{array, ptr};
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize};
+
+#[cfg(feature = "global")]
+use crate::HAZARDS;
+use crate::ordering::Ordering::*;
+use crate::{Domain, Shield};
+
+/// The number of messages in a segment.
+const SEGMENT: usize = 32;
+
+/// Single-producer multi-consumer broadcast channel, where every receiver sees every message sent
+/// after it subscribed, as long as it keeps up.
+///
+/// Messages are stored in a list of segments. Once more than `max_segments` segments are live, the
+/// producer retires the oldest one, so memory stays bounded without counting references to each
+/// message. A receiver protects the segment it reads, so a slow receiver may finish reading a
+/// retired segment, and then skips to the oldest live one, missing the messages in between. So the
+/// messages must be `'static`, as a retired segment drops them at a later collection, possibly
+/// after the channel itself.
+///
+/// ```
+/// use hazard::Domain;
+/// use hazard::collections::Broadcast;
+///
+/// let domain = Domain::new();
+/// let broadcast = Broadcast::with_domain(4, &domain);
+/// let mut receiver = broadcast.receiver();
+/// let mut sender = broadcast.sender();
+/// sender.send(1);
+/// sender.send(2);
+/// assert_eq!((receiver.recv(), receiver.recv(), receiver.recv()), (Some(1), Some(2), None));
+/// ```
+#[derive(Debug)]
+pub struct Broadcast<T, D: Deref<Target = Domain> = &'static Domain> {
+    // The oldest live segment.
+    head: AtomicPtr<Segment<T>>,
+    // The index of `head`. Segments with smaller indices are retired.
+    head_index: AtomicUsize,
+    // The segment being written by the producer.
+    tail: AtomicPtr<Segment<T>>,
+    max_segments: usize,
+    // Whether a sender exists, to ensure a single producer.
+    producing: AtomicBool,
+    domain: D,
+}
+
+#[derive(Debug)]
+struct Segment<T> {
+    // The position of the segment in the channel.
+    index: usize,
+    // `messages[..written]` are initialized.
+    messages: [UnsafeCell<MaybeUninit<T>>; SEGMENT],
+    written: AtomicUsize,
+    next: AtomicPtr<Segment<T>>,
+}
+
+/// The producer of a `Broadcast`. See `Broadcast::sender`.
+#[derive(Debug)]
+pub struct Sender<'b, T, D: Deref<Target = Domain>> {
+    broadcast: &'b Broadcast<T, D>,
+}
+
+/// A consumer of a `Broadcast`. See `Broadcast::receiver`.
+#[derive(Debug)]
+pub struct Receiver<'b, T, D: Deref<Target = Domain>> {
+    broadcast: &'b Broadcast<T, D>,
+    // `shields[0]` protects `segment`, and `shields[1]` its successor while moving to it.
+    shields: [Shield<'b>; 2],
+    segment: *mut Segment<T>,
+    position: usize,
+    missed: usize,
+}
+
+unsafe impl<T: Send + Sync, D: Deref<Target = Domain> + Send> Send for Broadcast<T, D> {}
+unsafe impl<T: Send + Sync, D: Deref<Target = Domain> + Sync> Sync for Broadcast<T, D> {}
+unsafe impl<T: Send + Sync, D: Deref<Target = Domain> + Sync> Send for Receiver<'_, T, D> {}
+
+impl<T> Segment<T> {
+    fn new(index: usize) -> *mut Self {
+        Box::into_raw(Box::new(Self {
+            index,
+            messages: array::from_fn(|_| UnsafeCell::new(MaybeUninit::uninit())),
+            written: AtomicUsize::new(0),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }))
+    }
+}
+
+impl<T> Drop for Segment<T> {
+    fn drop(&mut self) {
+        let written = self.written.load(Relaxed);
+        for message in &mut self.messages[..written] {
+            unsafe { message.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+#[cfg(feature = "global")]
+impl<T: 'static> Broadcast<T> {
+    /// Creates a new channel in the default domain, keeping at most `max_segments` segments live.
+    pub fn new(max_segments: usize) -> Self {
+        Self::with_domain(max_segments, &HAZARDS)
+    }
+}
+
+impl<T: 'static, D: Deref<Target = Domain>> Broadcast<T, D> {
+    /// Creates a new channel whose segments are retired to `domain`, keeping at most
+    /// `max_segments` segments live. Must be positive.
+    pub fn with_domain(max_segments: usize, domain: D) -> Self {
+        assert!(max_segments > 0, "`max_segments` must be positive");
+        let segment = Segment::new(0);
+        Self {
+            head: AtomicPtr::new(segment),
+            head_index: AtomicUsize::new(0),
+            tail: AtomicPtr::new(segment),
+            max_segments,
+            producing: AtomicBool::new(false),
+            domain,
+        }
+    }
+
+    /// Returns the producer of the channel. Panics if another sender exists.
+    pub fn sender(&self) -> Sender<'_, T, D> {
+        assert!(
+            !self.producing.swap(true, Acquire),
+            "`Broadcast` has multiple senders at once"
+        );
+        Sender { broadcast: self }
+    }
+
+    /// Returns a consumer receiving the messages sent from now on.
+    pub fn receiver(&self) -> Receiver<'_, T, D> {
+        let shields = [(); 2].map(|_| Shield::new(self.domain.hazards()));
+        // The tail is retired only after it is not the tail.
+        let segment = shields[0].protect(&self.tail);
+        Receiver {
+            broadcast: self,
+            shields,
+            segment,
+            position: unsafe { (*segment).written.load(Acquire) },
+            missed: 0,
+        }
+    }
+}
+
+impl<T, D: Deref<Target = Domain>> Drop for Broadcast<T, D> {
+    fn drop(&mut self) {
+        let mut segment = self.head.load(Relaxed);
+        while !segment.is_null() {
+            let next = unsafe { (*segment).next.load(Relaxed) };
+            drop(unsafe { Box::from_raw(segment) });
+            segment = next;
+        }
+    }
+}
+
+impl<T: Send + 'static, D: Deref<Target = Domain>> Sender<'_, T, D> {
+    /// Sends `value` to all the receivers.
+    pub fn send(&mut self, value: T) {
+        let broadcast = self.broadcast;
+        // Only the sender changes the tail and the head.
+        let mut tail = broadcast.tail.load(Relaxed);
+        let mut written = unsafe { (*tail).written.load(Relaxed) };
+        if written == SEGMENT {
+            let new = Segment::new(unsafe { (*tail).index } + 1);
+            unsafe { (*tail).next.store(new, Release) };
+            broadcast.tail.store(new, Release);
+            (tail, written) = (new, 0);
+            self.retire_old(unsafe { (*new).index });
+        }
+        unsafe { (*(*tail).messages[written].get()).write(value) };
+        unsafe { (*tail).written.store(written + 1, Release) };
+    }
+
+    /// Retires the oldest segments while more than `max_segments` are live.
+    fn retire_old(&mut self, tail_index: usize) {
+        let broadcast = self.broadcast;
+        loop {
+            let head = broadcast.head.load(Relaxed);
+            if tail_index - unsafe { (*head).index } < broadcast.max_segments {
+                return;
+            }
+            let next = unsafe { (*head).next.load(Relaxed) };
+            broadcast.head.store(next, Release);
+            broadcast.head_index.store(unsafe { (*next).index }, SeqCst);
+            // SAFETY: `head` is unlinked, and receivers moving to it check `head_index`.
+            unsafe { broadcast.domain.retire(head) };
+        }
+    }
+}
+
+impl<T, D: Deref<Target = Domain>> Drop for Sender<'_, T, D> {
+    fn drop(&mut self) {
+        self.broadcast.producing.store(false, Release);
+    }
+}
+
+impl<T: Clone, D: Deref<Target = Domain>> Receiver<'_, T, D> {
+    /// Receives the next message, or returns `None` if it is not sent yet.
+    pub fn recv(&mut self) -> Option<T> {
+        loop {
+            // SAFETY: `segment` is protected by `shields[0]`.
+            let segment = unsafe { &*self.segment };
+            if self.position < segment.written.load(Acquire) {
+                let message = unsafe { (*segment.messages[self.position].get()).assume_init_ref() };
+                self.position += 1;
+                return Some(message.clone());
+            }
+            if self.position < SEGMENT {
+                return None;
+            }
+            let next = segment.next.load(Acquire);
+            if next.is_null() {
+                return None;
+            }
+            let _ = self.shields[1].set(next);
+            // `next` is not retired if the head has not moved past it, as it is retired only after
+            // the head moves.
+            if self.broadcast.head_index.load(SeqCst) > segment.index + 1 {
+                self.skip_to_head();
+            } else {
+                self.segment = next;
+                self.position = 0;
+                self.shields.swap(0, 1);
+            }
+            self.shields[1].clear();
+        }
+    }
+
+    /// Moves to the oldest live segment after falling behind, counting the messages missed.
+    fn skip_to_head(&mut self) {
+        let head = self.shields[1].protect(&self.broadcast.head);
+        let index = unsafe { (*self.segment).index };
+        // the messages in the rest of this segment are received, and the skipped ones missed.
+        self.missed += (unsafe { (*head).index } - index - 1) * SEGMENT;
+        self.segment = head;
+        self.position = 0;
+        self.shields.swap(0, 1);
+    }
+
+    /// Returns the number of messages missed by falling behind the retired segments.
+    pub fn missed(&self) -> usize {
+        self.missed
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::thread::scope;
+
+    use super::{Broadcast, SEGMENT};
+    use crate::Domain;
+
+    // every receiver sees every message in order while keeping up.
+    #[test]
+    fn broadcast() {
+        const RECEIVERS: usize = 4;
+        const MESSAGES: usize = SEGMENT * 16;
+
+        let domain = Domain::builder().threshold(4).build();
+        let broadcast = Broadcast::with_domain(MESSAGES / SEGMENT + 1, &domain);
+        scope(|s| {
+            for _ in 0..RECEIVERS {
+                let mut receiver = broadcast.receiver();
+                let _ = s.spawn(move || {
+                    for i in 0..MESSAGES {
+                        let message = loop {
+                            if let Some(message) = receiver.recv() {
+                                break message;
+                            }
+                        };
+                        assert_eq!(message, Box::new(i));
+                    }
+                    assert_eq!(receiver.missed(), 0);
+                });
+            }
+            let mut sender = broadcast.sender();
+            for i in 0..MESSAGES {
+                sender.send(Box::new(i));
+            }
+        });
+    }
+
+    // a receiver falling behind skips the retired segments, and frees the one it read on moving on.
+    #[test]
+    fn lagging_receiver() {
+        let domain = Domain::builder().threshold(1).build();
+        let broadcast = Broadcast::with_domain(2, &domain);
+        let mut receiver = broadcast.receiver();
+        let mut sender = broadcast.sender();
+        for i in 0..SEGMENT * 4 {
+            sender.send(i);
+        }
+        // the segment protected by the receiver stays, and the other retired one is freed.
+        assert_eq!(domain.pending_objects(), 1);
+        for i in (0..SEGMENT).chain(SEGMENT * 2..SEGMENT * 4) {
+            assert_eq!(receiver.recv(), Some(i));
+        }
+        assert_eq!(receiver.missed(), SEGMENT);
+        assert_eq!(receiver.recv(), None);
+        domain.collect();
+        assert_eq!(domain.pending_objects(), 0);
+    }
+}
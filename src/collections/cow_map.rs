@@ -0,0 +1,190 @@
+use core::borrow::Borrow;
+use core::hash::Hash;
+use core::ops::Deref;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::AtomicPtr;
+use std::collections::HashMap;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicPtr;
+
+#[cfg(feature = "global")]
+use crate::HAZARDS;
+use crate::ordering::Ordering::*;
+use crate::{Domain, Protect, Reclaimer};
+
+/// Copy-on-write map for read-mostly tables.
+///
+/// Readers access an immutable snapshot protected by a single guard. Writers clone the snapshot,
+/// modify the clone and swap it in, retiring the old one. So reads never block nor retry, while
+/// each write costs a copy of the whole map.
+///
+/// The keys and values must be `'static`, as an old snapshot is dropped by a later collection,
+/// possibly after the map itself and anything borrowed by its entries.
+///
+/// ```compile_fail
+/// use hazard::collections::HpCowMap;
+///
+/// let key = String::from("key");
+/// let map = HpCowMap::new();
+/// let _ = map.insert(key.as_str(), 1);
+/// ```
+///
+/// ```
+/// use hazard::collections::HpCowMap;
+///
+/// let routes = HpCowMap::new();
+/// let _ = routes.insert("/", 80);
+/// assert_eq!(routes.get("/"), Some(80));
+/// assert_eq!(routes.read(|map| map.len()), 1);
+/// ```
+#[derive(Debug)]
+pub struct HpCowMap<K, V, R: Deref<Target: Reclaimer> = &'static Domain> {
+    snapshot: AtomicPtr<HashMap<K, V>>,
+    reclaimer: R,
+}
+
+unsafe impl<K: Send + Sync, V: Send + Sync, R: Deref<Target: Reclaimer> + Send> Send
+    for HpCowMap<K, V, R>
+{
+}
+unsafe impl<K: Send + Sync, V: Send + Sync, R: Deref<Target: Reclaimer> + Sync> Sync
+    for HpCowMap<K, V, R>
+{
+}
+
+#[cfg(feature = "global")]
+impl<K: 'static, V: 'static> HpCowMap<K, V> {
+    /// Creates a new map in the default domain.
+    pub fn new() -> Self {
+        Self::with_reclaimer(&HAZARDS)
+    }
+}
+
+#[cfg(feature = "global")]
+impl<K: 'static, V: 'static> Default for HpCowMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: 'static, V: 'static, R: Deref<Target: Reclaimer>> HpCowMap<K, V, R> {
+    /// Creates a new map whose snapshots are reclaimed by `reclaimer`.
+    pub fn with_reclaimer(reclaimer: R) -> Self {
+        Self {
+            snapshot: AtomicPtr::new(reclaimer.alloc(HashMap::new())),
+            reclaimer,
+        }
+    }
+
+    /// Returns the reclaimer of this map.
+    pub fn reclaimer(&self) -> &R::Target {
+        &self.reclaimer
+    }
+
+    /// Runs `f` with the current snapshot of the map.
+    pub fn read<T>(&self, f: impl FnOnce(&HashMap<K, V>) -> T) -> T {
+        let guard = self.reclaimer.guard();
+        let snapshot = guard.protect(&self.snapshot);
+        // SAFETY: the snapshot is always valid, and protected & validated.
+        f(unsafe { &*snapshot })
+    }
+}
+
+impl<K: Clone + Eq + Hash + 'static, V: Clone + 'static, R: Deref<Target: Reclaimer>>
+    HpCowMap<K, V, R>
+{
+    /// Returns a clone of the value of `key`, if any.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.read(|map| map.get(key).cloned())
+    }
+
+    /// Replaces the map with a clone modified by `f`, retrying `f` on a fresh clone if another
+    /// writer swaps the map in between. Returns the result of the successful `f`.
+    pub fn update<T>(&self, mut f: impl FnMut(&mut HashMap<K, V>) -> T) -> T {
+        let guard = self.reclaimer.guard();
+        loop {
+            let current = guard.protect(&self.snapshot);
+            // SAFETY: the snapshot is always valid, and protected & validated.
+            let mut map = unsafe { &*current }.clone();
+            let result = f(&mut map);
+            let new = self.reclaimer.alloc(map);
+            match self
+                .snapshot
+                .compare_exchange(current, new, AcqRel, Relaxed)
+            {
+                Ok(_) => {
+                    // SAFETY: old snapshots are only read, so they can be freed by any thread.
+                    unsafe { self.reclaimer.retire(current) };
+                    return result;
+                }
+                Err(_) => unsafe { self.reclaimer.dealloc(new) },
+            }
+        }
+    }
+
+    /// Inserts `value` for `key`, returning the previous value if any.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.update(|map| map.insert(key.clone(), value.clone()))
+    }
+
+    /// Removes `key`, returning its value if any.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.update(|map| map.remove(key))
+    }
+}
+
+impl<K, V, R: Deref<Target: Reclaimer>> Drop for HpCowMap<K, V, R> {
+    fn drop(&mut self) {
+        #[cfg(not(feature = "check-loom"))]
+        let snapshot = *self.snapshot.get_mut();
+        #[cfg(feature = "check-loom")]
+        let snapshot = self.snapshot.load(Relaxed);
+        unsafe { self.reclaimer.dealloc(snapshot) };
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::thread::scope;
+
+    use super::HpCowMap;
+    use crate::Domain;
+
+    const THREADS: usize = 8;
+    const ITER: usize = 256;
+
+    // concurrent writers do not lose updates, and readers see consistent snapshots.
+    #[test]
+    fn concurrent_updates() {
+        let domain = Domain::builder().threshold(8).build();
+        let map = HpCowMap::with_reclaimer(&domain);
+        scope(|s| {
+            for t in 0..THREADS {
+                let map = &map;
+                let _ = s.spawn(move || {
+                    for i in 0..ITER {
+                        assert_eq!(map.insert((t, i), i), None);
+                        assert_eq!(map.get(&(t, i)), Some(i));
+                    }
+                });
+            }
+            let _ = s.spawn(|| {
+                for _ in 0..ITER {
+                    map.read(|m| assert!(m.iter().all(|(&(_, i), &v)| i == v)));
+                }
+            });
+        });
+        assert_eq!(map.read(|m| m.len()), THREADS * ITER);
+        assert_eq!(map.remove(&(0, 0)), Some(0));
+        assert_eq!(map.get(&(0, 0)), None);
+    }
+}
@@ -0,0 +1,431 @@
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::ops::Deref;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::AtomicPtr;
+use core::{iter, mem};
+use std::collections::hash_map::RandomState;
+use std::sync::Arc;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicPtr;
+
+#[cfg(feature = "global")]
+use crate::HAZARDS;
+use crate::ordering::Ordering::*;
+use crate::{Domain, Protect, Reclaimer};
+
+/// The number of hash bits consumed by each level.
+const BITS: u32 = 5;
+
+/// Hash array mapped trie, whose versions share the unchanged subtrees.
+///
+/// Writers copy the path from the root to the changed leaf, and swap the root in with a CAS,
+/// retiring the old root. Readers protect the root with a single guard, and traverse the trie
+/// without touching reference counts. `snapshot` takes a version that stays unchanged by later
+/// writes, at the cost of a reference count.
+///
+/// The keys and values must be `'static`, as a retired root drops them at a later collection,
+/// possibly after the map itself.
+///
+/// ```
+/// use hazard::collections::Hamt;
+///
+/// let map = Hamt::new();
+/// let _ = map.insert("a", 1);
+/// let snapshot = map.snapshot();
+/// let _ = map.insert("b", 2);
+/// assert_eq!((map.get("b"), snapshot.get("b")), (Some(2), None));
+/// assert_eq!(snapshot.len(), 1);
+/// ```
+#[derive(Debug)]
+pub struct Hamt<K, V, R: Deref<Target: Reclaimer> = &'static Domain> {
+    root: AtomicPtr<Arc<Node<K, V>>>,
+    hasher: RandomState,
+    reclaimer: R,
+}
+
+/// A version of a `Hamt`, unchanged by later writes. See `Hamt::snapshot`.
+#[derive(Debug, Clone)]
+pub struct HamtSnapshot<K, V> {
+    root: Arc<Node<K, V>>,
+    hasher: RandomState,
+}
+
+#[derive(Debug, Clone)]
+enum Node<K, V> {
+    // `children` are ordered by their indices, which are the set bits of `bitmap`.
+    Branch {
+        bitmap: u32,
+        children: Vec<Arc<Node<K, V>>>,
+    },
+    // The entries whose keys have the same `hash`.
+    Leaf {
+        hash: u64,
+        entries: Vec<(K, V)>,
+    },
+}
+
+unsafe impl<K: Send + Sync, V: Send + Sync, R: Deref<Target: Reclaimer> + Send> Send
+    for Hamt<K, V, R>
+{
+}
+unsafe impl<K: Send + Sync, V: Send + Sync, R: Deref<Target: Reclaimer> + Sync> Sync
+    for Hamt<K, V, R>
+{
+}
+
+/// Returns the index of `hash` in a branch at `shift`.
+fn index(hash: u64, shift: u32) -> u32 {
+    ((hash >> shift) & ((1 << BITS) - 1)) as u32
+}
+
+impl<K, V> Node<K, V> {
+    fn empty() -> Self {
+        Self::Branch {
+            bitmap: 0,
+            children: Vec::new(),
+        }
+    }
+
+    fn get<Q>(&self, hash: u64, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let mut node = self;
+        let mut shift = 0;
+        loop {
+            match node {
+                Self::Branch { bitmap, children } => {
+                    let bit = 1 << index(hash, shift);
+                    if bitmap & bit == 0 {
+                        return None;
+                    }
+                    node = &children[(bitmap & (bit - 1)).count_ones() as usize];
+                    shift += BITS;
+                }
+                Self::Leaf { hash: h, entries } => {
+                    return (*h == hash)
+                        .then(|| entries.iter().find(|(k, _)| k.borrow() == key))
+                        .flatten()
+                        .map(|(_, v)| v);
+                }
+            }
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        let mut stack = vec![self];
+        let mut entries = [].iter();
+        iter::from_fn(move || {
+            loop {
+                if let Some((k, v)) = entries.next() {
+                    return Some((k, v));
+                }
+                match stack.pop()? {
+                    Self::Branch { children, .. } => stack.extend(children.iter().map(|c| &**c)),
+                    Self::Leaf { entries: e, .. } => entries = e.iter(),
+                }
+            }
+        })
+    }
+}
+
+impl<K: Clone + Eq, V: Clone> Node<K, V> {
+    /// Returns a copy of `node` with `key` set to `value`, and the previous value.
+    fn insert(node: &Arc<Self>, hash: u64, shift: u32, key: K, value: V) -> (Self, Option<V>) {
+        match &**node {
+            Self::Branch { bitmap, children } => {
+                let bit = 1 << index(hash, shift);
+                let position = (bitmap & (bit - 1)).count_ones() as usize;
+                let mut children = children.clone();
+                if bitmap & bit == 0 {
+                    let leaf = Self::Leaf {
+                        hash,
+                        entries: vec![(key, value)],
+                    };
+                    children.insert(position, Arc::new(leaf));
+                    let bitmap = bitmap | bit;
+                    return (Self::Branch { bitmap, children }, None);
+                }
+                let (child, previous) =
+                    Self::insert(&children[position], hash, shift + BITS, key, value);
+                children[position] = Arc::new(child);
+                let bitmap = *bitmap;
+                (Self::Branch { bitmap, children }, previous)
+            }
+            Self::Leaf { hash: h, entries } if *h == hash => {
+                let mut entries = entries.clone();
+                let previous = match entries.iter_mut().find(|(k, _)| *k == key) {
+                    Some((_, v)) => Some(mem::replace(v, value)),
+                    None => {
+                        entries.push((key, value));
+                        None
+                    }
+                };
+                (Self::Leaf { hash, entries }, previous)
+            }
+            // the hashes differ at this level or below, so split the leaf into a branch.
+            &Self::Leaf { hash: h, .. } => {
+                let branch = Arc::new(Self::Branch {
+                    bitmap: 1 << index(h, shift),
+                    children: vec![node.clone()],
+                });
+                Self::insert(&branch, hash, shift, key, value)
+            }
+        }
+    }
+
+    /// Returns a copy of `node` without `key`, or `None` if it becomes empty, and the removed
+    /// value. Returns `None` if `key` is absent.
+    fn remove<Q>(node: &Self, hash: u64, shift: u32, key: &Q) -> Option<(Option<Self>, V)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        match node {
+            Self::Branch { bitmap, children } => {
+                let bit = 1 << index(hash, shift);
+                if bitmap & bit == 0 {
+                    return None;
+                }
+                let position = (bitmap & (bit - 1)).count_ones() as usize;
+                let (child, value) = Self::remove(&children[position], hash, shift + BITS, key)?;
+                let mut children = children.clone();
+                let mut bitmap = *bitmap;
+                match child {
+                    Some(child) => children[position] = Arc::new(child),
+                    None => {
+                        let _ = children.remove(position);
+                        bitmap &= !bit;
+                    }
+                }
+                // a branch below the root holding a single leaf collapses into it.
+                let node = match &*children {
+                    [] if shift > 0 => None,
+                    [leaf] if shift > 0 && matches!(**leaf, Self::Leaf { .. }) => {
+                        Some((**leaf).clone())
+                    }
+                    _ => Some(Self::Branch { bitmap, children }),
+                };
+                Some((node, value))
+            }
+            Self::Leaf { hash: h, entries } => {
+                let position = (*h == hash)
+                    .then(|| entries.iter().position(|(k, _)| k.borrow() == key))
+                    .flatten()?;
+                let mut entries = entries.clone();
+                let (_, value) = entries.remove(position);
+                let node = (!entries.is_empty()).then_some(Self::Leaf { hash, entries });
+                Some((node, value))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "global")]
+impl<K: 'static, V: 'static> Hamt<K, V> {
+    /// Creates a new map in the default domain.
+    pub fn new() -> Self {
+        Self::with_reclaimer(&HAZARDS)
+    }
+}
+
+#[cfg(feature = "global")]
+impl<K: 'static, V: 'static> Default for Hamt<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: 'static, V: 'static, R: Deref<Target: Reclaimer>> Hamt<K, V, R> {
+    /// Creates a new map whose roots are reclaimed by `reclaimer`.
+    pub fn with_reclaimer(reclaimer: R) -> Self {
+        Self {
+            root: AtomicPtr::new(reclaimer.alloc(Arc::new(Node::empty()))),
+            hasher: RandomState::new(),
+            reclaimer,
+        }
+    }
+
+    /// Returns the reclaimer of this map.
+    pub fn reclaimer(&self) -> &R::Target {
+        &self.reclaimer
+    }
+
+    /// Returns the current version of the map.
+    pub fn snapshot(&self) -> HamtSnapshot<K, V> {
+        let guard = self.reclaimer.guard();
+        let root = guard.protect(&self.root);
+        HamtSnapshot {
+            // SAFETY: the root is always valid, and protected & validated.
+            root: unsafe { &*root }.clone(),
+            hasher: self.hasher.clone(),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, R: Deref<Target: Reclaimer>> Hamt<K, V, R> {
+    /// Returns a clone of the value of `key`, if any.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        let guard = self.reclaimer.guard();
+        let root = guard.protect(&self.root);
+        // SAFETY: the root is always valid, and protected & validated. It owns the whole trie.
+        unsafe { &*root }
+            .get(self.hasher.hash_one(key), key)
+            .cloned()
+    }
+}
+
+impl<K, V, R> Hamt<K, V, R>
+where
+    K: Clone + Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    R: Deref<Target: Reclaimer>,
+{
+    /// Replaces the root with the one returned by `f`, retrying `f` on the fresh root if another
+    /// writer swaps the root in between. `f` returns `None` to leave the map unchanged.
+    fn update<T>(&self, mut f: impl FnMut(&Arc<Node<K, V>>) -> (Option<Node<K, V>>, T)) -> T {
+        let guard = self.reclaimer.guard();
+        loop {
+            let current = guard.protect(&self.root);
+            // SAFETY: the root is always valid, and protected & validated.
+            let (root, result) = f(unsafe { &*current });
+            let Some(root) = root else {
+                return result;
+            };
+            let new = self.reclaimer.alloc(Arc::new(root));
+            match self.root.compare_exchange(current, new, AcqRel, Relaxed) {
+                Ok(_) => {
+                    // SAFETY: the nodes are only read, and `K` and `V` are `Send + Sync + 'static`,
+                    // so the old root can be freed by any thread at any later time.
+                    unsafe { self.reclaimer.retire(current) };
+                    return result;
+                }
+                Err(_) => unsafe { self.reclaimer.dealloc(new) },
+            }
+        }
+    }
+
+    /// Inserts `value` for `key`, returning the previous value if any.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let hash = self.hasher.hash_one(&key);
+        self.update(|root| {
+            let (root, previous) = Node::insert(root, hash, 0, key.clone(), value.clone());
+            (Some(root), previous)
+        })
+    }
+
+    /// Removes `key`, returning its value if any.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.hasher.hash_one(key);
+        self.update(|root| match Node::remove(root, hash, 0, key) {
+            Some((root, value)) => (Some(root.unwrap_or_else(Node::empty)), Some(value)),
+            None => (None, None),
+        })
+    }
+}
+
+impl<K, V, R: Deref<Target: Reclaimer>> Drop for Hamt<K, V, R> {
+    fn drop(&mut self) {
+        #[cfg(not(feature = "check-loom"))]
+        let root = *self.root.get_mut();
+        #[cfg(feature = "check-loom")]
+        let root = self.root.load(Relaxed);
+        unsafe { self.reclaimer.dealloc(root) };
+    }
+}
+
+impl<K: Hash + Eq, V> HamtSnapshot<K, V> {
+    /// Returns the value of `key` in this version, if any.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.root.get(self.hasher.hash_one(key), key)
+    }
+}
+
+impl<K, V> HamtSnapshot<K, V> {
+    /// Returns an iterator over the entries of this version, in an unspecified order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.root.iter()
+    }
+
+    /// Returns the number of entries in this version, by visiting all of them.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Returns `true` if this version has no entry.
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::thread::scope;
+
+    use super::Hamt;
+    use crate::Domain;
+
+    const THREADS: usize = 8;
+    const ITER: usize = 256;
+
+    // concurrent writers do not lose updates, and removing every key empties the trie.
+    #[test]
+    fn concurrent_updates() {
+        let domain = Domain::builder().threshold(8).build();
+        let map = Hamt::with_reclaimer(&domain);
+        scope(|s| {
+            for t in 0..THREADS {
+                let map = &map;
+                let _ = s.spawn(move || {
+                    for i in 0..ITER {
+                        assert_eq!(map.insert((t, i), i), None);
+                        assert_eq!(map.get(&(t, i)), Some(i));
+                    }
+                });
+            }
+        });
+        let snapshot = map.snapshot();
+        assert_eq!(snapshot.len(), THREADS * ITER);
+        assert!(snapshot.iter().all(|(&(_, i), &v)| i == v));
+        for t in 0..THREADS {
+            for i in 0..ITER {
+                assert_eq!(map.remove(&(t, i)), Some(i));
+            }
+        }
+        assert_eq!(map.remove(&(0, 0)), None);
+        assert!(map.snapshot().is_empty());
+    }
+
+    // a snapshot keeps its version, sharing the unchanged subtrees with later ones.
+    #[test]
+    fn snapshot_isolation() {
+        let domain = Domain::new();
+        let map = Hamt::with_reclaimer(&domain);
+        for i in 0..ITER {
+            let _ = map.insert(i, i);
+        }
+        let snapshot = map.snapshot();
+        assert_eq!(map.insert(0, 1), Some(0));
+        assert_eq!(map.remove(&1), Some(1));
+        domain.collect();
+        assert_eq!((snapshot.get(&0), snapshot.get(&1)), (Some(&0), Some(&1)));
+        assert_eq!((map.get(&0), map.get(&1)), (Some(1), None));
+        assert_eq!(snapshot.len(), ITER);
+    }
+}
@@ -0,0 +1,25 @@
+//! Concurrent data structures whose nodes are reclaimed with hazard pointers.
+//!
+//! The data structures are generic over the [`Reclaimer`](crate::Reclaimer). Each data structure
+//! uses the default domain `HAZARDS` unless constructed with another reclaimer, either borrowed
+//! (e.g. `&Domain`) or shared (e.g. `Arc<Domain>`).
+
+mod broadcast;
+mod cow_map;
+mod hamt;
+mod mpsc;
+mod persistent;
+mod queue;
+mod radix;
+mod ring;
+mod stack;
+
+pub use broadcast::{Broadcast, Receiver, Sender};
+pub use cow_map::HpCowMap;
+pub use hamt::{Hamt, HamtSnapshot};
+pub use mpsc::{Linked, MpscQueue};
+pub use persistent::{ListSnapshot, PersistentList};
+pub use queue::Queue;
+pub use radix::RadixTree;
+pub use ring::RingBuffer;
+pub use stack::Stack;
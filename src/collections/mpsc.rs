@@ -0,0 +1,217 @@
+use core::ops::Deref;
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicBool, AtomicPtr};
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicBool, AtomicPtr};
+
+#[cfg(feature = "global")]
+use crate::HAZARDS;
+use crate::ordering::Ordering::*;
+use crate::{Domain, Shield};
+
+/// A node of an [`MpscQueue`], which embeds the link to the next node.
+///
+/// # Safety
+///
+/// `next` must always return the same link, which is accessed only by the queue while the node is
+/// in it.
+pub unsafe trait Linked: Sized {
+    /// Returns the link to the next node.
+    fn next(&self) -> &AtomicPtr<Self>;
+}
+
+/// Intrusive multi-producer single-consumer queue of nodes owned by the callers, e.g. the mailbox
+/// of an actor, after Vyukov.
+///
+/// The consumer visits each node in place, and keeps it as the front of the queue until it visits
+/// the next one. Then the node is retired to the domain, as other threads may still be reading
+/// it, e.g. in `is_empty`. So the nodes must be `'static`, as they are dropped by a later
+/// collection, possibly after the queue itself.
+///
+/// ```
+/// use std::sync::atomic::AtomicPtr;
+/// use hazard::Domain;
+/// use hazard::collections::{Linked, MpscQueue};
+///
+/// #[derive(Default)]
+/// struct Message {
+///     value: usize,
+///     next: AtomicPtr<Message>,
+/// }
+///
+/// unsafe impl Linked for Message {
+///     fn next(&self) -> &AtomicPtr<Self> {
+///         &self.next
+///     }
+/// }
+///
+/// let domain = Domain::new();
+/// let queue = MpscQueue::with_domain(Box::default(), &domain);
+/// queue.push(Box::new(Message { value: 1, ..Default::default() }));
+/// assert_eq!(queue.pop_with(|message| message.value), Some(1));
+/// assert!(queue.is_empty());
+/// ```
+#[derive(Debug)]
+pub struct MpscQueue<T: Linked, D: Deref<Target = Domain> = &'static Domain> {
+    // The node visited last by the consumer, or the initial stub node.
+    head: AtomicPtr<T>,
+    tail: AtomicPtr<T>,
+    // Whether a consumer is popping, to detect concurrent consumers.
+    consuming: AtomicBool,
+    domain: D,
+}
+
+unsafe impl<T: Linked + Send + Sync, D: Deref<Target = Domain> + Send> Send for MpscQueue<T, D> {}
+unsafe impl<T: Linked + Send + Sync, D: Deref<Target = Domain> + Sync> Sync for MpscQueue<T, D> {}
+
+#[cfg(feature = "global")]
+impl<T: Linked + 'static> MpscQueue<T> {
+    /// Creates a new queue in the default domain, starting with the `stub` node that is never
+    /// visited.
+    pub fn new(stub: Box<T>) -> Self {
+        Self::with_domain(stub, &HAZARDS)
+    }
+}
+
+impl<T: Linked + 'static, D: Deref<Target = Domain>> MpscQueue<T, D> {
+    /// Creates a new queue whose nodes are retired to `domain`, starting with the `stub` node that
+    /// is never visited.
+    pub fn with_domain(stub: Box<T>, domain: D) -> Self {
+        let stub = Box::into_raw(stub);
+        unsafe { (*stub).next() }.store(ptr::null_mut(), Relaxed);
+        Self {
+            head: AtomicPtr::new(stub),
+            tail: AtomicPtr::new(stub),
+            consuming: AtomicBool::new(false),
+            domain,
+        }
+    }
+
+    /// Links `node` at the back of the queue. Any thread may push.
+    pub fn push(&self, node: Box<T>) {
+        let node = Box::into_raw(node);
+        unsafe { (*node).next() }.store(ptr::null_mut(), Relaxed);
+        let prev = self.tail.swap(node, AcqRel);
+        // SAFETY: `prev` is not retired until its link is set here, as the consumer stops at the
+        // null link.
+        unsafe { (*prev).next() }.store(node, Release);
+    }
+
+    /// Visits the front node of the queue with `f` and returns the result, or `None` if the queue
+    /// is empty or a producer has not linked the next node yet. The previous front node is
+    /// retired.
+    ///
+    /// Panics if another thread is popping at the same time.
+    pub fn pop_with<U>(&self, f: impl FnOnce(&T) -> U) -> Option<U> {
+        assert!(
+            !self.consuming.swap(true, Acquire),
+            "`MpscQueue` is popped by multiple consumers at once"
+        );
+        let shield = Shield::new(self.domain.hazards());
+        let head = self.head.load(Relaxed);
+        // SAFETY: only the consumer retires `head`.
+        let next = shield.protect(unsafe { (*head).next() });
+        let result = (!next.is_null()).then(|| {
+            self.head.store(next, Release);
+            // SAFETY: `head` is unlinked, and only the consumer retires it once.
+            unsafe { self.domain.retire(head) };
+            // SAFETY: `next` is the front now, which is not retired until the next pop.
+            f(unsafe { &*next })
+        });
+        self.consuming.store(false, Release);
+        result
+    }
+
+    /// Returns `true` if the queue has no node to pop. Any thread may call this.
+    pub fn is_empty(&self) -> bool {
+        let shield = Shield::new(self.domain.hazards());
+        let head = shield.protect(&self.head);
+        // SAFETY: `head` is protected, and always valid as it is stored with valid nodes only.
+        unsafe { (*head).next() }.load(Acquire).is_null()
+    }
+}
+
+impl<T: Linked, D: Deref<Target = Domain>> Drop for MpscQueue<T, D> {
+    fn drop(&mut self) {
+        let mut node = self.head.load(Relaxed);
+        while !node.is_null() {
+            let next = unsafe { (*node).next() }.load(Relaxed);
+            drop(unsafe { Box::from_raw(node) });
+            node = next;
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering::Relaxed};
+    use std::thread::scope;
+
+    use super::{Linked, MpscQueue};
+    use crate::Domain;
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    struct Message {
+        value: usize,
+        next: AtomicPtr<Message>,
+    }
+
+    impl Message {
+        fn new(value: usize) -> Box<Self> {
+            Box::new(Self {
+                value,
+                next: AtomicPtr::default(),
+            })
+        }
+    }
+
+    impl Drop for Message {
+        fn drop(&mut self) {
+            let _ = DROPS.fetch_add(1, Relaxed);
+        }
+    }
+
+    unsafe impl Linked for Message {
+        fn next(&self) -> &AtomicPtr<Self> {
+            &self.next
+        }
+    }
+
+    // the messages of each producer are popped in order, and every node is freed.
+    #[test]
+    fn producers_consumer() {
+        const THREADS: usize = 4;
+        const ITER: usize = 1024;
+
+        let domain = Domain::builder().threshold(16).build();
+        let queue = MpscQueue::with_domain(Message::new(0), &domain);
+        scope(|s| {
+            for thread in 0..THREADS {
+                let queue = &queue;
+                let _ = s.spawn(move || {
+                    for i in 0..ITER {
+                        queue.push(Message::new(thread * ITER + i));
+                    }
+                });
+            }
+            let mut last = [None; THREADS];
+            let mut popped = 0;
+            while popped < THREADS * ITER {
+                let Some(value) = queue.pop_with(|message| message.value) else {
+                    continue;
+                };
+                let (thread, i) = (value / ITER, value % ITER);
+                assert!(last[thread].is_none_or(|last| last < i));
+                last[thread] = Some(i);
+                popped += 1;
+            }
+        });
+        assert!(queue.is_empty() && queue.pop_with(|_| ()).is_none());
+        drop(queue);
+        drop(domain);
+        assert_eq!(DROPS.load(Relaxed), THREADS * ITER + 1);
+    }
+}
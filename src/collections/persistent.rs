@@ -0,0 +1,266 @@
+use core::iter;
+use core::ops::Deref;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::AtomicPtr;
+use std::sync::Arc;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicPtr;
+
+#[cfg(feature = "global")]
+use crate::HAZARDS;
+use crate::ordering::Ordering::*;
+use crate::{Domain, Protect, Reclaimer};
+
+/// Persistent singly linked list, whose versions share their tails.
+///
+/// Each write builds a new version from the current one and swaps it in, retiring the old version
+/// once it is unreachable. Readers protect the current version with a single guard, so they read
+/// a consistent version while writers proceed. `snapshot` keeps a version beyond the guard.
+///
+/// The values must be `'static`, as a retired version drops them at a later collection, possibly
+/// after the list itself.
+///
+/// ```
+/// use hazard::collections::PersistentList;
+///
+/// let list = PersistentList::new();
+/// list.push_front(1);
+/// let snapshot = list.snapshot();
+/// list.push_front(2);
+/// assert_eq!(list.read(|version| version.iter().copied().collect::<Vec<_>>()), [2, 1]);
+/// assert_eq!(snapshot.iter().collect::<Vec<_>>(), [&1]);
+/// ```
+#[derive(Debug)]
+pub struct PersistentList<T, R: Deref<Target: Reclaimer> = &'static Domain> {
+    version: AtomicPtr<ListSnapshot<T>>,
+    reclaimer: R,
+}
+
+/// An immutable version of a `PersistentList`, which is cheap to clone and to extend.
+#[derive(Debug)]
+pub struct ListSnapshot<T> {
+    head: Option<Arc<Node<T>>>,
+    len: usize,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    value: T,
+    next: Option<Arc<Node<T>>>,
+}
+
+unsafe impl<T: Send + Sync, R: Deref<Target: Reclaimer> + Send> Send for PersistentList<T, R> {}
+unsafe impl<T: Send + Sync, R: Deref<Target: Reclaimer> + Sync> Sync for PersistentList<T, R> {}
+
+impl<T> Drop for Node<T> {
+    /// Drops the unshared tail iteratively, so that long lists do not overflow the stack.
+    fn drop(&mut self) {
+        let mut next = self.next.take();
+        while let Some(node) = next.and_then(Arc::into_inner) {
+            next = { node }.next.take();
+        }
+    }
+}
+
+impl<T> Clone for ListSnapshot<T> {
+    fn clone(&self) -> Self {
+        Self {
+            head: self.head.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<T> Default for ListSnapshot<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ListSnapshot<T> {
+    /// Returns the empty version.
+    pub const fn new() -> Self {
+        Self { head: None, len: 0 }
+    }
+
+    /// Returns the number of values.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if there is no value.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the first value, if any.
+    pub fn first(&self) -> Option<&T> {
+        self.head.as_deref().map(|node| &node.value)
+    }
+
+    /// Returns a version with `value` in front of this one, sharing this one as its tail.
+    pub fn push_front(&self, value: T) -> Self {
+        let next = self.head.clone();
+        Self {
+            head: Some(Arc::new(Node { value, next })),
+            len: self.len + 1,
+        }
+    }
+
+    /// Returns this version without its first value, which is itself if empty.
+    pub fn tail(&self) -> Self {
+        Self {
+            head: self.head.as_ref().and_then(|node| node.next.clone()),
+            len: self.len.saturating_sub(1),
+        }
+    }
+
+    /// Returns an iterator over the values from the front.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let mut node = self.head.as_deref();
+        iter::from_fn(move || {
+            let current = node?;
+            node = current.next.as_deref();
+            Some(&current.value)
+        })
+    }
+}
+
+#[cfg(feature = "global")]
+impl<T: 'static> PersistentList<T> {
+    /// Creates a new empty list in the default domain.
+    pub fn new() -> Self {
+        Self::with_reclaimer(&HAZARDS)
+    }
+}
+
+#[cfg(feature = "global")]
+impl<T: 'static> Default for PersistentList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: 'static, R: Deref<Target: Reclaimer>> PersistentList<T, R> {
+    /// Creates a new empty list whose versions are reclaimed by `reclaimer`.
+    pub fn with_reclaimer(reclaimer: R) -> Self {
+        Self {
+            version: AtomicPtr::new(reclaimer.alloc(ListSnapshot::new())),
+            reclaimer,
+        }
+    }
+
+    /// Returns the reclaimer of this list.
+    pub fn reclaimer(&self) -> &R::Target {
+        &self.reclaimer
+    }
+
+    /// Runs `f` with the current version of the list.
+    pub fn read<U>(&self, f: impl FnOnce(&ListSnapshot<T>) -> U) -> U {
+        let guard = self.reclaimer.guard();
+        let version = guard.protect(&self.version);
+        // SAFETY: the version is always valid, and protected & validated.
+        f(unsafe { &*version })
+    }
+
+    /// Returns the current version, which stays valid after later writes.
+    pub fn snapshot(&self) -> ListSnapshot<T> {
+        self.read(ListSnapshot::clone)
+    }
+}
+
+impl<T: Send + Sync + 'static, R: Deref<Target: Reclaimer>> PersistentList<T, R> {
+    /// Replaces the current version with the one returned by `f`, retrying `f` on the fresh
+    /// version if another writer swaps it in between. Returns the result of the successful `f`.
+    pub fn update<U>(&self, mut f: impl FnMut(&ListSnapshot<T>) -> (ListSnapshot<T>, U)) -> U {
+        let guard = self.reclaimer.guard();
+        loop {
+            let current = guard.protect(&self.version);
+            // SAFETY: the version is always valid, and protected & validated.
+            let (version, result) = f(unsafe { &*current });
+            let new = self.reclaimer.alloc(version);
+            match self.version.compare_exchange(current, new, AcqRel, Relaxed) {
+                Ok(_) => {
+                    // SAFETY: versions are only read, and `T` is `Send + Sync + 'static`, so the
+                    // old version can be freed by any thread at any later time.
+                    unsafe { self.reclaimer.retire(current) };
+                    return result;
+                }
+                Err(_) => unsafe { self.reclaimer.dealloc(new) },
+            }
+        }
+    }
+
+    /// Pushes `value` at the front of the list.
+    pub fn push_front(&self, value: T)
+    where
+        T: Clone,
+    {
+        self.update(|version| (version.push_front(value.clone()), ()))
+    }
+
+    /// Pops the front value of the list, if any.
+    pub fn pop_front(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.update(|version| (version.tail(), version.first().cloned()))
+    }
+}
+
+impl<T, R: Deref<Target: Reclaimer>> Drop for PersistentList<T, R> {
+    fn drop(&mut self) {
+        #[cfg(not(feature = "check-loom"))]
+        let version = *self.version.get_mut();
+        #[cfg(feature = "check-loom")]
+        let version = self.version.load(Relaxed);
+        unsafe { self.reclaimer.dealloc(version) };
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::thread::scope;
+
+    use super::{ListSnapshot, PersistentList};
+    use crate::Domain;
+
+    const THREADS: usize = 8;
+    const ITER: usize = 256;
+
+    // readers iterate consistent versions while writers push and pop.
+    #[test]
+    fn snapshot_iteration() {
+        let domain = Domain::builder().threshold(8).build();
+        let list = PersistentList::with_reclaimer(&domain);
+        scope(|s| {
+            for _ in 0..THREADS {
+                let _ = s.spawn(|| {
+                    for i in 0..ITER {
+                        list.push_front(i);
+                        let snapshot = list.snapshot();
+                        assert_eq!(snapshot.iter().count(), snapshot.len());
+                        assert!(list.pop_front().is_some());
+                    }
+                });
+            }
+        });
+        assert!(list.read(|version| version.is_empty()));
+    }
+
+    // dropping a long version does not overflow the stack.
+    #[test]
+    fn drop_long() {
+        let domain = Domain::new();
+        let list = PersistentList::with_reclaimer(&domain);
+        list.update(|_| {
+            let version = (0..1 << 20).fold(ListSnapshot::new(), |v, i| v.push_front(i));
+            (version, ())
+        });
+        assert_eq!(
+            list.read(|version| version.first().copied()),
+            Some((1 << 20) - 1)
+        );
+    }
+}
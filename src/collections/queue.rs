@@ -0,0 +1,210 @@
+use core::mem::MaybeUninit;
+use core::ops::Deref;
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::AtomicPtr;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicPtr;
+
+#[cfg(feature = "global")]
+use crate::HAZARDS;
+use crate::ordering::Ordering::*;
+use crate::{Domain, Protect, Reclaimer};
+
+/// Michael-Scott queue.
+#[derive(Debug)]
+pub struct Queue<T, R: Deref<Target: Reclaimer> = &'static Domain> {
+    head: AtomicPtr<Node<T>>,
+    tail: AtomicPtr<Node<T>>,
+    reclaimer: R,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    data: MaybeUninit<T>,
+    next: AtomicPtr<Node<T>>,
+}
+
+unsafe impl<T: Send, R: Deref<Target: Reclaimer> + Sync> Sync for Queue<T, R> {}
+unsafe impl<T: Send, R: Deref<Target: Reclaimer> + Send> Send for Queue<T, R> {}
+
+#[cfg(feature = "global")]
+impl<T> Queue<T> {
+    /// Creates a new queue in the default domain.
+    pub fn new() -> Self {
+        Self::with_reclaimer(&HAZARDS)
+    }
+}
+
+#[cfg(feature = "global")]
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, R: Deref<Target: Reclaimer>> Queue<T, R> {
+    /// Creates a new queue whose nodes are reclaimed by `reclaimer`.
+    pub fn with_reclaimer(reclaimer: R) -> Self {
+        let sentinel = reclaimer.alloc(Node {
+            data: MaybeUninit::uninit(),
+            next: AtomicPtr::default(),
+        });
+
+        Self {
+            head: AtomicPtr::new(sentinel),
+            tail: AtomicPtr::new(sentinel),
+            reclaimer,
+        }
+    }
+
+    /// Returns the reclaimer of this queue.
+    pub fn reclaimer(&self) -> &R::Target {
+        &self.reclaimer
+    }
+
+    /// Pushes `t` at the back of the queue.
+    pub fn push(&self, t: T) {
+        let new = self.reclaimer.alloc(Node {
+            data: MaybeUninit::new(t),
+            next: AtomicPtr::default(),
+        });
+        let shield = self.reclaimer.guard();
+
+        loop {
+            let tail = shield.protect(&self.tail);
+            // SAFETY
+            // 1. queue's `tail` is always valid as it will be CASed with valid nodes only.
+            // 2. `tail` is protected & validated.
+            let tail_ref = unsafe { &*tail };
+
+            let next = tail_ref.next.load(Acquire);
+            if !next.is_null() {
+                let _ = self.tail.compare_exchange(tail, next, Release, Relaxed);
+                continue;
+            }
+
+            if tail_ref
+                .next
+                .compare_exchange(ptr::null_mut(), new, Release, Relaxed)
+                .is_ok()
+            {
+                let _ = self.tail.compare_exchange(tail, new, Release, Relaxed);
+                break;
+            }
+        }
+    }
+
+    /// Pops the front of the queue, if any.
+    pub fn try_pop(&self) -> Option<T> {
+        let head_shield = self.reclaimer.guard();
+        let next_shield = self.reclaimer.guard();
+        let mut head = self.head.load(Acquire);
+        loop {
+            if let Err(err) = head_shield.try_protect(head, &self.head) {
+                head = err.observed();
+                continue;
+            }
+            // SAFETY:
+            // 1. queue's `head` is always valid as it will be CASed with valid nodes only.
+            // 2. `head` is protected & validated.
+            let head_ref = unsafe { &*head };
+
+            let next = head_ref.next.load(Acquire);
+            if next.is_null() {
+                return None;
+            }
+            next_shield.set(next);
+            let next_ref = match head_shield.validate(head, &self.head) {
+                Ok(_) => {
+                    // SAFETY:
+                    // 1. If `next` was not null, then it must be a valid node that another
+                    //    thread has `push()`ed.
+                    // 2. Validation: If `head` is not retired, then `next` is not retired. So
+                    //    re-validating `head` also validates `next.
+                    unsafe { &*next }
+                }
+                Err(err) => {
+                    head = err.observed();
+                    continue;
+                }
+            };
+
+            let tail = self.tail.load(Relaxed);
+            if tail == head {
+                let _ = self.tail.compare_exchange(tail, next, Release, Relaxed);
+            }
+
+            match self.head.compare_exchange(head, next, Release, Relaxed) {
+                Ok(_) => {
+                    let result = unsafe { next_ref.data.assume_init_read() };
+                    // the node does not drop `data`, so it can be freed by any thread.
+                    unsafe { self.reclaimer.retire(head) };
+                    return Some(result);
+                }
+                Err(new) => head = new,
+            }
+        }
+    }
+}
+
+impl<T, R: Deref<Target: Reclaimer>> Drop for Queue<T, R> {
+    fn drop(&mut self) {
+        #[cfg(not(feature = "check-loom"))]
+        let sentinel = *self.head.get_mut();
+        #[cfg(feature = "check-loom")]
+        let sentinel = self.head.load(Relaxed);
+
+        let mut o_curr = unsafe { (*sentinel).next.load(Relaxed) };
+        unsafe { self.reclaimer.dealloc(sentinel) };
+        while !o_curr.is_null() {
+            let curr = unsafe { &mut *o_curr };
+            drop(unsafe { curr.data.assume_init_read() });
+            let next = curr.next.load(Relaxed);
+            unsafe { self.reclaimer.dealloc(o_curr) };
+            o_curr = next;
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::thread::scope;
+
+    use super::Queue;
+    use crate::Domain;
+
+    const THREADS: usize = 8;
+    const ITER: usize = 1024;
+
+    // a queue in a private domain should work as in the default domain.
+    #[test]
+    fn private_domain() {
+        let domain = Domain::builder().threshold(8).build();
+        let queue = Queue::with_reclaimer(&domain);
+        scope(|s| {
+            for _ in 0..THREADS {
+                let _ = s.spawn(|| {
+                    for i in 0..ITER {
+                        queue.push(i);
+                        assert!(queue.try_pop().is_some());
+                    }
+                });
+            }
+        });
+        assert!(queue.try_pop().is_none());
+    }
+
+    // values are popped in FIFO order.
+    #[cfg(feature = "global")]
+    #[test]
+    fn fifo() {
+        let queue = Queue::new();
+        for i in 0..16 {
+            queue.push(i);
+        }
+        assert!((0..16).all(|i| queue.try_pop() == Some(i)));
+        assert_eq!(queue.try_pop(), None);
+    }
+}
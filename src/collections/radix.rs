@@ -0,0 +1,505 @@
+use core::ops::Deref;
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU8};
+#[cfg(not(feature = "check-loom"))]
+use std::sync::Mutex;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::Mutex;
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicBool, AtomicPtr, AtomicU8};
+
+#[cfg(feature = "global")]
+use crate::HAZARDS;
+use crate::ordering::Ordering::*;
+use crate::{Domain, Protect, Reclaimer};
+
+/// The most keys of a node with sorted keys.
+const SORTED: usize = 16;
+/// The most keys of a node with an index of its keys.
+const INDEXED: usize = 48;
+
+/// Adaptive radix tree mapping byte strings to values, in the order of the keys.
+///
+/// Each node branches on one byte of the key, and stores its children in the smallest of three
+/// layouts: sorted keys for up to 16 children, an index of up to 48 children, or 256 direct links.
+/// A node that outgrows its layout, or whose sorted keys change, is replaced by a copy and
+/// retired. Readers protect the nodes along the path hand over hand, and never block, while
+/// writers are serialized. Integer keys keep their order as big-endian bytes, e.g.
+/// `u64::to_be_bytes`.
+///
+/// Removing a key retires its value, but keeps the nodes on its path. The values must be
+/// `'static`, as retired values and nodes are dropped by a later collection, possibly after the
+/// tree itself.
+///
+/// ```
+/// use hazard::collections::RadixTree;
+///
+/// let tree = RadixTree::new();
+/// for key in ["apple", "apricot", "banana"] {
+///     let _ = tree.insert(key, key.len());
+/// }
+/// assert_eq!(tree.get("apple"), Some(5));
+/// let mut keys = Vec::new();
+/// tree.scan_prefix("ap", |key, _| keys.push(key.to_vec()));
+/// assert_eq!(keys, [b"apple".to_vec(), b"apricot".to_vec()]);
+/// ```
+#[derive(Debug)]
+pub struct RadixTree<V, R: Deref<Target: Reclaimer> = &'static Domain> {
+    // The root branches on the first byte, and is never replaced.
+    root: Node<V>,
+    writer: Mutex<()>,
+    reclaimer: R,
+}
+
+#[derive(Debug)]
+struct Node<V> {
+    // The value of the key ending at this node.
+    value: AtomicPtr<V>,
+    children: Children<V>,
+    // Whether the node is replaced, so that its links may be outdated.
+    obsolete: AtomicBool,
+}
+
+#[derive(Debug)]
+enum Children<V> {
+    Sorted {
+        keys: Box<[u8]>,
+        links: Box<[AtomicPtr<Node<V>>]>,
+    },
+    // `index[byte]` is one plus the position of the link of `byte`, and zero if absent.
+    Indexed {
+        index: Box<[AtomicU8]>,
+        links: Box<[AtomicPtr<Node<V>>]>,
+        len: AtomicU8,
+    },
+    Direct {
+        links: Box<[AtomicPtr<Node<V>>]>,
+    },
+}
+
+/// The guard of the reclaimer `R`.
+type Guard<'r, R> = <<R as Deref>::Target as Reclaimer>::Guard<'r>;
+
+/// A reader found a node replaced, and restarts from the root.
+struct Restart;
+
+unsafe impl<V: Send + Sync, R: Deref<Target: Reclaimer> + Send> Send for RadixTree<V, R> {}
+unsafe impl<V: Send + Sync, R: Deref<Target: Reclaimer> + Sync> Sync for RadixTree<V, R> {}
+
+fn links<V>(len: usize) -> Box<[AtomicPtr<Node<V>>]> {
+    (0..len).map(|_| AtomicPtr::new(ptr::null_mut())).collect()
+}
+
+impl<V> Node<V> {
+    /// Returns a node with `value` and `children` sorted by their bytes, in the smallest layout.
+    fn new(value: *mut V, children: Vec<(u8, *mut Node<V>)>) -> Self {
+        let children = match children.len() {
+            len if len <= SORTED => Children::Sorted {
+                keys: children.iter().map(|&(byte, _)| byte).collect(),
+                links: children
+                    .iter()
+                    .map(|&(_, child)| AtomicPtr::new(child))
+                    .collect(),
+            },
+            len if len <= INDEXED => {
+                let index = (0..256).map(|_| AtomicU8::new(0)).collect::<Box<[_]>>();
+                let links = links(INDEXED);
+                for (position, &(byte, child)) in children.iter().enumerate() {
+                    index[byte as usize].store(position as u8 + 1, Relaxed);
+                    links[position].store(child, Relaxed);
+                }
+                let len = AtomicU8::new(len as u8);
+                Children::Indexed { index, links, len }
+            }
+            _ => {
+                let links = links(256);
+                for (byte, child) in children {
+                    links[byte as usize].store(child, Relaxed);
+                }
+                Children::Direct { links }
+            }
+        };
+        Self {
+            value: AtomicPtr::new(value),
+            children,
+            obsolete: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the link to the child of `byte`, which may be null, or `None` if there is none.
+    fn link(&self, byte: u8) -> Option<&AtomicPtr<Node<V>>> {
+        match &self.children {
+            Children::Sorted { keys, links } => keys.binary_search(&byte).ok().map(|i| &links[i]),
+            Children::Indexed { index, links, .. } => {
+                let position = index[byte as usize].load(Acquire);
+                (position != 0).then(|| &links[position as usize - 1])
+            }
+            Children::Direct { links } => Some(&links[byte as usize]),
+        }
+    }
+
+    /// Returns the links to the children in the order of their bytes.
+    fn links(&self) -> Vec<(u8, &AtomicPtr<Node<V>>)> {
+        match &self.children {
+            Children::Sorted { keys, links } => keys.iter().copied().zip(links.iter()).collect(),
+            Children::Indexed { index, links, .. } => (0..=u8::MAX)
+                .filter_map(|byte| {
+                    let position = index[byte as usize].load(Acquire);
+                    (position != 0).then(|| (byte, &links[position as usize - 1]))
+                })
+                .collect(),
+            Children::Direct { links } => (0..=u8::MAX).zip(links.iter()).collect(),
+        }
+    }
+
+    /// Links `child` for `byte` in place if the layout has room, or returns `false`. Only the
+    /// writer calls this.
+    fn try_add(&self, byte: u8, child: *mut Node<V>) -> bool {
+        match &self.children {
+            Children::Sorted { .. } => false,
+            Children::Indexed { index, links, len } => {
+                let position = len.load(Relaxed);
+                if position as usize == INDEXED {
+                    return false;
+                }
+                links[position as usize].store(child, Release);
+                index[byte as usize].store(position + 1, Release);
+                len.store(position + 1, Relaxed);
+                true
+            }
+            Children::Direct { links } => {
+                links[byte as usize].store(child, Release);
+                true
+            }
+        }
+    }
+
+    /// Returns a copy of this node with `child` added for `byte`. Only the writer calls this.
+    fn with_child(&self, byte: u8, child: *mut Node<V>) -> Self {
+        let mut children = self
+            .links()
+            .into_iter()
+            .map(|(b, link)| (b, link.load(Relaxed)))
+            .filter(|(_, child)| !child.is_null())
+            .collect::<Vec<_>>();
+        let position = children.partition_point(|&(b, _)| b < byte);
+        children.insert(position, (byte, child));
+        Self::new(self.value.load(Relaxed), children)
+    }
+}
+
+#[cfg(feature = "global")]
+impl<V: 'static> RadixTree<V> {
+    /// Creates a new tree in the default domain.
+    pub fn new() -> Self {
+        Self::with_reclaimer(&HAZARDS)
+    }
+}
+
+#[cfg(feature = "global")]
+impl<V: 'static> Default for RadixTree<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V: 'static, R: Deref<Target: Reclaimer>> RadixTree<V, R> {
+    /// Creates a new tree whose nodes and values are reclaimed by `reclaimer`.
+    pub fn with_reclaimer(reclaimer: R) -> Self {
+        Self {
+            root: Node {
+                value: AtomicPtr::new(ptr::null_mut()),
+                children: Children::Direct { links: links(256) },
+                obsolete: AtomicBool::new(false),
+            },
+            writer: Mutex::new(()),
+            reclaimer,
+        }
+    }
+
+    /// Returns the reclaimer of this tree.
+    pub fn reclaimer(&self) -> &R::Target {
+        &self.reclaimer
+    }
+
+    /// Returns a clone of the value of `key`, if any.
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Option<V>
+    where
+        V: Clone,
+    {
+        let shields = [self.reclaimer.guard(), self.reclaimer.guard()];
+        loop {
+            if let Ok(value) = self.try_get(key.as_ref(), &shields) {
+                return value;
+            }
+        }
+    }
+
+    fn try_get<G: Protect>(&self, key: &[u8], shields: &[G; 2]) -> Result<Option<V>, Restart>
+    where
+        V: Clone,
+    {
+        let mut node = &self.root;
+        // the parent of `node` stays protected by the other shield while protecting its child.
+        for (depth, &byte) in key.iter().enumerate() {
+            let Some(link) = node.link(byte) else {
+                return Ok(None);
+            };
+            let child = shields[depth % 2].protect(link);
+            if node.obsolete.load(SeqCst) {
+                return Err(Restart);
+            }
+            if child.is_null() {
+                return Ok(None);
+            }
+            // SAFETY: `child` is protected, and validated as `node` is not replaced.
+            node = unsafe { &*child };
+        }
+        let value = shields[key.len() % 2].protect(&node.value);
+        if node.obsolete.load(SeqCst) {
+            return Err(Restart);
+        }
+        // SAFETY: `value` is protected, and validated as `node` is not replaced.
+        Ok(unsafe { value.as_ref() }.cloned())
+    }
+
+    /// Visits the entries whose keys start with `prefix` in the order of the keys.
+    pub fn scan_prefix(&self, prefix: impl AsRef<[u8]>, mut f: impl FnMut(&[u8], &V)) {
+        let prefix = prefix.as_ref();
+        let mut shields = Vec::new();
+        let mut key = Vec::new();
+        // the last key visited, to skip the visited keys after a restart.
+        let mut last = None;
+        loop {
+            key.clear();
+            if self
+                .try_scan(prefix, &mut key, &mut shields, &mut last, &mut f)
+                .is_ok()
+            {
+                return;
+            }
+        }
+    }
+
+    fn try_scan<'r>(
+        &'r self,
+        prefix: &[u8],
+        key: &mut Vec<u8>,
+        shields: &mut Vec<Guard<'r, R>>,
+        last: &mut Option<Vec<u8>>,
+        f: &mut impl FnMut(&[u8], &V),
+    ) -> Result<(), Restart> {
+        let mut node = &self.root;
+        for &byte in prefix {
+            let Some(link) = node.link(byte) else {
+                return Ok(());
+            };
+            let Some(child) = self.protect_child(node, link, key.len(), shields)? else {
+                return Ok(());
+            };
+            key.push(byte);
+            node = child;
+        }
+        self.scan_node(node, key, shields, last, f)
+    }
+
+    /// Protects the child of `node` loaded from `link` with the shield of `depth`.
+    fn protect_child<'r, 'n>(
+        &'r self,
+        node: &Node<V>,
+        link: &AtomicPtr<Node<V>>,
+        depth: usize,
+        shields: &mut Vec<Guard<'r, R>>,
+    ) -> Result<Option<&'n Node<V>>, Restart> {
+        while shields.len() <= depth {
+            shields.push(self.reclaimer.guard());
+        }
+        let child = shields[depth].protect(link);
+        if node.obsolete.load(SeqCst) {
+            return Err(Restart);
+        }
+        // SAFETY: `child` is protected, and validated as `node` is not replaced. The caller keeps
+        // it protected while using it.
+        Ok(unsafe { child.as_ref() })
+    }
+
+    fn scan_node<'r>(
+        &'r self,
+        node: &Node<V>,
+        key: &mut Vec<u8>,
+        shields: &mut Vec<Guard<'r, R>>,
+        last: &mut Option<Vec<u8>>,
+        f: &mut impl FnMut(&[u8], &V),
+    ) -> Result<(), Restart> {
+        let depth = key.len();
+        if last.as_deref().is_none_or(|last| &key[..] > last) {
+            let guard = self.reclaimer.guard();
+            let value = guard.protect(&node.value);
+            if node.obsolete.load(SeqCst) {
+                return Err(Restart);
+            }
+            // SAFETY: `value` is protected, and validated as `node` is not replaced.
+            if let Some(value) = unsafe { value.as_ref() } {
+                f(key, value);
+                *last = Some(key.clone());
+            }
+        }
+        for (byte, link) in node.links() {
+            if let Some(child) = self.protect_child(node, link, depth, shields)? {
+                key.push(byte);
+                self.scan_node(child, key, shields, last, f)?;
+                let _ = key.pop();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<V: Send + 'static, R: Deref<Target: Reclaimer>> RadixTree<V, R> {
+    /// Sets the value of `key` to `value`. Returns `true` if `key` had a value, which is retired.
+    pub fn insert(&self, key: impl AsRef<[u8]>, value: V) -> bool {
+        let _writer = self.writer.lock().unwrap();
+        let mut node = &self.root;
+        // the link to `node`, which is `None` for the root.
+        let mut link_to = None;
+        for &byte in key.as_ref() {
+            let child = match node.link(byte) {
+                Some(link) if !link.load(Relaxed).is_null() => link.load(Relaxed),
+                _ => {
+                    let child = self.reclaimer.alloc(Node::new(ptr::null_mut(), Vec::new()));
+                    if !node.try_add(byte, child) {
+                        let link: &AtomicPtr<_> =
+                            link_to.expect("the root always has room for its children");
+                        let new = self.reclaimer.alloc(node.with_child(byte, child));
+                        link.store(new, Release);
+                        node.obsolete.store(true, SeqCst);
+                        // SAFETY: `node` is unlinked above, and its children and value are moved
+                        // to `new`, which only the writer changes from now on.
+                        unsafe {
+                            self.reclaimer
+                                .retire(node as *const Node<V> as *mut Node<V>)
+                        };
+                        // SAFETY: `new` is allocated above, and only the writer retires it.
+                        node = unsafe { &*new };
+                    }
+                    child
+                }
+            };
+            link_to = node.link(byte);
+            // SAFETY: only the writer retires the nodes.
+            node = unsafe { &*child };
+        }
+        let old = node.value.swap(self.reclaimer.alloc(value), AcqRel);
+        if old.is_null() {
+            return false;
+        }
+        // SAFETY: `old` is unlinked by the swap above, and only the writer retires it.
+        unsafe { self.reclaimer.retire(old) };
+        true
+    }
+
+    /// Removes the value of `key`, which is retired. Returns `true` if `key` had a value.
+    pub fn remove(&self, key: impl AsRef<[u8]>) -> bool {
+        let _writer = self.writer.lock().unwrap();
+        let mut node = &self.root;
+        for &byte in key.as_ref() {
+            let Some(child) = node.link(byte).map(|link| link.load(Relaxed)) else {
+                return false;
+            };
+            if child.is_null() {
+                return false;
+            }
+            // SAFETY: only the writer retires the nodes.
+            node = unsafe { &*child };
+        }
+        let old = node.value.swap(ptr::null_mut(), AcqRel);
+        if old.is_null() {
+            return false;
+        }
+        // SAFETY: `old` is unlinked by the swap above, and only the writer retires it.
+        unsafe { self.reclaimer.retire(old) };
+        true
+    }
+}
+
+impl<V, R: Deref<Target: Reclaimer>> RadixTree<V, R> {
+    /// Frees the value and the descendants of `node`.
+    fn free(&self, node: &Node<V>) {
+        let value = node.value.load(Relaxed);
+        if !value.is_null() {
+            unsafe { self.reclaimer.dealloc(value) };
+        }
+        for (_, link) in node.links() {
+            let child = link.load(Relaxed);
+            if !child.is_null() {
+                self.free(unsafe { &*child });
+                unsafe { self.reclaimer.dealloc(child) };
+            }
+        }
+    }
+}
+
+impl<V, R: Deref<Target: Reclaimer>> Drop for RadixTree<V, R> {
+    fn drop(&mut self) {
+        self.free(&self.root);
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::thread::scope;
+
+    use super::RadixTree;
+    use crate::Domain;
+
+    const THREADS: usize = 4;
+    const ITER: u64 = 512;
+
+    // readers see the values of integer keys while a writer grows the nodes past every layout.
+    #[test]
+    fn concurrent_reads() {
+        let domain = Domain::builder().threshold(8).build();
+        let tree = RadixTree::with_reclaimer(&domain);
+        scope(|s| {
+            for _ in 0..THREADS {
+                let _ = s.spawn(|| {
+                    for i in 0..ITER {
+                        if let Some(value) = tree.get(i.to_be_bytes()) {
+                            assert_eq!(value, i);
+                        }
+                    }
+                });
+            }
+            for i in 0..ITER {
+                assert!(!tree.insert(i.to_be_bytes(), i));
+            }
+        });
+        for i in 0..ITER {
+            assert_eq!(tree.get(i.to_be_bytes()), Some(i));
+        }
+        assert!(tree.insert(0u64.to_be_bytes(), 1) && tree.remove(1u64.to_be_bytes()));
+        assert_eq!(tree.get(0u64.to_be_bytes()), Some(1));
+        assert_eq!(tree.get(1u64.to_be_bytes()), None);
+    }
+
+    // a prefix scan visits the keys in order, including a key that is a prefix of others.
+    #[test]
+    fn ordered_scan() {
+        let domain = Domain::new();
+        let tree = RadixTree::with_reclaimer(&domain);
+        for key in ["b", "ab", "a", "abc", "ac", ""] {
+            let _ = tree.insert(key, key.len());
+        }
+        let mut keys = Vec::new();
+        tree.scan_prefix("a", |key, &len| {
+            assert_eq!(key.len(), len);
+            keys.push(String::from_utf8(key.to_vec()).unwrap());
+        });
+        assert_eq!(keys, ["a", "ab", "abc", "ac"]);
+        let mut count = 0;
+        tree.scan_prefix("", |_, _| count += 1);
+        assert_eq!(count, 6);
+    }
+}
@@ -0,0 +1,269 @@
+use core::ops::Deref;
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicPtr, AtomicUsize};
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, AtomicUsize};
+
+#[cfg(feature = "global")]
+use crate::HAZARDS;
+use crate::ordering::Ordering::*;
+use crate::{Domain, Protect, Reclaimer};
+
+/// Fixed-capacity multi-producer multi-consumer ring of boxed items, after Vyukov's bounded queue.
+///
+/// Unlike `Queue`, pushing allocates only the item, and no node is retired per operation. Popped
+/// and displaced items are retired, as other threads may be reading them with `peek_with`. So the
+/// items must be `'static`, as they are dropped by a later collection, possibly after the ring.
+///
+/// ```
+/// use hazard::Domain;
+/// use hazard::collections::RingBuffer;
+///
+/// let domain = Domain::new();
+/// let ring = RingBuffer::with_reclaimer(2, &domain);
+/// assert!(ring.push(1).is_ok() && ring.push(2).is_ok());
+/// assert_eq!(ring.push(3), Err(3));
+/// assert!(ring.force_push(3));
+/// assert_eq!(ring.peek_with(|item| *item), Some(2));
+/// assert_eq!(ring.pop_with(|item| *item), Some(2));
+/// ```
+#[derive(Debug)]
+pub struct RingBuffer<T, R: Deref<Target: Reclaimer> = &'static Domain> {
+    slots: Box<[Slot<T>]>,
+    // The position of the next pop.
+    head: AtomicUsize,
+    // The position of the next push.
+    tail: AtomicUsize,
+    reclaimer: R,
+}
+
+#[derive(Debug)]
+struct Slot<T> {
+    // `position` while empty for the push at `position`, and `position + 1` while full for the pop
+    // at `position`, where the slot is at `position % capacity`.
+    sequence: AtomicUsize,
+    item: AtomicPtr<T>,
+}
+
+unsafe impl<T: Send + Sync, R: Deref<Target: Reclaimer> + Sync> Sync for RingBuffer<T, R> {}
+unsafe impl<T: Send, R: Deref<Target: Reclaimer> + Send> Send for RingBuffer<T, R> {}
+
+#[cfg(feature = "global")]
+impl<T: Send + 'static> RingBuffer<T> {
+    /// Creates a new ring of `capacity` items in the default domain.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_reclaimer(capacity, &HAZARDS)
+    }
+}
+
+impl<T: Send + 'static, R: Deref<Target: Reclaimer>> RingBuffer<T, R> {
+    /// Creates a new ring of `capacity` items, which are reclaimed by `reclaimer`. `capacity` must
+    /// be positive.
+    pub fn with_reclaimer(capacity: usize, reclaimer: R) -> Self {
+        assert!(capacity > 0, "`capacity` must be positive");
+        Self {
+            slots: (0..capacity)
+                .map(|position| Slot {
+                    sequence: AtomicUsize::new(position),
+                    item: AtomicPtr::new(ptr::null_mut()),
+                })
+                .collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            reclaimer,
+        }
+    }
+
+    /// Returns the reclaimer of this ring.
+    pub fn reclaimer(&self) -> &R::Target {
+        &self.reclaimer
+    }
+
+    /// Returns the number of items the ring holds at most.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns the number of items in the ring, which may be outdated under concurrent updates.
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Relaxed);
+        self.tail.load(Relaxed).saturating_sub(head)
+    }
+
+    /// Returns `true` if the ring has no item, which may be outdated under concurrent updates.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Pushes `value` at the back of the ring, or returns it back if the ring is full.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let mut tail = self.tail.load(Relaxed);
+        loop {
+            let slot = &self.slots[tail % self.capacity()];
+            let sequence = slot.sequence.load(Acquire);
+            if sequence == tail {
+                match self
+                    .tail
+                    .compare_exchange_weak(tail, tail + 1, Relaxed, Relaxed)
+                {
+                    Ok(_) => {
+                        slot.item.store(self.reclaimer.alloc(value), Release);
+                        slot.sequence.store(tail + 1, Release);
+                        return Ok(());
+                    }
+                    Err(current) => tail = current,
+                }
+            } else if sequence < tail {
+                // the slot still holds the item of the previous lap.
+                return Err(value);
+            } else {
+                tail = self.tail.load(Relaxed);
+            }
+        }
+    }
+
+    /// Pushes `value` at the back of the ring, displacing the front items while the ring is full.
+    /// Returns `true` if any item is displaced.
+    pub fn force_push(&self, mut value: T) -> bool {
+        let mut displaced = false;
+        loop {
+            match self.push(value) {
+                Ok(()) => return displaced,
+                Err(rejected) => value = rejected,
+            }
+            displaced |= self.pop_with(|_| ()).is_some();
+        }
+    }
+
+    /// Claims the front item of the ring, visits it with `f` and returns the result, or `None` if
+    /// the ring is empty. The item is retired afterwards.
+    pub fn pop_with<U>(&self, f: impl FnOnce(&T) -> U) -> Option<U> {
+        let mut head = self.head.load(Relaxed);
+        loop {
+            let slot = &self.slots[head % self.capacity()];
+            let sequence = slot.sequence.load(Acquire);
+            if sequence == head + 1 {
+                match self
+                    .head
+                    .compare_exchange_weak(head, head + 1, Relaxed, Relaxed)
+                {
+                    Ok(_) => {
+                        let item = slot.item.swap(ptr::null_mut(), Acquire);
+                        // the slot is free for the push of the next lap once the item is taken.
+                        slot.sequence.store(head + self.capacity(), Release);
+                        // SAFETY: `item` is unlinked by the swap above, so only this thread
+                        // retires it, and readers protect it before validating against the slot.
+                        let result = f(unsafe { &*item });
+                        unsafe { self.reclaimer.retire(item) };
+                        return Some(result);
+                    }
+                    Err(current) => head = current,
+                }
+            } else if sequence <= head {
+                // the slot is not pushed yet in this lap.
+                return None;
+            } else {
+                head = self.head.load(Relaxed);
+            }
+        }
+    }
+
+    /// Visits the front item of the ring with `f` without claiming it, and returns the result, or
+    /// `None` if the ring is empty.
+    pub fn peek_with<U>(&self, f: impl FnOnce(&T) -> U) -> Option<U> {
+        let guard = self.reclaimer.guard();
+        loop {
+            let head = self.head.load(Acquire);
+            let slot = &self.slots[head % self.capacity()];
+            let sequence = slot.sequence.load(Acquire);
+            if sequence <= head {
+                return None;
+            }
+            if sequence != head + 1 {
+                continue;
+            }
+            let item = guard.protect(&slot.item);
+            // a null item is being popped, and the head may have moved past since.
+            if item.is_null() || self.head.load(Acquire) != head {
+                continue;
+            }
+            // SAFETY: `item` is protected, and it is retired only after it is taken from the slot.
+            return Some(f(unsafe { &*item }));
+        }
+    }
+}
+
+impl<T, R: Deref<Target: Reclaimer>> Drop for RingBuffer<T, R> {
+    fn drop(&mut self) {
+        for slot in &*self.slots {
+            let item = slot.item.load(Relaxed);
+            if !item.is_null() {
+                unsafe { self.reclaimer.dealloc(item) };
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+    use std::thread::scope;
+
+    use super::RingBuffer;
+    use crate::Domain;
+
+    const THREADS: usize = 4;
+    const ITER: usize = 1024;
+
+    // every pushed item is popped once by concurrent producers and consumers.
+    #[test]
+    fn producers_consumers() {
+        let domain = Domain::builder().threshold(8).build();
+        let ring = RingBuffer::with_reclaimer(16, &domain);
+        let popped = AtomicUsize::new(0);
+        scope(|s| {
+            for _ in 0..THREADS {
+                let _ = s.spawn(|| {
+                    for i in 0..ITER {
+                        let mut item = Box::new(i);
+                        while let Err(rejected) = ring.push(item) {
+                            item = rejected;
+                        }
+                    }
+                });
+                let _ = s.spawn(|| {
+                    let mut count = 0;
+                    while count < ITER {
+                        if let Some(i) = ring.pop_with(|item| **item) {
+                            let _ = popped.fetch_add(i, Relaxed);
+                            count += 1;
+                        }
+                        let _ = ring.peek_with(|item| assert!(**item < ITER));
+                    }
+                });
+            }
+        });
+        assert!(ring.is_empty());
+        assert_eq!(popped.load(Relaxed), THREADS * ITER * (ITER - 1) / 2);
+    }
+
+    // displaced items are retired, and freed only once no reader protects them.
+    #[test]
+    fn displaced_retired() {
+        let domain = Domain::new();
+        let ring = RingBuffer::with_reclaimer(2, &domain);
+        for i in 0..2 {
+            assert!(!ring.force_push(i));
+        }
+        ring.peek_with(|_| {
+            assert!(ring.force_push(2));
+            domain.collect();
+            assert_eq!(domain.pending_objects(), 1);
+        });
+        domain.collect();
+        assert_eq!(domain.pending_objects(), 0);
+        assert_eq!((ring.len(), ring.peek_with(|item| *item)), (2, Some(1)));
+    }
+}
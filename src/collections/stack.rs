@@ -0,0 +1,163 @@
+use core::mem::MaybeUninit;
+use core::ops::Deref;
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::AtomicPtr;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicPtr;
+
+#[cfg(feature = "global")]
+use crate::HAZARDS;
+use crate::ordering::Ordering::*;
+use crate::{Domain, Protect, Reclaimer};
+
+/// Treiber's lock-free stack.
+#[derive(Debug)]
+pub struct Stack<T, R: Deref<Target: Reclaimer> = &'static Domain> {
+    head: AtomicPtr<Node<T>>,
+    reclaimer: R,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    data: MaybeUninit<T>,
+    next: *mut Node<T>,
+}
+
+unsafe impl<T: Send, R: Deref<Target: Reclaimer> + Send> Send for Stack<T, R> {}
+unsafe impl<T: Send, R: Deref<Target: Reclaimer> + Sync> Sync for Stack<T, R> {}
+
+#[cfg(feature = "global")]
+impl<T> Stack<T> {
+    /// Creates a new stack in the default domain.
+    pub fn new() -> Self {
+        Self::with_reclaimer(&HAZARDS)
+    }
+}
+
+#[cfg(feature = "global")]
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, R: Deref<Target: Reclaimer>> Stack<T, R> {
+    /// Creates a new stack whose nodes are reclaimed by `reclaimer`.
+    pub fn with_reclaimer(reclaimer: R) -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+            reclaimer,
+        }
+    }
+
+    /// Returns the reclaimer of this stack.
+    pub fn reclaimer(&self) -> &R::Target {
+        &self.reclaimer
+    }
+
+    /// Pushes `t` onto the stack.
+    pub fn push(&self, t: T) {
+        let new = self.reclaimer.alloc(Node {
+            data: MaybeUninit::new(t),
+            next: ptr::null_mut(),
+        });
+
+        let mut head = self.head.load(Relaxed);
+
+        loop {
+            unsafe { (*new).next = head };
+
+            match self.head.compare_exchange(head, new, Release, Relaxed) {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Pops the top of the stack, if any.
+    pub fn try_pop(&self) -> Option<T> {
+        let shield = self.reclaimer.guard();
+        loop {
+            let head_ptr = shield.protect(&self.head);
+            let head_ref = unsafe { head_ptr.as_ref() }?;
+
+            if self
+                .head
+                .compare_exchange(head_ptr, head_ref.next, Relaxed, Relaxed)
+                .is_ok()
+            {
+                let data = unsafe { head_ref.data.assume_init_read() };
+                // the node does not drop `data`, so it can be freed by any thread.
+                unsafe { self.reclaimer.retire(head_ptr) };
+                return Some(data);
+            }
+        }
+    }
+
+    /// Returns `true` if the stack is empty.
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Relaxed).is_null()
+    }
+}
+
+impl<T, R: Deref<Target: Reclaimer>> Drop for Stack<T, R> {
+    fn drop(&mut self) {
+        #[cfg(not(feature = "check-loom"))]
+        let mut o_curr = *self.head.get_mut();
+        #[cfg(feature = "check-loom")]
+        let mut o_curr = self.head.load(Relaxed);
+
+        while !o_curr.is_null() {
+            let curr = unsafe { &mut *o_curr };
+            drop(unsafe { curr.data.assume_init_read() });
+            let next = curr.next;
+            unsafe { self.reclaimer.dealloc(o_curr) };
+            o_curr = next;
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::sync::Arc;
+    use std::thread::scope;
+
+    use super::Stack;
+    use crate::Domain;
+
+    const THREADS: usize = 8;
+    const ITER: usize = 1024;
+
+    // a stack in a private domain should work as in the default domain.
+    #[test]
+    fn private_domain() {
+        let domain = Domain::builder().threshold(8).build();
+        let stack = Stack::with_reclaimer(&domain);
+        scope(|s| {
+            for _ in 0..THREADS {
+                let _ = s.spawn(|| {
+                    for i in 0..ITER {
+                        stack.push(i);
+                        assert!(stack.try_pop().is_some());
+                    }
+                });
+            }
+        });
+        assert!(stack.is_empty());
+    }
+
+    // a stack can share ownership of its domain.
+    #[test]
+    fn shared_domain() {
+        let domain = Arc::new(Domain::new());
+        let stack = Stack::with_reclaimer(domain.clone());
+        stack.push(1);
+        stack.push(2);
+        assert_eq!(stack.try_pop(), Some(2));
+        drop(domain);
+        assert_eq!(stack.try_pop(), Some(1));
+        assert_eq!(stack.try_pop(), None);
+    }
+}
@@ -0,0 +1,213 @@
+//! Reference-counted objects whose counts are acquired under hazard pointers.
+
+use core::fmt;
+use core::ops::Deref;
+use core::ptr::NonNull;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicPtr, AtomicUsize};
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, AtomicUsize};
+
+use super::ordering::Ordering;
+use super::{Domain, Shield};
+
+/// An atomic reference-counted pointer to `T`, reclaimed in `domain`.
+///
+/// A reader protects the object with a shield only while incrementing its count, and then holds a
+/// `CountedRef` for as long as it likes without occupying a hazard slot. The object is retired
+/// once the count drops to zero, i.e. it is neither stored nor referenced. As it is then freed by
+/// a later collection of `domain`, possibly after anything it borrows, `T` must be `'static`.
+///
+/// ```
+/// use hazard::{AtomicCounted, Domain};
+///
+/// let domain = Domain::new();
+/// let atomic = AtomicCounted::new(&domain, 1);
+/// let first = atomic.load();
+/// atomic.store(2);
+/// assert_eq!((*first, *atomic.load()), (1, 2));
+/// ```
+pub struct AtomicCounted<'d, T: Send + Sync + 'static> {
+    domain: &'d Domain,
+    pointer: AtomicPtr<Counted<T>>,
+}
+
+/// An object with the number of `AtomicCounted` and `CountedRef` referencing it.
+struct Counted<T> {
+    count: AtomicUsize,
+    value: T,
+}
+
+/// A counted reference to an object loaded from an `AtomicCounted`.
+#[must_use = "the reference is released as soon as it is dropped"]
+pub struct CountedRef<'d, T: Send + Sync + 'static> {
+    domain: &'d Domain,
+    counted: NonNull<Counted<T>>,
+}
+
+unsafe impl<T: Send + Sync + 'static> Send for AtomicCounted<'_, T> {}
+unsafe impl<T: Send + Sync + 'static> Sync for AtomicCounted<'_, T> {}
+unsafe impl<T: Send + Sync + 'static> Send for CountedRef<'_, T> {}
+unsafe impl<T: Send + Sync + 'static> Sync for CountedRef<'_, T> {}
+
+impl<'d, T: Send + Sync + 'static> AtomicCounted<'d, T> {
+    /// Creates a new atomic pointer to `value`, reclaimed in `domain`.
+    pub fn new(domain: &'d Domain, value: T) -> Self {
+        Self {
+            domain,
+            pointer: AtomicPtr::new(Counted::alloc(value)),
+        }
+    }
+
+    /// Returns a counted reference to the current object.
+    pub fn load(&self) -> CountedRef<'d, T> {
+        let shield = Shield::new(self.domain.hazards());
+        loop {
+            let pointer = shield.protect(&self.pointer);
+            // # Safety
+            // `pointer` is protected, and it is never null.
+            let counted = unsafe { &*pointer };
+            // A zero count means that the object is replaced and being retired.
+            if counted
+                .count
+                .fetch_update(Ordering::Acquire, Ordering::Relaxed, |count| {
+                    (count != 0).then_some(count + 1)
+                })
+                .is_ok()
+            {
+                return CountedRef {
+                    domain: self.domain,
+                    counted: NonNull::from(counted),
+                };
+            }
+        }
+    }
+
+    /// Replaces the current object with `value`. The replaced object is retired once it is no
+    /// longer referenced.
+    pub fn store(&self, value: T) {
+        let old = self.pointer.swap(Counted::alloc(value), Ordering::AcqRel);
+        unsafe { Counted::release(old, self.domain) };
+    }
+}
+
+impl<T: Send + Sync + 'static> Drop for AtomicCounted<'_, T> {
+    fn drop(&mut self) {
+        unsafe { Counted::release(*self.pointer.get_mut(), self.domain) };
+    }
+}
+
+impl<T: Send + Sync + fmt::Debug + 'static> fmt::Debug for AtomicCounted<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicCounted").field(&*self.load()).finish()
+    }
+}
+
+impl<T: Send + Sync + 'static> Counted<T> {
+    /// Allocates `value` with a count of 1.
+    fn alloc(value: T) -> *mut Self {
+        Box::into_raw(Box::new(Self {
+            count: AtomicUsize::new(1),
+            value,
+        }))
+    }
+
+    /// Decrements the count of `pointer`, and retires it to `domain` if it drops to zero.
+    ///
+    /// # Safety
+    ///
+    /// The caller must own one count of `pointer`.
+    unsafe fn release(pointer: *mut Self, domain: &Domain) {
+        if unsafe { &*pointer }.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            // # Safety
+            // It is no longer stored nor referenced, but may still be protected by readers
+            // failing to increment its count.
+            unsafe { domain.retire(pointer) };
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static> Deref for CountedRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &self.counted.as_ref().value }
+    }
+}
+
+impl<T: Send + Sync + 'static> Clone for CountedRef<'_, T> {
+    fn clone(&self) -> Self {
+        let _ = unsafe { self.counted.as_ref() }
+            .count
+            .fetch_add(1, Ordering::Relaxed);
+        Self {
+            domain: self.domain,
+            counted: self.counted,
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static> Drop for CountedRef<'_, T> {
+    fn drop(&mut self) {
+        unsafe { Counted::release(self.counted.as_ptr(), self.domain) };
+    }
+}
+
+impl<T: Send + Sync + fmt::Debug + 'static> fmt::Debug for CountedRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CountedRef").field(&**self).finish()
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+    use std::thread::scope;
+
+    use super::AtomicCounted;
+    use crate::Domain;
+    use crate::test::common::Tester;
+
+    // a replaced object stays alive while referenced, and is freed after the last reference.
+    #[test]
+    fn outlive_store() {
+        let freed = Arc::new(AtomicUsize::new(0));
+        let domain = Domain::builder().threshold(1).build();
+        let atomic = AtomicCounted::new(&domain, Tester(freed.clone()));
+        let first = atomic.load();
+        let second = first.clone();
+        atomic.store(Tester(freed.clone()));
+        domain.collect();
+        assert_eq!(freed.load(Relaxed), 0);
+        drop((first, second));
+        domain.collect();
+        assert_eq!(freed.load(Relaxed), 1);
+        assert!(domain.hazards().all_hazards().is_empty());
+    }
+
+    // concurrent loads and stores free every replaced object exactly once.
+    #[test]
+    fn concurrent() {
+        const THREADS: usize = 4;
+        const ITER: usize = 1024;
+        let freed = Arc::new(AtomicUsize::new(0));
+        let domain = Domain::builder().threshold(16).build();
+        let atomic = AtomicCounted::new(&domain, Tester(freed.clone()));
+        scope(|s| {
+            for _ in 0..THREADS {
+                let _ = s.spawn(|| {
+                    for _ in 0..ITER {
+                        let held = atomic.load();
+                        atomic.store(Tester(freed.clone()));
+                        drop(held);
+                    }
+                });
+            }
+        });
+        drop(atomic);
+        drop(domain);
+        assert_eq!(freed.load(Relaxed), THREADS * ITER + 1);
+    }
+}
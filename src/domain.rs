@@ -0,0 +1,1544 @@
+use core::array;
+#[cfg(feature = "owner-info")]
+use core::fmt::Write;
+use core::mem;
+use core::ops::Deref;
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicPtr, AtomicUsize};
+use std::hash::{BuildHasher, BuildHasherDefault, DefaultHasher};
+use std::sync::OnceLock;
+#[cfg(not(feature = "check-loom"))]
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, AtomicUsize};
+#[cfg(feature = "check-loom")]
+use loom::sync::{Arc, Mutex};
+
+#[cfg(feature = "global")]
+use super::HAZARDS;
+use super::backoff::Backoff;
+#[cfg(feature = "stats-histogram")]
+use super::histogram::{LatencyHistogram, Recorder};
+#[cfg(any(test, feature = "test-hooks"))]
+use super::hooks::{self, HookPoint};
+use super::incremental::{IncrementalDrop, IncrementalDrops};
+#[cfg(feature = "prometheus")]
+use super::metrics::{self, Counters};
+use super::ordering::Ordering;
+use super::quiescent::{Quiescence, Quiescent};
+use super::retire::{self, ReclaimHandle, Retired};
+use super::retired_list::RetiredList;
+use super::table::HazardTable;
+use super::{HazardBag, RetiredSet, Shield, SlotAllocator};
+
+/// Reclamation policy of a [`Domain`].
+///
+/// New options may be added in the future, so this is constructed with [`DomainBuilder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DomainConfig {
+    /// The max length of a thread-local retired pointer list. `collect` is triggered when
+    /// `threshold` pointers are retired.
+    pub threshold: usize,
+    /// `collect` is triggered only every `collect_every`-th time the threshold is exceeded. Each
+    /// time the threshold is exceeded without `collect`, another `threshold` pointers are
+    /// retired before it is exceeded again. Larger values trade memory for fewer scans.
+    pub collect_every: usize,
+    /// How long dropping a thread-local retired pointer list waits for its pointers to be
+    /// unprotected before reporting them and following `stall_policy`. `None` waits silently.
+    pub stall_timeout: Option<Duration>,
+    /// What to do after `stall_timeout`.
+    pub stall_policy: StallPolicy,
+    /// What dropping a thread-local retired pointer list does with the pointers that are still
+    /// protected, unless the list overrides it.
+    pub drop_policy: DropPolicy,
+    /// `collect` is triggered whenever the bytes pending reclamation in the domain exceed this,
+    /// regardless of `threshold` and `collect_every`. `None` for no limit.
+    pub max_pending_bytes: Option<usize>,
+    /// As `max_pending_bytes`, for the number of objects pending reclamation.
+    pub max_pending_objects: Option<usize>,
+    /// What `retire` does when `max_pending_bytes` or `max_pending_objects` is exceeded.
+    pub pending_policy: PendingPolicy,
+    /// How many parts of an object retired by `RetiredSet::retire_incremental` each `collect`
+    /// drops once it is unprotected. `None` drops such objects at once.
+    pub drop_budget: Option<usize>,
+    /// The number of shared retired pointer lists of the domain, each with its own lock. Threads
+    /// retiring to the domain directly or handing off pick one by their id, which spreads the
+    /// contention on the lock. At most `MAX_SHARDS`.
+    pub shards: usize,
+    /// How often a background thread collects the domain, e.g. to free the pointers handed off
+    /// by threads that no longer retire. This applies to the domains that may outlive the thread:
+    /// the default domain configured by `init`, the domains `register`ed and those owned by a
+    /// `DomainHandle`. `None` for no background thread.
+    pub background_reclaim: Option<Duration>,
+    /// `collect` warns about the shields holding the same protection for this long, e.g. leaked by
+    /// `mem::forget`. `None` to never warn.
+    #[cfg(feature = "watchdog")]
+    pub hold_warning: Option<Duration>,
+}
+
+/// What `retire` does when the objects pending reclamation exceed `DomainConfig::max_pending_bytes`
+/// or `DomainConfig::max_pending_objects`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum PendingPolicy {
+    /// Collect once.
+    #[default]
+    Collect,
+    /// Keep collecting and backing off until the domain is below the limits, or for at most
+    /// `DomainConfig::stall_timeout`. This bounds the memory of deferred reclamation when a slow
+    /// reader holds pointers, at the cost of stalling the retiring thread.
+    Block,
+}
+
+/// What dropping a thread-local retired pointer list, e.g. when its thread exits, does with the
+/// pointers that are still protected after collecting once. See `DomainConfig::drop_policy` and
+/// `RetiredSet::set_drop_policy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum DropPolicy {
+    /// Wait until they are unprotected, following `DomainConfig::stall_policy` after
+    /// `DomainConfig::stall_timeout`.
+    #[default]
+    Wait,
+    /// Hand them off to the domain, to be freed by the next `collect` of any thread. With this
+    /// policy, the pointers must be safe to free in any thread, as for `RetiredSet::hand_off`.
+    HandOff,
+    /// Leak them, e.g. in short-lived tools exiting anyway.
+    Leak,
+}
+
+/// A lifecycle event of a [`Domain`], passed to the hook set by `Domain::set_event_hook`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DomainEvent {
+    /// A chunk of hazard slots is allocated as no inactive slot is left, making `slots` slots in
+    /// total.
+    SlotsAllocated { slots: usize },
+    /// A collection starts scanning the hazards for `retired` pointers.
+    CollectStart { retired: usize },
+    /// A collection has freed `reclaimed` of its pointers, and kept `remaining` that are still
+    /// protected.
+    CollectEnd { reclaimed: usize, remaining: usize },
+}
+
+/// What to do with the retired pointers that stay protected for `DomainConfig::stall_timeout`
+/// when their list is dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum StallPolicy {
+    /// Keep waiting.
+    #[default]
+    Wait,
+    /// Leak the pointers.
+    Leak,
+    /// Panic. As the list is dropped when its thread exits, this aborts the process.
+    Panic,
+}
+
+impl DomainConfig {
+    /// The default value of `threshold`.
+    pub const DEFAULT_THRESHOLD: usize = 64;
+
+    /// The default value of `stall_timeout`.
+    pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// The max value of `shards`.
+    pub const MAX_SHARDS: usize = 16;
+
+    /// The default value of `hold_warning`.
+    #[cfg(feature = "watchdog")]
+    pub const DEFAULT_HOLD_WARNING: Duration = Duration::from_secs(1);
+
+    /// Returns a builder starting from the default configuration.
+    pub fn builder() -> DomainBuilder {
+        DomainBuilder::new()
+    }
+}
+
+impl Default for DomainConfig {
+    fn default() -> Self {
+        Self {
+            threshold: Self::DEFAULT_THRESHOLD,
+            collect_every: 1,
+            stall_timeout: Some(Self::DEFAULT_STALL_TIMEOUT),
+            stall_policy: StallPolicy::Wait,
+            drop_policy: DropPolicy::Wait,
+            max_pending_bytes: None,
+            max_pending_objects: None,
+            pending_policy: PendingPolicy::Collect,
+            drop_budget: None,
+            shards: 1,
+            background_reclaim: None,
+            #[cfg(feature = "watchdog")]
+            hold_warning: Some(Self::DEFAULT_HOLD_WARNING),
+        }
+    }
+}
+
+/// Builder of [`DomainConfig`] and [`Domain`]. Options that are not set keep their default.
+///
+/// ```
+/// use hazard::Domain;
+///
+/// let domain = Domain::builder().threshold(128).build();
+/// assert_eq!(domain.config().threshold, 128);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DomainBuilder {
+    config: DomainConfig,
+    allocator: SlotAllocator,
+}
+
+impl DomainBuilder {
+    /// Creates a new builder starting from the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `DomainConfig::threshold`.
+    pub fn threshold(mut self, threshold: usize) -> Self {
+        self.config.threshold = threshold;
+        self
+    }
+
+    /// Sets `DomainConfig::collect_every`. Must be positive.
+    pub fn collect_every(mut self, collect_every: usize) -> Self {
+        assert!(collect_every > 0, "`collect_every` must be positive");
+        self.config.collect_every = collect_every;
+        self
+    }
+
+    /// Sets `DomainConfig::stall_timeout`.
+    pub fn stall_timeout(mut self, stall_timeout: Option<Duration>) -> Self {
+        self.config.stall_timeout = stall_timeout;
+        self
+    }
+
+    /// Sets `DomainConfig::stall_policy`.
+    pub fn stall_policy(mut self, stall_policy: StallPolicy) -> Self {
+        self.config.stall_policy = stall_policy;
+        self
+    }
+
+    /// Sets `DomainConfig::drop_policy`.
+    pub fn drop_policy(mut self, drop_policy: DropPolicy) -> Self {
+        self.config.drop_policy = drop_policy;
+        self
+    }
+
+    /// Sets `DomainConfig::max_pending_bytes`.
+    pub fn max_pending_bytes(mut self, max_pending_bytes: Option<usize>) -> Self {
+        self.config.max_pending_bytes = max_pending_bytes;
+        self
+    }
+
+    /// Sets `DomainConfig::max_pending_objects`.
+    pub fn max_pending_objects(mut self, max_pending_objects: Option<usize>) -> Self {
+        self.config.max_pending_objects = max_pending_objects;
+        self
+    }
+
+    /// Sets `DomainConfig::pending_policy`.
+    pub fn pending_policy(mut self, pending_policy: PendingPolicy) -> Self {
+        self.config.pending_policy = pending_policy;
+        self
+    }
+
+    /// Sets `DomainConfig::drop_budget`. Must be positive.
+    pub fn drop_budget(mut self, drop_budget: Option<usize>) -> Self {
+        assert!(drop_budget != Some(0), "`drop_budget` must be positive");
+        self.config.drop_budget = drop_budget;
+        self
+    }
+
+    /// Sets `DomainConfig::shards`. Must be positive and at most `DomainConfig::MAX_SHARDS`.
+    pub fn shards(mut self, shards: usize) -> Self {
+        assert!(
+            (1..=DomainConfig::MAX_SHARDS).contains(&shards),
+            "`shards` must be positive and at most `MAX_SHARDS`"
+        );
+        self.config.shards = shards;
+        self
+    }
+
+    /// Sets `DomainConfig::background_reclaim`.
+    pub fn background_reclaim(mut self, interval: Option<Duration>) -> Self {
+        self.config.background_reclaim = interval;
+        self
+    }
+
+    /// Sets `DomainConfig::hold_warning`.
+    #[cfg(feature = "watchdog")]
+    pub fn hold_warning(mut self, hold_warning: Option<Duration>) -> Self {
+        self.config.hold_warning = hold_warning;
+        self
+    }
+
+    /// Sets the allocator of the chunks of hazard slots and of the retired pointer lists of the
+    /// domain.
+    pub fn slot_allocator(mut self, allocator: SlotAllocator) -> Self {
+        self.allocator = allocator;
+        self
+    }
+
+    /// Returns the configuration built so far.
+    pub fn config(self) -> DomainConfig {
+        self.config
+    }
+
+    /// Creates a new domain with the configuration built so far.
+    pub fn build(self) -> Domain {
+        let mut domain = Domain::with_config(self.config);
+        domain.hazards = HazardBag::with_allocator(self.allocator);
+        domain.retired = array::from_fn(|_| Mutex::new(SharedRetired::new(self.allocator)));
+        domain
+    }
+}
+
+/// A hazard pointer domain: the bag of hazard slots together with the policy used to reclaim the
+/// pointers retired against it.
+#[derive(Debug)]
+pub struct Domain {
+    hazards: HazardBag,
+    config: OnceLock<DomainConfig>,
+    /// The shards of the shared retired pointer list. See `DomainConfig::shards`.
+    retired: [Mutex<SharedRetired>; DomainConfig::MAX_SHARDS],
+    /// `fn(*mut (), usize)` called for each reclaimed object, or null.
+    reclaim_hook: AtomicPtr<()>,
+    /// Memory accounting of retired objects. See `pending_bytes`.
+    bytes: RetiredBytes,
+    /// Grace periods of the threads registered by `register_quiescent`.
+    quiescence: Quiescence,
+    /// Unprotected objects being dropped by `DomainConfig::drop_budget` parts per collection.
+    drops: IncrementalDrops,
+    /// Latencies from `retire` to free. See `reclaim_latencies`.
+    #[cfg(feature = "stats-histogram")]
+    latencies: Recorder,
+    /// Counters of `metrics_prometheus`.
+    #[cfg(feature = "prometheus")]
+    counters: Counters,
+}
+
+/// The number of bytes of objects retired to a domain.
+#[derive(Debug)]
+struct RetiredBytes {
+    pending: AtomicUsize,
+    reclaimed: AtomicUsize,
+    high_watermark: AtomicUsize,
+    // The number of objects pending reclamation.
+    objects: AtomicUsize,
+}
+
+impl RetiredBytes {
+    #[cfg(not(feature = "check-loom"))]
+    const fn new() -> Self {
+        Self {
+            pending: AtomicUsize::new(0),
+            reclaimed: AtomicUsize::new(0),
+            high_watermark: AtomicUsize::new(0),
+            objects: AtomicUsize::new(0),
+        }
+    }
+
+    #[cfg(feature = "check-loom")]
+    fn new() -> Self {
+        Self {
+            pending: AtomicUsize::new(0),
+            reclaimed: AtomicUsize::new(0),
+            high_watermark: AtomicUsize::new(0),
+            objects: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// A shard of the retired pointers shared by all threads. See `Domain::retire`.
+#[derive(Debug)]
+struct SharedRetired {
+    inner: RetiredList,
+    /// Hazards found by the last `collect`, kept to reuse its allocation. Only that of the first
+    /// shard is used.
+    hazards: HazardTable,
+}
+
+impl SharedRetired {
+    const fn new(allocator: SlotAllocator) -> Self {
+        Self {
+            inner: RetiredList::new(allocator),
+            hazards: HazardTable::new(),
+        }
+    }
+}
+
+// Retired pointers are freed by any thread collecting the domain, as required by
+// `Domain::retire`.
+unsafe impl Send for SharedRetired {}
+
+#[cfg(not(feature = "check-loom"))]
+/// Domains collected by `Domain::collect_all` besides the default domain.
+static REGISTRY: Mutex<Vec<Registered>> = Mutex::new(Vec::new());
+
+#[cfg(not(feature = "check-loom"))]
+/// An entry of `REGISTRY`.
+enum Registered {
+    /// A domain `register`ed explicitly.
+    Static(&'static Domain),
+    /// The domain owned by a `DomainHandle`, unregistered once all its handles are dropped.
+    Handle(Weak<Domain>),
+}
+
+#[cfg(not(feature = "check-loom"))]
+impl Registered {
+    fn is_alive(&self) -> bool {
+        match self {
+            Self::Static(_) => true,
+            Self::Handle(domain) => domain.strong_count() > 0,
+        }
+    }
+
+    /// Collects the domain as `collect_all`, and returns `false` if it is dropped.
+    fn collect(&self) -> bool {
+        match self {
+            Self::Static(domain) => domain.collect_many(),
+            Self::Handle(domain) => match domain.upgrade() {
+                Some(domain) => domain.collect_many(),
+                None => return false,
+            },
+        }
+        true
+    }
+
+    /// Spawns the thread of `DomainConfig::background_reclaim`, which collects the domain every
+    /// `interval` until it is dropped.
+    fn spawn_reclaimer(self, interval: Duration) {
+        let _ = thread::Builder::new()
+            .name("hazard-reclaim".into())
+            .spawn(move || {
+                loop {
+                    thread::sleep(interval);
+                    if !self.collect() {
+                        return;
+                    }
+                }
+            })
+            .expect("failed to spawn the background reclaim thread");
+    }
+}
+
+impl Domain {
+    #[cfg(not(feature = "check-loom"))]
+    /// Creates a new domain. Its configuration is fixed to the default on first use unless
+    /// `configure`d before.
+    pub const fn new() -> Self {
+        Self {
+            hazards: HazardBag::new(),
+            config: OnceLock::new(),
+            retired: [const { Mutex::new(SharedRetired::new(SlotAllocator::GLOBAL)) };
+                DomainConfig::MAX_SHARDS],
+            reclaim_hook: AtomicPtr::new(ptr::null_mut()),
+            bytes: RetiredBytes::new(),
+            quiescence: Quiescence::new(),
+            drops: IncrementalDrops::new(),
+            #[cfg(feature = "stats-histogram")]
+            latencies: Recorder::new(),
+            #[cfg(feature = "prometheus")]
+            counters: Counters::new(),
+        }
+    }
+
+    #[cfg(feature = "check-loom")]
+    /// Creates a new domain. Its configuration is fixed to the default on first use unless
+    /// `configure`d before.
+    pub fn new() -> Self {
+        Self {
+            hazards: HazardBag::new(),
+            config: OnceLock::new(),
+            retired: array::from_fn(|_| Mutex::new(SharedRetired::new(SlotAllocator::GLOBAL))),
+            reclaim_hook: AtomicPtr::new(ptr::null_mut()),
+            bytes: RetiredBytes::new(),
+            quiescence: Quiescence::new(),
+            drops: IncrementalDrops::new(),
+            #[cfg(feature = "stats-histogram")]
+            latencies: Recorder::new(),
+            #[cfg(feature = "prometheus")]
+            counters: Counters::new(),
+        }
+    }
+
+    /// Creates a new domain with the given configuration.
+    pub fn with_config(config: DomainConfig) -> Self {
+        let domain = Self::new();
+        let _ = domain.config.set(config);
+        domain
+    }
+
+    /// Runs `f` with a new domain that lives only for the call, e.g. shared by a scope of
+    /// threads. The shields and retired pointer lists of the domain borrow it, so they cannot
+    /// outlive `f`. When `f` returns, every pointer retired to the domain is freed and its slots
+    /// are released.
+    ///
+    /// ```
+    /// use std::thread;
+    /// use hazard::{Domain, DomainConfig, RetiredSet};
+    ///
+    /// Domain::scope(DomainConfig::default(), |domain| {
+    ///     thread::scope(|s| {
+    ///         for i in 0..4 {
+    ///             let _ = s.spawn(move || {
+    ///                 let mut retired = RetiredSet::new(domain);
+    ///                 unsafe { retired.retire(Box::into_raw(Box::new(i))) };
+    ///             });
+    ///         }
+    ///     });
+    /// });
+    /// ```
+    pub fn scope<R>(config: DomainConfig, f: impl FnOnce(&Domain) -> R) -> R {
+        f(&Self::with_config(config))
+    }
+
+    /// Returns a builder of a domain.
+    pub fn builder() -> DomainBuilder {
+        DomainBuilder::new()
+    }
+
+    /// Returns the bag of hazard pointers of this domain.
+    pub fn hazards(&self) -> &HazardBag {
+        &self.hazards
+    }
+
+    /// Returns the configuration of this domain.
+    pub fn config(&self) -> &DomainConfig {
+        self.config.get_or_init(DomainConfig::default)
+    }
+
+    /// Sets the configuration of this domain. Fails and returns `config` back if the domain is
+    /// already configured or used.
+    // `config` is returned back by value, so that it can be passed to another domain.
+    #[allow(clippy::result_large_err)]
+    pub fn configure(&self, config: DomainConfig) -> Result<(), DomainConfig> {
+        self.config.set(config)
+    }
+
+    /// Blocks until no shield of this domain protects `pointer`, or `timeout` elapses. See
+    /// `HazardBag::wait_until_unprotected`.
+    #[cfg(any(test, feature = "test-hooks"))]
+    pub fn wait_until_unprotected<T>(&self, pointer: *mut T, timeout: Duration) -> bool {
+        self.hazards.wait_until_unprotected(pointer, timeout)
+    }
+
+    /// Sets `hook` to be called with the address and the size of each object reclaimed in this
+    /// domain, right after it is freed, e.g. for allocator statistics. The size is 0 for pointers
+    /// retired with a custom deleter.
+    pub fn set_reclaim_hook(&self, hook: fn(*mut (), usize)) {
+        self.reclaim_hook.store(hook as *mut (), Ordering::Release);
+    }
+
+    /// Removes the hook set by `set_reclaim_hook`.
+    pub fn clear_reclaim_hook(&self) {
+        self.reclaim_hook.store(ptr::null_mut(), Ordering::Release);
+    }
+
+    /// Sets `hook` to be called on each lifecycle event of this domain, e.g. to forward them to a
+    /// logging or telemetry system. The hook runs on the thread causing the event, within the
+    /// allocation of slots or the collection, so it should be quick and must not use this domain.
+    pub fn set_event_hook(&self, hook: fn(DomainEvent)) {
+        self.hazards.set_event_hook(hook as *mut ());
+    }
+
+    /// Removes the hook set by `set_event_hook`.
+    pub fn clear_event_hook(&self) {
+        self.hazards.set_event_hook(ptr::null_mut());
+    }
+
+    /// Calls the hook set by `set_event_hook` with `event`, if any.
+    pub(crate) fn event(&self, event: DomainEvent) {
+        self.hazards.event(event);
+    }
+
+    /// Frees a retired pointer that is no longer protected, and calls the reclaim hook.
+    ///
+    /// # Safety
+    ///
+    /// See `Retired::free`.
+    pub(crate) unsafe fn free(&self, retired: Retired) {
+        unsafe { self.free_with(retired, |retired| retired.free()) };
+    }
+
+    /// Frees a retired pointer as `free`, which is known statically to the caller so
+    /// that it is inlined, e.g. in `retire::free_run`.
+    ///
+    /// # Safety
+    ///
+    /// See `Retired::free`. `free` must free the pointer as its deleter.
+    #[inline]
+    pub(crate) unsafe fn free_with(&self, retired: Retired, free: impl FnOnce(Retired)) {
+        let (pointer, size) = (retired.pointer, retired.size);
+        #[cfg(feature = "stats-histogram")]
+        let retired_at = retired.retired_at;
+        free(retired);
+        #[cfg(feature = "stats-histogram")]
+        self.latencies.record(retired_at.elapsed());
+        #[cfg(feature = "prometheus")]
+        self.counters.reclaimed();
+        let _ = self.bytes.pending.fetch_sub(size, Ordering::Relaxed);
+        let _ = self.bytes.objects.fetch_sub(1, Ordering::Relaxed);
+        let _ = self.bytes.reclaimed.fetch_add(size, Ordering::Relaxed);
+        let hook = self.reclaim_hook.load(Ordering::Acquire);
+        if !hook.is_null() {
+            // # Safety
+            // only `set_reclaim_hook` stores non-null pointers, which are `fn(*mut (), usize)`.
+            let hook = unsafe { mem::transmute::<*mut (), fn(*mut (), usize)>(hook) };
+            hook(pointer, size);
+        }
+    }
+
+    /// Drops an unprotected `object` by `DomainConfig::drop_budget` parts per collection, or at
+    /// once without a budget.
+    pub(crate) fn drop_incrementally(&self, object: Box<dyn IncrementalDrop>) {
+        match self.config().drop_budget {
+            Some(_) => self.drops.push(object),
+            None => drop(object),
+        }
+    }
+
+    /// Advances the incremental drops by `DomainConfig::drop_budget` parts, at the end of a
+    /// collection.
+    pub(crate) fn step_drops(&self) {
+        if let Some(budget) = self.config().drop_budget {
+            self.drops.step(budget);
+        }
+    }
+
+    /// Returns the number of unprotected objects whose incremental drop is not finished yet. See
+    /// `DomainConfig::drop_budget`.
+    pub fn incremental_drops(&self) -> usize {
+        self.drops.len()
+    }
+
+    /// Counts an object of `size` bytes as pending reclamation. Returns `true` if the domain
+    /// exceeds `max_pending_bytes` or `max_pending_objects`, so that the caller should call
+    /// `relieve`.
+    pub(crate) fn add_pending(&self, size: usize) -> bool {
+        let pending = self.bytes.pending.fetch_add(size, Ordering::Relaxed) + size;
+        let _ = self.bytes.objects.fetch_add(1, Ordering::Relaxed);
+        let _ = self
+            .bytes
+            .high_watermark
+            .fetch_max(pending, Ordering::Relaxed);
+        self.over_limits()
+    }
+
+    /// Leaks the pointers in `retired`, which are no longer counted as pending reclamation.
+    pub(crate) fn leak(&self, retired: &mut RetiredList) {
+        let size = retired.iter().map(|retired| retired.size).sum();
+        let _ = self.bytes.pending.fetch_sub(size, Ordering::Relaxed);
+        let _ = self
+            .bytes
+            .objects
+            .fetch_sub(retired.len(), Ordering::Relaxed);
+        retired.clear();
+    }
+
+    /// Returns `true` if the domain exceeds `max_pending_bytes` or `max_pending_objects`.
+    fn over_limits(&self) -> bool {
+        let config = self.config();
+        config
+            .max_pending_bytes
+            .is_some_and(|max| self.pending_bytes() > max)
+            || config
+                .max_pending_objects
+                .is_some_and(|max| self.pending_objects() > max)
+    }
+
+    /// Runs `collect` after exceeding the limits, and keeps running it with backoff while they are
+    /// exceeded if `pending_policy` is `Block`.
+    pub(crate) fn relieve(&self, mut collect: impl FnMut()) {
+        collect();
+        let config = self.config();
+        if config.pending_policy != PendingPolicy::Block {
+            return;
+        }
+        let start = Instant::now();
+        let mut backoff = Backoff::parking();
+        while self.over_limits()
+            && config
+                .stall_timeout
+                .is_none_or(|timeout| start.elapsed() < timeout)
+        {
+            backoff.snooze();
+            collect();
+        }
+    }
+
+    /// Registers the current thread to access the pointers of this domain without shields until
+    /// the returned handle is dropped, announcing quiescent states with it. See `Quiescent`.
+    pub fn register_quiescent(&self) -> Quiescent<'_> {
+        Quiescent::new(self)
+    }
+
+    pub(crate) fn quiescence(&self) -> &Quiescence {
+        &self.quiescence
+    }
+
+    /// Returns the number of objects retired to this domain and not reclaimed yet.
+    pub fn pending_objects(&self) -> usize {
+        self.bytes.objects.load(Ordering::Relaxed)
+    }
+
+    #[cfg(feature = "owner-info")]
+    /// Describes the shields of this domain protecting a pointer, longest held first, with the
+    /// thread owning each and how long it has held its slot. This tells who prevents reclamation,
+    /// e.g. a leaked shield.
+    pub fn dump_owners(&self) -> String {
+        let mut held = self.hazards.held();
+        held.sort_by_key(|(_, _, owner)| owner.acquired);
+        let now = Instant::now();
+        let mut report = format!("hazard: {} shields protect pointers", held.len());
+        for (index, hazard, owner) in held {
+            let _ = write!(
+                report,
+                "\n  slot {index} protects {hazard:p}, held by {:?} {:?} for {:?}",
+                owner.id,
+                owner.name,
+                now.saturating_duration_since(owner.acquired)
+            );
+        }
+        report
+    }
+
+    /// Returns the number of bytes of the objects retired to this domain and not reclaimed yet,
+    /// including those in thread-local lists. Objects retired with custom deleters count as 0.
+    pub fn pending_bytes(&self) -> usize {
+        self.bytes.pending.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes of the objects reclaimed in this domain so far.
+    pub fn reclaimed_bytes(&self) -> usize {
+        self.bytes.reclaimed.load(Ordering::Relaxed)
+    }
+
+    /// Returns the largest `pending_bytes` so far, i.e. the worst memory overhead of deferred
+    /// reclamation.
+    pub fn high_watermark(&self) -> usize {
+        self.bytes.high_watermark.load(Ordering::Relaxed)
+    }
+
+    #[cfg(feature = "stats-histogram")]
+    /// Returns a histogram of the latencies from `retire` to the free of the objects reclaimed in
+    /// this domain so far, e.g. to watch the tail latency of reclamation.
+    pub fn reclaim_latencies(&self) -> LatencyHistogram {
+        self.latencies.snapshot()
+    }
+
+    #[cfg(feature = "prometheus")]
+    /// Returns the metrics of this domain in the Prometheus text exposition format, to be served
+    /// to scrapers: the slots, the objects and bytes pending reclamation, the reclaimed objects
+    /// and bytes, and the time spent scanning hazards. With `stats-histogram`, also the quantiles
+    /// of the reclamation latency. The metrics have no labels, so a process exporting several
+    /// domains should relabel them.
+    pub fn metrics_prometheus(&self) -> String {
+        metrics::prometheus(self, &self.counters)
+    }
+
+    #[cfg(feature = "prometheus")]
+    /// Counts a scan of the hazards taking `time`.
+    pub(crate) fn scanned(&self, time: Duration) {
+        self.counters.scanned(time);
+    }
+
+    /// Returns `true` if this is the default domain `HAZARDS`.
+    #[cfg(feature = "global")]
+    fn is_default(&self) -> bool {
+        let default: &Domain = &HAZARDS;
+        ptr::eq(self, default)
+    }
+
+    /// Retires a pointer protected by this domain. Pointers retired to the default domain go to
+    /// the thread-local list, and the others to the list shared by all threads, which is collected
+    /// when it holds `threshold` pointers.
+    ///
+    /// # Safety
+    ///
+    /// * `pointer` must be removed from shared memory before calling this function, and must be
+    ///   valid.
+    /// * The same `pointer` should only be retired once.
+    /// * `pointer` may be freed by any thread collecting this domain, so it must be safe to drop
+    ///   it there (e.g. `T: Send`).
+    pub unsafe fn retire<T>(&self, pointer: *mut T) {
+        #[cfg(feature = "global")]
+        if self.is_default() {
+            return unsafe { crate::retire(pointer) };
+        }
+        self.push(retire::retired(&self.hazards, pointer));
+    }
+
+    /// Retires a pointer as `retire`, and returns a handle telling whether it is reclaimed.
+    ///
+    /// # Safety
+    ///
+    /// See `retire`.
+    pub unsafe fn retire_notify<T>(&self, pointer: *mut T) -> ReclaimHandle {
+        #[cfg(feature = "global")]
+        if self.is_default() {
+            return unsafe { crate::retire_notify(pointer) };
+        }
+        let handle = ReclaimHandle::new();
+        retire::poison(&self.hazards, pointer);
+        self.push(Retired::notify(pointer, &handle));
+        handle
+    }
+
+    /// Retires a pointer protected by this domain, to be passed to `deleter` with `context`
+    /// instead of being freed, e.g. to recycle it.
+    ///
+    /// # Safety
+    ///
+    /// * `pointer` must be removed from shared memory before calling this function.
+    /// * The same `pointer` should only be retired once.
+    /// * It must be safe to call `deleter` with `pointer` and `context` in any thread collecting
+    ///   this domain, once it is no longer protected.
+    pub unsafe fn retire_with(
+        &self,
+        pointer: *mut (),
+        deleter: unsafe fn(*mut (), *const ()),
+        context: *const (),
+    ) {
+        #[cfg(feature = "global")]
+        if self.is_default() {
+            return unsafe { crate::retire_with(pointer, deleter, context) };
+        }
+        self.push(Retired::with_deleter(pointer, deleter, context));
+    }
+
+    /// Adds a retired pointer to the shared list, and collects if it holds `threshold` pointers or
+    /// `max_pending_bytes` is exceeded.
+    fn push(&self, mut entry: Retired) {
+        entry.epoch = self.quiescence.epoch();
+        let over = self.add_pending(entry.size);
+        let mut retired = self.shard().lock().unwrap_or_else(|e| e.into_inner());
+        retired.inner.push(entry);
+        #[cfg(any(test, feature = "test-hooks"))]
+        hooks::run(HookPoint::Retired);
+        if over {
+            drop(retired);
+            self.relieve(|| self.collect());
+        } else if retired.inner.len() >= self.config().threshold {
+            drop(retired);
+            self.collect();
+        }
+    }
+
+    #[cfg(not(feature = "check-loom"))]
+    /// Registers this domain to be collected by `collect_all`, and starts its
+    /// `DomainConfig::background_reclaim` thread, if set. This fixes the configuration. Domains
+    /// owned by a `DomainHandle` are registered already.
+    pub fn register(&'static self) {
+        let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+        if !registry
+            .iter()
+            .any(|entry| matches!(entry, Registered::Static(domain) if ptr::eq(*domain, self)))
+        {
+            registry.push(Registered::Static(self));
+            self.reclaim_in_background();
+        }
+    }
+
+    #[cfg(not(feature = "check-loom"))]
+    /// Starts the `DomainConfig::background_reclaim` thread of this domain, if set.
+    pub(crate) fn reclaim_in_background(&'static self) {
+        if let Some(interval) = self.config().background_reclaim {
+            Registered::Static(self).spawn_reclaimer(interval);
+        }
+    }
+
+    #[cfg(not(feature = "check-loom"))]
+    /// Collects the default domain on the current thread, every `register`ed domain and every
+    /// domain owned by a `DomainHandle`.
+    ///
+    /// This is meant to be called when memory runs out, e.g. from the allocation failure handler
+    /// of an application before retrying, to reclaim the memory that is deferred. Destructors of
+    /// the pointers freed here must not `register` domains or create `DomainHandle`s.
+    pub fn collect_all() {
+        #[cfg(feature = "global")]
+        crate::collect();
+        // Collect under the lock, as the registry is not copied to avoid allocating.
+        let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+        registry.retain(Registered::is_alive);
+        for entry in registry.iter() {
+            let _ = entry.collect();
+        }
+    }
+
+    /// Frees the pointers that are `retire`d to this domain by any thread and not `protect`ed. For
+    /// the default domain, these are the pointers retired by the current thread or handed off.
+    pub fn collect(&self) {
+        #[cfg(feature = "global")]
+        if self.is_default() {
+            return crate::collect();
+        }
+        self.collect_shared(|can_free| unsafe { retire::free_all(self, &can_free) });
+    }
+
+    /// Collects this domain in `collect_all`, where the retired backlog may be large. With the
+    /// `parallel-collect` feature, the pointers are freed on multiple threads.
+    #[cfg(not(feature = "check-loom"))]
+    fn collect_many(&self) {
+        #[cfg(feature = "parallel-collect")]
+        {
+            #[cfg(feature = "global")]
+            if self.is_default() {
+                return crate::collect();
+            }
+            self.collect_shared(|can_free| retire::free_parallel(self, can_free));
+        }
+        #[cfg(not(feature = "parallel-collect"))]
+        self.collect();
+    }
+
+    /// Moves `batch` to the shared list, which is collected by any thread.
+    pub(crate) fn hand_off(&self, batch: &mut RetiredList) {
+        let mut retired = self.shard().lock().unwrap_or_else(|e| e.into_inner());
+        retired.inner.append(batch);
+    }
+
+    /// Frees the pointers in `local` and in the shared list that are not protected, with a single
+    /// scan of the hazards into `table`. Unlike `collect`, this applies to the default domain as
+    /// well.
+    pub(crate) fn collect_with(&self, local: &mut RetiredList, table: &mut HazardTable) {
+        let mut shared = self.take_shared();
+        let mut lists = Vec::with_capacity(self.shards().len() + 1);
+        lists.push(&mut *local);
+        lists.extend(shared.iter_mut().take(self.shards().len()));
+        let can_free = retire::unprotected(self, &mut lists, table);
+        drop(lists);
+        let reclaimed = can_free.len();
+        unsafe { retire::free_all(self, &can_free) };
+        self.step_drops();
+        self.event(DomainEvent::CollectEnd {
+            reclaimed,
+            remaining: local.len() + shared.iter().map(|retired| retired.len()).sum::<usize>(),
+        });
+        self.put_back(&mut shared);
+    }
+
+    /// Passes the pointers in the shared list that are not protected to `free`.
+    fn collect_shared(&self, free: impl FnOnce(RetiredList)) {
+        let mut shared = self.take_shared();
+        let mut hazards = mem::take(
+            &mut self.retired[0]
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .hazards,
+        );
+        let can_free = retire::unprotected(
+            self,
+            &mut shared.each_mut()[..self.shards().len()],
+            &mut hazards,
+        );
+        let reclaimed = can_free.len();
+        free(can_free);
+        self.step_drops();
+        self.event(DomainEvent::CollectEnd {
+            reclaimed,
+            remaining: shared.iter().map(|retired| retired.len()).sum(),
+        });
+        self.put_back(&mut shared);
+        self.retired[0]
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .hazards = hazards;
+    }
+
+    /// Returns the shards of the shared list in use.
+    fn shards(&self) -> &[Mutex<SharedRetired>] {
+        &self.retired[..self.config().shards.clamp(1, DomainConfig::MAX_SHARDS)]
+    }
+
+    /// Returns the shard of the shared list picked by the current thread.
+    fn shard(&self) -> &Mutex<SharedRetired> {
+        let shards = self.shards();
+        if shards.len() == 1 {
+            return &shards[0];
+        }
+        let hash = BuildHasherDefault::<DefaultHasher>::default().hash_one(thread::current().id());
+        &shards[hash as usize % shards.len()]
+    }
+
+    /// Takes the pointers out of each shard of the shared list, so that the destructors run
+    /// without the locks, as they may retire other pointers.
+    fn take_shared(&self) -> [RetiredList; DomainConfig::MAX_SHARDS] {
+        let shards = self.shards();
+        array::from_fn(|index| match shards.get(index) {
+            Some(shard) => shard.lock().unwrap_or_else(|e| e.into_inner()).inner.take(),
+            None => RetiredList::new(self.hazards.allocator()),
+        })
+    }
+
+    /// Puts the pointers taken by `take_shared` that are still retired back to their shards.
+    fn put_back(&self, shared: &mut [RetiredList]) {
+        for (shard, retired) in self.shards().iter().zip(shared) {
+            if !retired.is_empty() {
+                let mut shard = shard.lock().unwrap_or_else(|e| e.into_inner());
+                shard.inner.append(retired);
+            }
+        }
+    }
+}
+
+impl Drop for Domain {
+    /// Frees all the pointers retired to this domain. No shield of this domain may exist at this
+    /// point.
+    fn drop(&mut self) {
+        for index in 0..DomainConfig::MAX_SHARDS {
+            let shard = self.retired[index]
+                .get_mut()
+                .unwrap_or_else(|e| e.into_inner());
+            let retired = shard.inner.take();
+            for &retired in retired.iter() {
+                unsafe { self.free(retired) };
+            }
+        }
+    }
+}
+
+impl Default for Domain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An owned, cloneable handle to a shared domain, so that a domain and the retired pointer lists
+/// using it can be stored in structs and shared across threads without borrowing.
+///
+/// ```
+/// use hazard::{Domain, DomainHandle};
+///
+/// let domain = DomainHandle::new(Domain::builder().threshold(8).build());
+/// let handle = domain.clone();
+/// std::thread::spawn(move || {
+///     let mut retired = handle.retired_set();
+///     unsafe { retired.retire(Box::into_raw(Box::new(1))) };
+/// })
+/// .join()
+/// .unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DomainHandle {
+    inner: Arc<Domain>,
+}
+
+impl DomainHandle {
+    /// Creates a handle owning `domain`, which is registered to be collected by
+    /// `Domain::collect_all`, and starts its `DomainConfig::background_reclaim` thread, if set.
+    pub fn new(domain: Domain) -> Self {
+        let inner = Arc::new(domain);
+        #[cfg(not(feature = "check-loom"))]
+        {
+            let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+            registry.retain(Registered::is_alive);
+            registry.push(Registered::Handle(Arc::downgrade(&inner)));
+            if let Some(interval) = inner.config().background_reclaim {
+                Registered::Handle(Arc::downgrade(&inner)).spawn_reclaimer(interval);
+            }
+        }
+        Self { inner }
+    }
+
+    /// Creates a new shield of the domain.
+    pub fn shield(&self) -> Shield<'_> {
+        Shield::new(self.hazards())
+    }
+
+    /// Creates a new retired pointer list of the domain, which keeps the domain alive.
+    pub fn retired_set(&self) -> RetiredSet<DomainHandle> {
+        RetiredSet::new(self.clone())
+    }
+}
+
+impl Deref for DomainHandle {
+    type Target = Domain;
+
+    fn deref(&self) -> &Domain {
+        &self.inner
+    }
+}
+
+impl From<Domain> for DomainHandle {
+    fn from(domain: Domain) -> Self {
+        Self::new(domain)
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use super::{Domain, DomainBuilder, DomainConfig};
+
+    // the configuration can be set only once, and not after the domain is used.
+    #[test]
+    fn configure_once() {
+        let config = DomainBuilder::new().threshold(8).config();
+        let domain = Domain::new();
+        assert_eq!(domain.configure(config), Ok(()));
+        assert_eq!(
+            domain.configure(DomainConfig::default()),
+            Err(DomainConfig::default())
+        );
+        assert_eq!(domain.config(), &config);
+
+        let domain = Domain::new();
+        assert_eq!(domain.config(), &DomainConfig::default());
+        assert_eq!(domain.configure(config), Err(config));
+    }
+
+    // pointers retired to a domain are freed once unprotected, or when the domain is dropped.
+    #[test]
+    fn retire_collect() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+        use crate::Shield;
+        use crate::test::common::Tester;
+
+        let freed = Arc::new(AtomicUsize::new(0));
+        let domain = Domain::builder().threshold(4).build();
+        let protected = Box::into_raw(Box::new(Tester(freed.clone())));
+        let shield = Shield::new(domain.hazards());
+        let _ = shield.protect(&AtomicPtr::new(protected));
+        unsafe { domain.retire(protected) };
+        for _ in 0..3 {
+            unsafe { domain.retire(Box::into_raw(Box::new(Tester(freed.clone())))) };
+        }
+        assert_eq!(freed.load(Ordering::Relaxed), 3);
+        domain.collect();
+        assert_eq!(freed.load(Ordering::Relaxed), 3);
+
+        drop(shield);
+        unsafe { domain.retire(Box::into_raw(Box::new(Tester(freed.clone())))) };
+        drop(domain);
+        assert_eq!(freed.load(Ordering::Relaxed), 5);
+    }
+
+    // the retired pointer lists of a domain are allocated with its slot allocator.
+    #[test]
+    fn retired_allocator() {
+        use std::alloc::{self, Layout};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use crate::{RetiredSet, SlotAllocator};
+
+        static LISTS: AtomicUsize = AtomicUsize::new(0);
+        unsafe fn alloc(layout: Layout) -> *mut u8 {
+            let _ = LISTS.fetch_add(1, Ordering::Relaxed);
+            unsafe { alloc::alloc(layout) }
+        }
+        unsafe fn dealloc(pointer: *mut u8, layout: Layout) {
+            let _ = LISTS.fetch_sub(1, Ordering::Relaxed);
+            unsafe { alloc::dealloc(pointer, layout) }
+        }
+        let domain = Domain::builder()
+            .threshold(usize::MAX)
+            .slot_allocator(SlotAllocator { alloc, dealloc })
+            .build();
+        let mut retired = RetiredSet::new(&domain);
+        unsafe { retired.retire(Box::into_raw(Box::new(1))) };
+        assert_eq!(LISTS.load(Ordering::Relaxed), 1);
+        // the list keeps its allocation, and the shared list of the domain allocates.
+        unsafe { retired.hand_off() };
+        assert_eq!(LISTS.load(Ordering::Relaxed), 2);
+        drop(retired);
+        drop(domain);
+        assert_eq!(LISTS.load(Ordering::Relaxed), 0);
+    }
+
+    // pointers retired to the shards of a domain by several threads are collected together.
+    #[test]
+    fn shards() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+        use std::thread;
+
+        use crate::Shield;
+        use crate::test::common::Tester;
+
+        let freed = Arc::new(AtomicUsize::new(0));
+        let domain = Domain::builder().threshold(usize::MAX).shards(4).build();
+        let protected = Box::into_raw(Box::new(Tester(freed.clone())));
+        let shield = Shield::new(domain.hazards());
+        let _ = shield.protect(&AtomicPtr::new(protected));
+        unsafe { domain.retire(protected) };
+        thread::scope(|s| {
+            for _ in 0..8 {
+                let _ = s.spawn(|| {
+                    for _ in 0..16 {
+                        unsafe { domain.retire(Box::into_raw(Box::new(Tester(freed.clone())))) };
+                    }
+                });
+            }
+        });
+        domain.collect();
+        assert_eq!(freed.load(Ordering::Relaxed), 8 * 16);
+        assert_eq!(domain.pending_objects(), 1);
+
+        drop(shield);
+        domain.collect();
+        assert_eq!(freed.load(Ordering::Relaxed), 8 * 16 + 1);
+    }
+
+    // retired pointer lists keep the domain alive through its handle.
+    #[test]
+    fn handle_retired_set() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::thread;
+
+        use super::DomainHandle;
+        use crate::RetiredSet;
+        use crate::test::common::Tester;
+
+        struct Worker {
+            retired: RetiredSet<DomainHandle>,
+        }
+        let freed = Arc::new(AtomicUsize::new(0));
+        let domain = DomainHandle::new(Domain::builder().threshold(usize::MAX).build());
+        let (handle, thread_freed) = (domain.clone(), freed.clone());
+        drop(domain);
+        thread::spawn(move || {
+            let mut worker = Worker {
+                retired: handle.retired_set(),
+            };
+            drop(handle);
+            let pointer = Box::into_raw(Box::new(Tester(thread_freed)));
+            unsafe { worker.retired.retire(pointer) };
+            drop(worker);
+        })
+        .join()
+        .unwrap();
+        assert_eq!(freed.load(Ordering::Relaxed), 1);
+    }
+
+    // the background thread collects a domain owned by a handle without any thread collecting.
+    #[test]
+    fn background_reclaim() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::thread;
+        use std::time::{Duration, Instant};
+
+        use super::DomainHandle;
+        use crate::test::common::Tester;
+
+        let freed = Arc::new(AtomicUsize::new(0));
+        let domain = DomainHandle::new(
+            Domain::builder()
+                .threshold(usize::MAX)
+                .background_reclaim(Some(Duration::from_millis(1)))
+                .build(),
+        );
+        unsafe { domain.retire(Box::into_raw(Box::new(Tester(freed.clone())))) };
+        let start = Instant::now();
+        while freed.load(Ordering::Relaxed) == 0 {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    // options that are not set keep their default.
+    #[test]
+    fn builder_defaults() {
+        assert_eq!(DomainBuilder::new().config(), DomainConfig::default());
+        let domain = Domain::builder().threshold(1).collect_every(2).build();
+        assert_eq!(domain.config().threshold, 1);
+        assert_eq!(domain.config().collect_every, 2);
+    }
+
+    // retired bytes are accounted per domain, and exceeding `max_pending_bytes` collects early.
+    #[test]
+    fn pending_bytes() {
+        use crate::RetiredSet;
+
+        let domain = Domain::builder()
+            .threshold(usize::MAX)
+            .max_pending_bytes(Some(24))
+            .build();
+        let mut retired = RetiredSet::new(&domain);
+        for _ in 0..3 {
+            unsafe { retired.retire(Box::into_raw(Box::new(0u64))) };
+        }
+        assert_eq!(domain.pending_bytes(), 24);
+        unsafe { domain.retire(Box::into_raw(Box::new(0u64))) };
+        assert_eq!((domain.pending_bytes(), domain.reclaimed_bytes()), (24, 8));
+        unsafe { retired.retire(Box::into_raw(Box::new(0u64))) };
+        assert_eq!((domain.pending_bytes(), domain.reclaimed_bytes()), (0, 40));
+        assert_eq!(domain.high_watermark(), 32);
+    }
+
+    // with `PendingPolicy::Block`, retiring beyond the limit waits until a reader releases the
+    // pointers.
+    #[test]
+    fn block_pending() {
+        use std::sync::Barrier;
+        use std::sync::atomic::{AtomicPtr, Ordering};
+        use std::thread::{scope, sleep};
+        use std::time::Duration;
+
+        use super::PendingPolicy;
+        use crate::Shield;
+
+        let domain = Domain::builder()
+            .max_pending_objects(Some(0))
+            .pending_policy(PendingPolicy::Block)
+            .build();
+        let src = AtomicPtr::new(Box::into_raw(Box::new(1)));
+        let barrier = Barrier::new(2);
+        scope(|s| {
+            let _ = s.spawn(|| {
+                let shield = Shield::new(domain.hazards());
+                let _ = shield.protect(&src);
+                let _ = barrier.wait();
+                sleep(Duration::from_millis(10));
+            });
+            let _ = barrier.wait();
+            unsafe { domain.retire(src.load(Ordering::Relaxed)) };
+            assert_eq!(domain.pending_objects(), 0);
+        });
+    }
+
+    // the reclaim hook observes each reclaimed object with its size, including those freed when
+    // the domain is dropped.
+    #[test]
+    fn reclaim_hook() {
+        use std::ptr;
+        use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+        static RECLAIMED: AtomicUsize = AtomicUsize::new(0);
+        static BYTES: AtomicUsize = AtomicUsize::new(0);
+        fn hook(_: *mut (), size: usize) {
+            let _ = RECLAIMED.fetch_add(1, Relaxed);
+            let _ = BYTES.fetch_add(size, Relaxed);
+        }
+        unsafe fn leak(_: *mut (), _: *const ()) {}
+
+        let domain = Domain::builder().threshold(usize::MAX).build();
+        domain.set_reclaim_hook(hook);
+        unsafe { domain.retire(Box::into_raw(Box::new([0u64; 2]))) };
+        unsafe { domain.retire_with(ptr::dangling_mut(), leak, ptr::null()) };
+        domain.collect();
+        assert_eq!((RECLAIMED.load(Relaxed), BYTES.load(Relaxed)), (2, 16));
+
+        unsafe { domain.retire(Box::into_raw(Box::new(0u32))) };
+        drop(domain);
+        assert_eq!((RECLAIMED.load(Relaxed), BYTES.load(Relaxed)), (3, 20));
+    }
+
+    // the event hook observes slot allocation and the start and end of each collection.
+    #[test]
+    fn event_hook() {
+        use std::sync::Mutex;
+        use std::sync::atomic::AtomicPtr;
+
+        use super::DomainEvent;
+        use crate::Shield;
+
+        static EVENTS: Mutex<Vec<DomainEvent>> = Mutex::new(Vec::new());
+        fn hook(event: DomainEvent) {
+            EVENTS.lock().unwrap().push(event);
+        }
+
+        let domain = Domain::builder().threshold(usize::MAX).build();
+        domain.set_event_hook(hook);
+        let src = AtomicPtr::new(Box::into_raw(Box::new(0)));
+        let shield = Shield::new(domain.hazards());
+        let protected = shield.protect(&src);
+        unsafe { domain.retire(protected) };
+        unsafe { domain.retire(Box::into_raw(Box::new(1))) };
+        domain.collect();
+        domain.clear_event_hook();
+        drop(shield);
+        domain.collect();
+        let events = EVENTS.lock().unwrap();
+        assert!(matches!(events[0], DomainEvent::SlotsAllocated { .. }));
+        assert_eq!(
+            events[1..],
+            [
+                DomainEvent::CollectStart { retired: 2 },
+                DomainEvent::CollectEnd {
+                    reclaimed: 1,
+                    remaining: 1
+                }
+            ]
+        );
+    }
+
+    // the owners of the shields protecting pointers are reported, longest held first.
+    #[cfg(feature = "owner-info")]
+    #[test]
+    fn dump_owners() {
+        use std::sync::Barrier;
+        use std::sync::atomic::AtomicPtr;
+        use std::thread;
+        use std::time::Duration;
+
+        use crate::Shield;
+
+        let domain = Domain::new();
+        let (mut one, mut two) = (1, 2);
+        let (first, second) = (AtomicPtr::new(&mut one), AtomicPtr::new(&mut two));
+        let barrier = Barrier::new(3);
+        thread::scope(|s| {
+            for (name, src) in [("first", &first), ("second", &second)] {
+                let _ = thread::Builder::new()
+                    .name(name.into())
+                    .spawn_scoped(s, || {
+                        let shield = Shield::new(domain.hazards());
+                        let _ = shield.protect(src);
+                        let _ = barrier.wait();
+                        let _ = barrier.wait();
+                    })
+                    .unwrap();
+                thread::sleep(Duration::from_millis(10));
+            }
+            let _ = barrier.wait();
+            let report = domain.dump_owners();
+            let _ = barrier.wait();
+            assert!(report.starts_with("hazard: 2 shields"), "{report}");
+            let position = |src: &AtomicPtr<i32>| {
+                let line = format!(
+                    "{:p}, held by",
+                    src.load(std::sync::atomic::Ordering::Relaxed)
+                );
+                report.find(&line).unwrap()
+            };
+            assert!(position(&first) < position(&second), "{report}");
+            assert!(report.contains("\"first\"") && report.contains("\"second\""));
+        });
+    }
+
+    // with a registered thread, retired pointers are freed only after its next quiescent state.
+    #[test]
+    fn quiescent_state() {
+        let domain = Domain::builder().threshold(usize::MAX).build();
+        let quiescent = domain.register_quiescent();
+        let handle = unsafe { domain.retire_notify(Box::into_raw(Box::new(1))) };
+        domain.collect();
+        assert!(!handle.is_reclaimed());
+        quiescent.quiescent_state();
+        domain.collect();
+        assert!(handle.is_reclaimed());
+
+        let handle = unsafe { domain.retire_notify(Box::into_raw(Box::new(2))) };
+        domain.collect();
+        assert!(!handle.is_reclaimed());
+        drop(quiescent);
+        domain.collect();
+        assert!(handle.is_reclaimed());
+    }
+
+    // every pointer retired in a scoped domain is freed when the scope ends.
+    #[test]
+    fn scope() {
+        use std::sync::atomic::AtomicPtr;
+
+        use crate::{RetiredSet, Shield};
+
+        let config = DomainBuilder::new().threshold(usize::MAX).config();
+        let handles = Domain::scope(config, |domain| {
+            let pointer = Box::into_raw(Box::new(0));
+            let shield = Shield::new(domain.hazards());
+            let _ = shield.protect(&AtomicPtr::new(pointer));
+            let handle = unsafe { domain.retire_notify(pointer) };
+            domain.collect();
+            assert!(!handle.is_reclaimed());
+            let mut retired = RetiredSet::new(domain);
+            let local = unsafe { retired.retire_notify(Box::into_raw(Box::new(1))) };
+            drop(shield);
+            [handle, local]
+        });
+        assert!(handles.iter().all(|handle| handle.is_reclaimed()));
+    }
+
+    // `collect_all` collects registered domains and domains owned by handles.
+    #[test]
+    fn collect_all() {
+        use super::DomainHandle;
+
+        let leaked: &'static Domain =
+            Box::leak(Box::new(Domain::builder().threshold(usize::MAX).build()));
+        leaked.register();
+        leaked.register();
+        let handle = DomainHandle::new(Domain::builder().threshold(usize::MAX).build());
+        let reclaimed = [&*handle, leaked]
+            .map(|domain| unsafe { domain.retire_notify(Box::into_raw(Box::new(1))) });
+        Domain::collect_all();
+        assert!(reclaimed.iter().all(|handle| handle.is_reclaimed()));
+    }
+
+    // a large backlog is freed in parallel by `collect_all`.
+    #[cfg(feature = "parallel-collect")]
+    #[test]
+    fn collect_all_parallel() {
+        use super::DomainHandle;
+
+        let domain = DomainHandle::new(Domain::builder().threshold(usize::MAX).build());
+        for i in 0..1 << 16 {
+            unsafe { domain.retire(Box::into_raw(Box::new(i))) };
+        }
+        Domain::collect_all();
+        assert_eq!(domain.pending_objects(), 0);
+        assert_eq!(domain.reclaimed_bytes(), 4 << 16);
+    }
+
+    // the latency of an object protected for a while is recorded once it is freed.
+    #[cfg(feature = "stats-histogram")]
+    #[test]
+    fn reclaim_latencies() {
+        use std::sync::atomic::AtomicPtr;
+        use std::thread;
+        use std::time::Duration;
+
+        use crate::Shield;
+
+        let domain = Domain::builder().threshold(usize::MAX).build();
+        let src = AtomicPtr::new(Box::into_raw(Box::new(0)));
+        let shield = Shield::new(domain.hazards());
+        let pointer = shield.protect(&src);
+        unsafe { domain.retire(pointer) };
+        domain.collect();
+        assert!(domain.reclaim_latencies().is_empty());
+        thread::sleep(Duration::from_millis(10));
+        drop(shield);
+        domain.collect();
+        let latencies = domain.reclaim_latencies();
+        assert_eq!(latencies.count(), 1);
+        assert!(latencies.quantile(0.99) >= Duration::from_millis(10));
+    }
+
+    // the metrics are exported in the text exposition format.
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn metrics_prometheus() {
+        use crate::Shield;
+
+        let domain = Domain::new();
+        let _shield = Shield::new(domain.hazards());
+        unsafe { domain.retire(Box::into_raw(Box::new(0u64))) };
+        domain.collect();
+        let metrics = domain.metrics_prometheus();
+        let sample = |name: &str| {
+            metrics
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+                .unwrap_or_else(|| panic!("no sample of {name} in {metrics}"))
+                .to_owned()
+        };
+        assert!(metrics.contains("# TYPE hazard_reclaimed_bytes_total counter\n"));
+        assert_eq!(sample("hazard_slots_active"), "1");
+        assert_eq!(sample("hazard_pending_objects"), "0");
+        assert_eq!(sample("hazard_reclaimed_objects_total"), "1");
+        assert_eq!(sample("hazard_reclaimed_bytes_total"), "8");
+        assert_eq!(sample("hazard_scan_duration_seconds_count"), "1");
+    }
+}
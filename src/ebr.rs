@@ -0,0 +1,296 @@
+//! Epoch-based reclamation (EBR), an alternative to hazard pointers behind the same
+//! [`Reclaimer`] interface.
+//!
+//! A guard pins the collector at the current global epoch, protecting all the objects that are
+//! reachable while it is pinned. The global epoch advances when all pinned guards have observed
+//! it, and an object retired at epoch `e` is freed once the global epoch reaches `e + 2`. Unlike
+//! hazard pointers, protecting a pointer is free, but a single long-lived guard blocks all
+//! reclamation.
+//!
+//! ```
+//! use hazard::collections::Stack;
+//! use hazard::ebr::Collector;
+//!
+//! let collector = Collector::new();
+//! let stack = Stack::with_reclaimer(&collector);
+//! stack.push(1);
+//! assert_eq!(stack.try_pop(), Some(1));
+//! ```
+
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, fence};
+#[cfg(not(feature = "check-loom"))]
+use std::sync::Mutex;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::Mutex;
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, fence};
+
+use super::backoff::Backoff;
+use super::ordering::Ordering;
+use super::retire::Retired;
+use super::{DomainConfig, Protect, Reclaimer};
+
+/// An epoch-based garbage collector.
+#[derive(Debug)]
+pub struct Collector {
+    /// The global epoch.
+    epoch: AtomicUsize,
+    /// Grow-only list of the participants, like `HazardBag`.
+    head: AtomicPtr<Participant>,
+    garbage: Mutex<Garbage>,
+}
+
+/// A record announcing the epoch of a pinned guard.
+#[derive(Debug)]
+struct Participant {
+    // Whether this record is occupied by a `Guard`.
+    active: AtomicBool,
+    // `(epoch << 1) | 1` if pinned at `epoch`, 0 if not pinned.
+    epoch: AtomicUsize,
+    // Immutable pointer to the next record.
+    next: *const Participant,
+}
+
+/// Retired pointers tagged with the global epoch at the time of retirement.
+#[derive(Debug, Default)]
+struct Garbage {
+    inner: Vec<(usize, Retired)>,
+    /// The length at which `collect` is triggered next. Pointers that are not expired yet stay
+    /// in the list, so this keeps `collect` from running on every `retire`.
+    trigger: usize,
+}
+
+// Retired pointers are freed by any thread collecting the collector, as required by
+// `Reclaimer::retire`.
+unsafe impl Send for Garbage {}
+unsafe impl Send for Participant {}
+unsafe impl Sync for Participant {}
+
+/// A guard pinning the current thread, which protects every object reachable while it lives.
+#[derive(Debug)]
+#[must_use = "the objects are unprotected as soon as the guard is dropped"]
+pub struct Guard<'c> {
+    participant: &'c Participant,
+    _marker: PhantomData<*const ()>, // !Send + !Sync
+}
+
+impl Collector {
+    #[cfg(not(feature = "check-loom"))]
+    /// Creates a new collector.
+    pub const fn new() -> Self {
+        Self {
+            epoch: AtomicUsize::new(0),
+            head: AtomicPtr::new(ptr::null_mut()),
+            garbage: Mutex::new(Garbage {
+                inner: Vec::new(),
+                trigger: 0,
+            }),
+        }
+    }
+
+    #[cfg(feature = "check-loom")]
+    /// Creates a new collector.
+    pub fn new() -> Self {
+        Self {
+            epoch: AtomicUsize::new(0),
+            head: AtomicPtr::new(ptr::null_mut()),
+            garbage: Mutex::new(Garbage {
+                inner: Vec::new(),
+                trigger: 0,
+            }),
+        }
+    }
+
+    /// Pins the current thread at the current epoch.
+    pub fn pin(&self) -> Guard<'_> {
+        let participant = self.acquire_participant();
+        let epoch = self.epoch.load(Ordering::Relaxed);
+        participant.epoch.store((epoch << 1) | 1, Ordering::Relaxed);
+        // Make the pin visible before any load of shared pointers.
+        fence(Ordering::SeqCst);
+        Guard {
+            participant,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the global epoch.
+    pub fn epoch(&self) -> usize {
+        self.epoch.load(Ordering::Relaxed)
+    }
+
+    /// Acquires an inactive participant record or allocates a new one.
+    fn acquire_participant(&self) -> &Participant {
+        let mut record_ptr = self.head.load(Ordering::Acquire);
+        while let Some(record) = unsafe { record_ptr.as_ref() } {
+            if !record.active.load(Ordering::Relaxed)
+                && record
+                    .active
+                    .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return record;
+            }
+            record_ptr = record.next as *mut Participant;
+        }
+
+        let record = Box::into_raw(Box::new(Participant {
+            active: AtomicBool::new(true),
+            epoch: AtomicUsize::new(0),
+            next: ptr::null(),
+        }));
+        let mut backoff = Backoff::new();
+        loop {
+            let head = self.head.load(Ordering::Relaxed);
+            unsafe { (*record).next = head };
+            if self
+                .head
+                .compare_exchange_weak(head, record, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+            {
+                return unsafe { &*record };
+            }
+            backoff.snooze();
+        }
+    }
+
+    /// Advances the global epoch if all pinned participants have observed it. Returns the global
+    /// epoch.
+    fn try_advance(&self) -> usize {
+        let epoch = self.epoch.load(Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+        let mut record_ptr = self.head.load(Ordering::Acquire);
+        while let Some(record) = unsafe { record_ptr.as_ref() } {
+            let pinned = record.epoch.load(Ordering::Relaxed);
+            if pinned & 1 == 1 && pinned >> 1 != epoch {
+                return epoch;
+            }
+            record_ptr = record.next as *mut Participant;
+        }
+        fence(Ordering::Acquire);
+        match self.epoch.compare_exchange(
+            epoch,
+            epoch.wrapping_add(1),
+            Ordering::Release,
+            Ordering::Relaxed,
+        ) {
+            Ok(_) => epoch.wrapping_add(1),
+            Err(current) => current,
+        }
+    }
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Collector {
+    /// Frees all the retired pointers and the participant records. No guard of this collector
+    /// may exist at this point.
+    fn drop(&mut self) {
+        let garbage = self.garbage.get_mut().unwrap_or_else(|e| e.into_inner());
+        for (_, retired) in garbage.inner.drain(..) {
+            unsafe { retired.free() };
+        }
+        let mut record_ptr = self.head.load(Ordering::Relaxed);
+        while !record_ptr.is_null() {
+            let record = unsafe { Box::from_raw(record_ptr) };
+            record_ptr = record.next as *mut Participant;
+        }
+    }
+}
+
+impl Drop for Guard<'_> {
+    /// Unpins and releases the participant record.
+    fn drop(&mut self) {
+        self.participant.epoch.store(0, Ordering::Release);
+        self.participant.active.store(false, Ordering::Release);
+    }
+}
+
+impl Protect for Guard<'_> {
+    /// Does nothing: all the objects are protected as long as the guard lives.
+    fn set<T>(&self, _: *mut T) {}
+
+    /// Does nothing: all the objects are protected as long as the guard lives.
+    fn clear(&self) {}
+}
+
+unsafe impl Reclaimer for Collector {
+    type Guard<'r> = Guard<'r>;
+
+    fn guard(&self) -> Guard<'_> {
+        self.pin()
+    }
+
+    unsafe fn retire<T>(&self, pointer: *mut T) {
+        fence(Ordering::SeqCst);
+        let epoch = self.epoch.load(Ordering::Relaxed);
+        let mut garbage = self.garbage.lock().unwrap_or_else(|e| e.into_inner());
+        garbage.inner.push((epoch, Retired::new(pointer)));
+        if garbage.inner.len() >= garbage.trigger.max(DomainConfig::DEFAULT_THRESHOLD) {
+            drop(garbage);
+            self.collect();
+        }
+    }
+
+    fn collect(&self) {
+        let epoch = self.try_advance();
+        // Take the expired pointers out so that the destructors run without the lock, as they
+        // may retire other pointers.
+        let expired = {
+            let mut garbage = self.garbage.lock().unwrap_or_else(|e| e.into_inner());
+            let (expired, pending) = mem::take(&mut garbage.inner)
+                .into_iter()
+                .partition(|(retired, _)| epoch.wrapping_sub(*retired) >= 2);
+            garbage.inner = pending;
+            garbage.trigger = garbage.inner.len() + DomainConfig::DEFAULT_THRESHOLD;
+            expired
+        };
+        for (_, retired) in Vec::into_iter(expired) {
+            unsafe { retired.free() };
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::Collector;
+    use crate::Reclaimer;
+    use crate::test::common::{Tester, exercise_collections};
+
+    // a pinned guard blocks freeing the objects retired while it lives.
+    #[test]
+    fn guard_blocks_reclamation() {
+        let collector = Collector::new();
+        let freed = Arc::new(AtomicUsize::new(0));
+        let guard = collector.pin();
+        unsafe { collector.retire(Box::into_raw(Box::new(Tester(freed.clone())))) };
+        for _ in 0..4 {
+            collector.collect();
+        }
+        assert_eq!(freed.load(Ordering::Relaxed), 0);
+
+        drop(guard);
+        for _ in 0..4 {
+            collector.collect();
+        }
+        assert_eq!(freed.load(Ordering::Relaxed), 1);
+    }
+
+    // the collections work with EBR, and their retired items are freed.
+    #[test]
+    fn collections() {
+        exercise_collections(&Collector::new());
+    }
+}
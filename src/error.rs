@@ -0,0 +1,116 @@
+use core::error::Error;
+use core::fmt;
+
+/// The error of protecting a pointer, when its source no longer points to it.
+///
+/// `observed` is the current value of the source, so a retry loop can continue with it:
+///
+/// ```
+/// use core::sync::atomic::AtomicPtr;
+/// use hazard::Shield;
+///
+/// let src = AtomicPtr::new(Box::into_raw(Box::new(1)));
+/// let shield = Shield::default();
+/// let err = shield.try_protect(core::ptr::null_mut(), &src).unwrap_err();
+/// assert_eq!(err.observed(), src.load(core::sync::atomic::Ordering::Relaxed));
+/// assert!(shield.try_protect(err.observed(), &src).is_ok());
+/// # drop(shield);
+/// # drop(unsafe { Box::from_raw(err.observed()) });
+/// ```
+pub struct ProtectError<T> {
+    expected: *mut T,
+    observed: *mut T,
+    retries: Option<usize>,
+}
+
+impl<T> ProtectError<T> {
+    /// Creates an error of protecting `expected` when the source points to `observed`.
+    pub fn new(expected: *mut T, observed: *mut T) -> Self {
+        Self {
+            expected,
+            observed,
+            retries: None,
+        }
+    }
+
+    /// Sets the number of retries before giving up.
+    pub(crate) fn with_retries(self, retries: usize) -> Self {
+        Self {
+            retries: Some(retries),
+            ..self
+        }
+    }
+
+    /// Returns the pointer that was to be protected.
+    pub fn expected(&self) -> *mut T {
+        self.expected
+    }
+
+    /// Returns the value of the source observed by the validation.
+    pub fn observed(&self) -> *mut T {
+        self.observed
+    }
+
+    /// Returns the number of retries before giving up, for the variants that retry, e.g.
+    /// `Shield::try_protect_for`.
+    pub fn retries(&self) -> Option<usize> {
+        self.retries
+    }
+}
+
+// Manually implemented not to require `T: Clone` and `T: Debug`.
+impl<T> Clone for ProtectError<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ProtectError<T> {}
+
+impl<T> PartialEq for ProtectError<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.expected == other.expected
+            && self.observed == other.observed
+            && self.retries == other.retries
+    }
+}
+
+impl<T> Eq for ProtectError<T> {}
+
+impl<T> fmt::Debug for ProtectError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProtectError")
+            .field("expected", &self.expected)
+            .field("observed", &self.observed)
+            .field("retries", &self.retries)
+            .finish()
+    }
+}
+
+impl<T> fmt::Display for ProtectError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to protect {:p}: the source points to {:p}",
+            self.expected, self.observed
+        )?;
+        if let Some(retries) = self.retries {
+            write!(f, " after {retries} retries")?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> Error for ProtectError<T> {}
+
+/// The error of allocating memory, e.g. a new chunk of hazard slots in `Shield::try_new`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocError;
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("memory allocation failed")
+    }
+}
+
+impl Error for AllocError {}
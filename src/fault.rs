@@ -0,0 +1,75 @@
+//! Fault injection for stress tests of downstream code.
+//!
+//! With `feature = "fault-injection"`, `collect` is randomly delayed, acquiring an inactive hazard
+//! slot randomly fails so that a new chunk is allocated, and the retired pointers are shuffled
+//! before each scan. Set `HAZARD_FAULT_SEED` to a number to make the faults of each thread
+//! reproducible.
+
+use core::cell::Cell;
+use std::hash::{BuildHasher, RandomState};
+use std::thread;
+use std::time::Duration;
+
+thread_local! {
+    /// State of the xorshift generator of the current thread.
+    static STATE: Cell<u64> = Cell::new(seed());
+}
+
+/// Returns the seed of the current thread, from `HAZARD_FAULT_SEED` if set and random otherwise.
+fn seed() -> u64 {
+    let seed = std::env::var("HAZARD_FAULT_SEED")
+        .ok()
+        .and_then(|seed| seed.parse::<u64>().ok())
+        .unwrap_or_else(|| RandomState::new().hash_one(thread::current().id()));
+    // the state must not be zero.
+    seed | 1
+}
+
+/// Returns a pseudo-random number.
+fn next() -> u64 {
+    STATE
+        .try_with(|state| {
+            let mut x = state.get();
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            state.set(x);
+            x
+        })
+        .unwrap_or(0)
+}
+
+/// Returns `true` with probability `1 / n`.
+pub(crate) fn one_in(n: u64) -> bool {
+    next().is_multiple_of(n)
+}
+
+/// Sometimes sleeps or yields for a while.
+pub(crate) fn delay() {
+    match next() % 8 {
+        0 => thread::sleep(Duration::from_micros(next() % 100)),
+        1 | 2 => thread::yield_now(),
+        _ => {}
+    }
+}
+
+/// Shuffles `items`.
+pub(crate) fn shuffle<T>(items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+        items.swap(i, (next() % (i as u64 + 1)) as usize);
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use super::shuffle;
+
+    // shuffling keeps all the items.
+    #[test]
+    fn shuffle_permutes() {
+        let mut items = (0..100).collect::<Vec<_>>();
+        shuffle(&mut items);
+        items.sort_unstable();
+        assert_eq!(items, (0..100).collect::<Vec<_>>());
+    }
+}
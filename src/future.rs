@@ -0,0 +1,158 @@
+//! Futures holding a protection until they complete.
+
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use super::Protected;
+
+/// A future running an inner future while a pointer stays protected, so that the pointer read
+/// before an `.await` is still valid when the task resumes, possibly on another worker thread.
+///
+/// The protection is released as soon as the inner future completes, or when this is dropped,
+/// e.g. cancelled.
+///
+/// ```
+/// use std::sync::atomic::AtomicPtr;
+/// use hazard::{HazardBag, Protected, ProtectedFuture, Shield};
+///
+/// let hazards = HazardBag::new();
+/// let src = AtomicPtr::new(Box::into_raw(Box::new(1)));
+/// let protected = Protected::new(Shield::new(&hazards), &src);
+/// let future = ProtectedFuture::new(protected, |pointer| async move { unsafe { *pointer } });
+/// # drop(future);
+/// # drop(unsafe { Box::from_raw(src.into_inner()) });
+/// ```
+#[must_use = "futures do nothing unless polled, and the pointer stays protected until dropped"]
+pub struct ProtectedFuture<'domain, T, F> {
+    // Declared first to be dropped first, as the state of the inner future may still access the
+    // pointer when dropped, e.g. on cancel.
+    future: F,
+    protected: Option<Protected<'domain, T>>,
+}
+
+impl<'domain, T, F: Future> ProtectedFuture<'domain, T, F> {
+    /// Creates a future running the future returned by `f` with the pointer of `protected`.
+    pub fn new(protected: Protected<'domain, T>, f: impl FnOnce(*mut T) -> F) -> Self {
+        let future = f(protected.as_ptr());
+        Self {
+            future,
+            protected: Some(protected),
+        }
+    }
+
+    /// Returns `true` if the pointer is still protected, i.e. the inner future is not complete.
+    pub fn is_protected(&self) -> bool {
+        self.protected.is_some()
+    }
+}
+
+impl<T, F: Future> Future for ProtectedFuture<'_, T, F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // # Safety
+        // `future` is pinned structurally and never moved, while `protected` is not pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let output = unsafe { Pin::new_unchecked(&mut this.future) }.poll(cx);
+        if output.is_ready() {
+            this.protected = None;
+        }
+        output
+    }
+}
+
+impl<T, F> fmt::Debug for ProtectedFuture<'_, T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProtectedFuture")
+            .field("pointer", &self.protected.as_ref().map(Protected::as_ptr))
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::cell::Cell;
+    use std::future::Future;
+    use std::pin::{Pin, pin};
+    use std::sync::atomic::AtomicPtr;
+    use std::task::{Context, Poll, Waker};
+
+    use super::ProtectedFuture;
+    use crate::{HazardBag, Protected, Shield};
+
+    /// A future that is pending on the first poll.
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            Poll::Pending
+        }
+    }
+
+    // the pointer is protected across the pending poll, and released on completion or cancel.
+    #[test]
+    fn protect_across_await() {
+        let hazard_bag = HazardBag::new();
+        let src = AtomicPtr::new(Box::into_raw(Box::new(7)));
+        let mut cx = Context::from_waker(Waker::noop());
+        let pointer = src.load(std::sync::atomic::Ordering::Relaxed);
+        let run = || {
+            let protected = Protected::new(Shield::new(&hazard_bag), &src);
+            ProtectedFuture::new(protected, |pointer| async move {
+                YieldOnce(false).await;
+                unsafe { *pointer }
+            })
+        };
+
+        let mut future = pin!(run());
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        assert!(hazard_bag.all_hazards().contains(&pointer.cast()));
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Ready(7));
+        assert!(!future.is_protected() && hazard_bag.all_hazards().is_empty());
+
+        let mut future = Box::pin(run());
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        drop(future);
+        assert!(hazard_bag.all_hazards().is_empty());
+        drop(unsafe { Box::from_raw(src.into_inner()) });
+    }
+
+    // the state of a cancelled inner future is dropped while the pointer is still protected.
+    #[test]
+    fn cancel_drops_future_first() {
+        /// Checks on drop that the pointer is still protected.
+        struct Probe<'a>(&'a HazardBag, *mut i32, &'a Cell<bool>);
+
+        impl Drop for Probe<'_> {
+            fn drop(&mut self) {
+                self.2.set(self.0.all_hazards().contains(&self.1.cast()));
+            }
+        }
+
+        let hazard_bag = HazardBag::new();
+        let src = AtomicPtr::new(Box::into_raw(Box::new(7)));
+        let protected_on_drop = Cell::new(false);
+        let mut cx = Context::from_waker(Waker::noop());
+        let protected = Protected::new(Shield::new(&hazard_bag), &src);
+        let mut future = Box::pin(ProtectedFuture::new(protected, |pointer| {
+            let (hazard_bag, protected_on_drop) = (&hazard_bag, &protected_on_drop);
+            async move {
+                let _probe = Probe(hazard_bag, pointer, protected_on_drop);
+                YieldOnce(false).await;
+            }
+        }));
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        drop(future);
+        assert!(protected_on_drop.get());
+        assert!(hazard_bag.all_hazards().is_empty());
+        drop(unsafe { Box::from_raw(src.into_inner()) });
+    }
+}
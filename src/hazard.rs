@@ -1,42 +1,274 @@
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64 as arch;
+#[cfg(debug_assertions)]
+use core::cell::Cell;
+use core::cell::RefCell;
+use core::marker::PhantomData;
 use core::ptr::{self, NonNull};
+use core::sync::atomic;
+#[cfg(all(debug_assertions, not(feature = "check-loom")))]
+use core::sync::atomic::fence;
 #[cfg(not(feature = "check-loom"))]
-use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering, fence};
+use core::sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize};
+use core::{array, iter, mem};
+use std::alloc::{self, Layout};
 use std::collections::HashSet;
 use std::fmt;
+#[cfg(any(feature = "owner-info", feature = "watchdog"))]
+use std::sync::Mutex;
+#[cfg(feature = "owner-info")]
+use std::thread::{self, ThreadId};
+#[cfg(not(feature = "check-loom"))]
+use std::thread_local;
+#[cfg(any(test, feature = "test-hooks", feature = "watchdog"))]
+use std::time::Duration;
+#[cfg(any(
+    test,
+    feature = "test-hooks",
+    feature = "owner-info",
+    feature = "watchdog"
+))]
+use std::time::Instant;
 
+#[cfg(all(debug_assertions, feature = "check-loom"))]
+use loom::sync::atomic::fence;
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize};
 #[cfg(feature = "check-loom")]
-use loom::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering, fence};
+use loom::thread_local;
 
+#[cfg(feature = "global")]
 use super::HAZARDS;
+use super::backoff::Backoff;
+#[cfg(feature = "fault-injection")]
+use super::fault;
+#[cfg(any(test, feature = "test-hooks"))]
+use super::hooks::{self, HookPoint};
+use super::ordering::{self, Ordering};
+use super::{AllocError, DomainEvent, ProtectError};
+
+/// Represents the ownership of a hazard pointer slot of a bag that outlives `'domain`.
+///
+/// A shield may be sent to another thread and keeps protecting its pointer meanwhile, e.g. when
+/// a task of an async executor holding it across an `.await` resumes on another worker thread.
+///
+/// ```compile_fail
+/// use hazard::{HazardBag, Shield};
+///
+/// let hazards = HazardBag::new();
+/// let shield = Shield::new(&hazards);
+/// drop(hazards);
+/// shield.clear();
+/// ```
+#[must_use = "the slot is released as soon as the shield is dropped"]
+pub struct Shield<'domain> {
+    // The chunk containing the slot, and the index of the slot in it.
+    chunk: NonNull<SlotChunk>,
+    index: u8,
+    // Generation of the slot when it was acquired by this shield.
+    #[cfg(debug_assertions)]
+    generation: usize,
+    // Whether the published pointer is null or validated since it was `set`, and thus may be
+    // dereferenced.
+    #[cfg(debug_assertions)]
+    validated: Cell<bool>,
+    // Whether the slot is of the default domain, and thus may be cached when released.
+    cached: bool,
+    hazards: &'domain HazardBag,
+}
+
+/// The max number of released shields of the default domain cached per thread.
+const CACHED_SHIELDS: usize = 8;
+
+thread_local! {
+    /// Released slots of the default domain that are kept active, to be reused by `Shield::new`
+    /// of the current thread without touching the bag.
+    static CACHE: RefCell<ShieldCache> = const { RefCell::new(ShieldCache(Vec::new())) };
+}
+
+/// Storage of `CACHE`, which releases the slots when the thread exits.
+struct ShieldCache(Vec<(NonNull<SlotChunk>, u8)>);
+
+impl Drop for ShieldCache {
+    fn drop(&mut self) {
+        #[cfg(feature = "global")]
+        for (chunk, index) in self.0.drain(..) {
+            // # Safety
+            // only the slots of the default domain are cached, which are never freed.
+            release(HAZARDS.hazards(), unsafe { chunk.as_ref() }, index);
+        }
+    }
+}
 
-/// Represents the ownership of a hazard pointer slot.
-pub struct Shield {
-    slot: NonNull<HazardSlot>,
+/// Hints the CPU to load the cache line of `pointer`, on the targets that support it.
+#[inline]
+fn prefetch<T>(pointer: *const T) {
+    #[cfg(target_arch = "x86_64")]
+    // # Safety
+    // prefetching never faults, whatever the address.
+    unsafe {
+        arch::_mm_prefetch(pointer.cast(), arch::_MM_HINT_T0)
+    };
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = pointer;
 }
 
-impl Shield {
-    /// Creates a new shield for hazard pointer.
-    pub fn new(hazards: &HazardBag) -> Self {
-        let slot = hazards.acquire_slot().into();
-        Self { slot }
+/// Releases the ownership of the slot at `index` in `chunk` of `hazards`.
+fn release(hazards: &HazardBag, chunk: &SlotChunk, index: u8) {
+    #[cfg(feature = "owner-info")]
+    chunk.slots[usize::from(index)].set_owner(None);
+    let _ = chunk.active.fetch_and(!(1 << index), Ordering::Release);
+    let _ = hazards.active.fetch_sub(1, Ordering::Relaxed);
+}
+
+impl<'domain> Shield<'domain> {
+    /// Creates a new shield for hazard pointer. Shields of the default domain are taken from the
+    /// cache of the current thread first.
+    ///
+    /// Aborts if a new chunk of slots cannot be allocated, see `try_new`.
+    pub fn new(hazards: &'domain HazardBag) -> Self {
+        Self::try_new(hazards).unwrap_or_else(|_| alloc::handle_alloc_error(SlotChunk::LAYOUT))
+    }
+
+    /// Creates a new shield from a slot `HazardBag::reserve`d up front, or returns `None` if all
+    /// the reserved slots are taken. Unlike `new`, this never allocates nor takes the cache of the
+    /// current thread, e.g. for real-time threads. Other shields never take reserved slots.
+    pub fn new_reserved(hazards: &'domain HazardBag) -> Option<Self> {
+        let (chunk, index) = hazards.try_acquire_reserved()?;
+        Some(Self {
+            #[cfg(debug_assertions)]
+            generation: chunk.slots[usize::from(index)]
+                .generation
+                .load(Ordering::Relaxed),
+            #[cfg(debug_assertions)]
+            validated: Cell::new(true),
+            chunk: chunk.into(),
+            index,
+            cached: false,
+            hazards,
+        })
+    }
+
+    /// Creates a new shield as `new`, or returns an error if all the slots of `hazards` are taken
+    /// and a new chunk of them cannot be allocated, e.g. where running out of memory must not
+    /// abort.
+    pub fn try_new(hazards: &'domain HazardBag) -> Result<Self, AllocError> {
+        #[cfg(feature = "global")]
+        let cached = ptr::eq(hazards, HAZARDS.hazards());
+        #[cfg(not(feature = "global"))]
+        let cached = false;
+        let (chunk, index) = match cached
+            .then(|| CACHE.try_with(|cache| cache.borrow_mut().0.pop()))
+            .and_then(Result::ok)
+            .flatten()
+        {
+            Some(acquired) => acquired,
+            None => {
+                let (chunk, index) = hazards.try_acquire_owned()?;
+                (chunk.into(), index)
+            }
+        };
+        Ok(Self {
+            #[cfg(debug_assertions)]
+            generation: unsafe { chunk.as_ref() }.slots[usize::from(index)]
+                .generation
+                .load(Ordering::Relaxed),
+            #[cfg(debug_assertions)]
+            validated: Cell::new(true),
+            chunk,
+            index,
+            cached,
+            hazards,
+        })
+    }
+
+    /// Returns the stable index of the slot owned by this shield in its bag, which is small and
+    /// never changes, e.g. to attribute metrics to slots. Unlike the indices of
+    /// `HazardBag::slots`, it counts from the first slot allocated in the bag.
+    pub fn slot_index(&self) -> usize {
+        let _ = self.slot();
+        self.chunk().base + usize::from(self.index)
+    }
+
+    /// Returns the chunk containing the slot owned by this shield.
+    fn chunk(&self) -> &SlotChunk {
+        // # Safety
+        // the chunk is never freed while the bag is borrowed.
+        unsafe { self.chunk.as_ref() }
+    }
+
+    /// Returns the hazard slot owned by this shield.
+    ///
+    /// In debug builds, panics if the slot has been released since this shield acquired it, e.g.
+    /// because of a bitwise copy of this shield that has been dropped.
+    fn slot(&self) -> &HazardSlot {
+        let slot = &self.chunk().slots[usize::from(self.index)];
+        #[cfg(debug_assertions)]
+        assert_eq!(
+            slot.generation.load(Ordering::Relaxed),
+            self.generation,
+            "stale shield: the hazard slot was released by another shield"
+        );
+        slot
     }
 
-    /// Store `pointer` to the hazard slot.
-    pub fn set<T>(&self, pointer: *mut T) {
-        let slot = unsafe { self.slot.as_ref() };
-        slot.hazard.store(pointer as *mut (), Ordering::Relaxed);
+    #[cfg(debug_assertions)]
+    /// Panics if `pointer` is not null and not the pointer validated last by this shield, i.e.
+    /// another pointer is `set` since, so that dereferencing it is a use-after-free in waiting.
+    fn assert_validated<T>(&self, pointer: *mut T) {
+        assert!(
+            pointer.is_null()
+                || self.validated.get()
+                    && self.slot().hazard.load(Ordering::Relaxed) == pointer.cast(),
+            "dereferencing a pointer published by `Shield::set` without validating it"
+        );
+    }
+
+    #[cfg(debug_assertions)]
+    /// Records whether the published pointer is validated, for this shield and for collectors.
+    fn set_validated(&self, validated: bool) {
+        self.validated.set(validated);
+        let slot = self.slot();
+        let pointer = if validated {
+            slot.hazard.load(Ordering::Relaxed)
+        } else {
+            ptr::null_mut()
+        };
+        slot.validated.store(pointer, Ordering::Relaxed);
+    }
+
+    /// Store `pointer` to the hazard slot. The returned token must be validated before `pointer`
+    /// can be dereferenced.
+    ///
+    /// In debug builds, the guards of this shield panic when dereferenced until the token is
+    /// validated.
+    pub fn set<T>(&self, pointer: *mut T) -> Unvalidated<'_, T> {
+        // Unmark the slot before publishing, so that a collector seeing `pointer` never sees it
+        // marked by an earlier validation. See `HazardBag::for_each_checked_hazard`.
+        #[cfg(debug_assertions)]
+        {
+            self.set_validated(false);
+            fence(Ordering::Release);
+        }
+        self.slot().publish(pointer.cast(), Ordering::Relaxed);
+        #[cfg(debug_assertions)]
+        self.validated.set(pointer.is_null());
+        Unvalidated {
+            shield: self,
+            pointer,
+        }
     }
 
     /// Clear the hazard slot.
     pub fn clear(&self) {
-        self.set(ptr::null_mut::<()>())
+        let _ = self.set(ptr::null_mut::<()>());
     }
 
-    /// Check if `src` still points to `pointer`. If not, returns the current value.
+    /// Check if `src` still points to `pointer`. If not, returns an error with the current value.
     ///
     /// For a pointer `p`, if "`src` still pointing to `pointer`" implies that `p` is not retired,
     /// then `Ok(())` means that shields set to `p` are validated.
-    pub fn validate<T>(pointer: *mut T, src: &AtomicPtr<T>) -> Result<(), *mut T> {
+    pub fn validate<T>(pointer: *mut T, src: &AtomicPtr<T>) -> Result<(), ProtectError<T>> {
         let current = src.load(Ordering::Relaxed);
         // double check the pointer make sure beween the reader `load the pointer and store in the
         // hazard slot` happed before the `writer retire the pointer and scan the retired
@@ -44,83 +276,640 @@ impl Shield {
         if current == pointer {
             Ok(())
         } else {
-            Err(current)
+            Err(ProtectError::new(pointer, current))
         }
     }
 
-    /// Try protecting `pointer` obtained from `src`. If not, returns the current value.
+    /// Publishes each `pointer` to its shield, and then validates all of them against their
+    /// sources, so that the validated pointers form a consistent snapshot, e.g. the head and tail
+    /// of a queue. Failed shields are cleared, and their indices returned.
+    pub fn validate_all<T>(entries: &[(&Self, &AtomicPtr<T>, *mut T)]) -> Result<(), Vec<usize>> {
+        for &(shield, _, pointer) in entries {
+            let _ = shield.set(pointer);
+        }
+        let failed = entries
+            .iter()
+            .enumerate()
+            .filter(|&(_, &(shield, src, pointer))| {
+                let result = Self::validate(pointer, src);
+                #[cfg(debug_assertions)]
+                shield.set_validated(result.is_ok());
+                result.inspect_err(|_| shield.clear()).is_err()
+            })
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(failed)
+        }
+    }
+
+    /// Try protecting `pointer` obtained from `src`. If not, returns an error with the current
+    /// value.
     ///
     /// If "`src` still pointing to `pointer`" implies that `pointer` is not retired, then `Ok(())`
     /// means that this shield is validated.
-    pub fn try_protect<T>(&self, pointer: *mut T, src: &AtomicPtr<T>) -> Result<(), *mut T> {
-        self.set(pointer);
-        Self::validate(pointer, src).inspect_err(|_| self.clear())
+    pub fn try_protect<T>(
+        &self,
+        pointer: *mut T,
+        src: &AtomicPtr<T>,
+    ) -> Result<(), ProtectError<T>> {
+        let unvalidated = self.set(pointer);
+        #[cfg(any(test, feature = "test-hooks"))]
+        hooks::run(HookPoint::Published);
+        unvalidated.validate(src).map(drop)
     }
 
     /// Get a protected pointer from `src`.
     ///
     /// See `try_protect()`.
+    #[must_use = "protecting a pointer without using it is a no-op"]
     pub fn protect<T>(&self, src: &AtomicPtr<T>) -> *mut T {
         let mut pointer = src.load(Ordering::Relaxed);
-        while let Err(new) = self.try_protect(pointer, src) {
-            pointer = new;
-            #[cfg(feature = "check-loom")]
-            loom::sync::atomic::spin_loop_hint();
+        let mut backoff = Backoff::new();
+        loop {
+            #[cfg(any(test, feature = "test-hooks"))]
+            hooks::run(HookPoint::Loaded);
+            match self.try_protect(pointer, src) {
+                Ok(()) => break,
+                Err(err) => pointer = err.observed(),
+            }
+            backoff.snooze();
         }
         pointer
     }
+
+    /// Protects the pointer loaded from `src` as `protect`, retrying at most `max_retries` times.
+    /// If `src` keeps changing, clears this shield and returns the error of the last attempt with
+    /// the number of retries, e.g. to bound the latency of a reader at the cost of falling back.
+    pub fn try_protect_for<T>(
+        &self,
+        src: &AtomicPtr<T>,
+        max_retries: usize,
+    ) -> Result<*mut T, ProtectError<T>> {
+        let mut pointer = src.load(Ordering::Relaxed);
+        let mut backoff = Backoff::new();
+        for retries in 0.. {
+            #[cfg(any(test, feature = "test-hooks"))]
+            hooks::run(HookPoint::Loaded);
+            match self.try_protect(pointer, src) {
+                Ok(()) => return Ok(pointer),
+                Err(err) if retries == max_retries => {
+                    self.clear();
+                    return Err(err.with_retries(retries));
+                }
+                Err(err) => pointer = err.observed(),
+            }
+            backoff.snooze();
+        }
+        unreachable!()
+    }
+
+    /// Protects the pointer loaded from `src` and returns a reference to the object for as long as
+    /// this shield is borrowed, or `None` if the pointer is null.
+    ///
+    /// # Safety
+    ///
+    /// The source must point only to valid objects that are retired before freed.
+    pub unsafe fn protect_ref<T>(&self, src: &AtomicPtr<T>) -> Option<&T> {
+        unsafe { self.protect(src).as_ref() }
+    }
+
+    /// Moves the protection of this shield to `to`, e.g. a shield acquired by the thread that
+    /// continues using the pointer. The shields swap their slots, so the pointer stays published
+    /// in the same slot throughout, and this shield is left with the slot of `to`, cleared.
+    pub fn hand_over(&mut self, to: &mut Shield<'domain>) {
+        assert!(
+            ptr::eq(self.hazards, to.hazards),
+            "`hand_over` between shields of different bags"
+        );
+        // The recorded owners follow the shields, not the slots.
+        #[cfg(feature = "owner-info")]
+        {
+            let owner = self.slot().owner();
+            self.slot().set_owner(to.slot().owner());
+            to.slot().set_owner(owner);
+        }
+        mem::swap(self, to);
+        #[cfg(any(test, feature = "test-hooks"))]
+        hooks::run(HookPoint::HandedOver);
+        self.clear();
+    }
+
+    /// Consumes the shield into an opaque token of its slot, e.g. to stash it in a C structure or
+    /// a task of a custom scheduler. The slot stays acquired and keeps protecting its pointer
+    /// until the token is turned back into a shield with `from_raw` and dropped.
+    pub fn into_raw(self) -> *mut () {
+        let shield = mem::ManuallyDrop::new(self);
+        ptr::from_ref(shield.slot()).cast_mut().cast()
+    }
+
+    /// Reconstitutes a shield from a token returned by `into_raw`, possibly on another thread.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must be returned by `into_raw` of a shield of `hazards`, and each token must be
+    /// reconstituted only once.
+    pub unsafe fn from_raw(hazards: &'domain HazardBag, raw: *mut ()) -> Self {
+        let slot = raw.cast::<HazardSlot>();
+        let chunk = hazards
+            .chunks()
+            .find(|chunk| chunk.slots.as_ptr_range().contains(&slot.cast_const()))
+            .expect("the token is not of a slot of `hazards`");
+        #[cfg(feature = "global")]
+        let cached = ptr::eq(hazards, HAZARDS.hazards());
+        #[cfg(not(feature = "global"))]
+        let cached = false;
+        // # Safety
+        // `raw` is a pointer to a slot of `chunk`.
+        let index = chunk.index_of(unsafe { &*slot });
+        Self {
+            #[cfg(debug_assertions)]
+            generation: chunk.slots[usize::from(index)]
+                .generation
+                .load(Ordering::Relaxed),
+            #[cfg(debug_assertions)]
+            validated: Cell::new(true),
+            chunk: chunk.into(),
+            index,
+            cached,
+            hazards,
+        }
+    }
 }
 
-impl Default for Shield {
+/// A raw handle of an acquired hazard slot of a bag that outlives `'domain`, for building
+/// protection layers other than `Shield`, e.g. a table of protections per task.
+///
+/// Acquiring, publishing, clearing and releasing are separate operations with explicit orderings,
+/// and nothing is done implicitly: the handle is `Copy`, and the slot stays acquired until
+/// `release`d. Scanning the hazards of the bag, e.g. in `collect`, observes the published pointer.
+///
+/// ```
+/// use std::sync::atomic::Ordering;
+/// use hazard::{HazardBag, SlotHandle};
+///
+/// let hazards = HazardBag::new();
+/// let handle = SlotHandle::acquire(&hazards);
+/// let pointer = Box::into_raw(Box::new(1));
+/// handle.publish(pointer, Ordering::SeqCst);
+/// assert_eq!(handle.hazard(Ordering::Relaxed), pointer.cast());
+/// handle.clear(Ordering::Release);
+/// unsafe { handle.release() };
+/// # drop(unsafe { Box::from_raw(pointer) });
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct SlotHandle<'domain> {
+    // The chunk containing the slot, and the index of the slot in it.
+    chunk: NonNull<SlotChunk>,
+    index: u8,
+    hazards: &'domain HazardBag,
+}
+
+// The slot is only accessed atomically, and released once as required by `release`.
+unsafe impl Send for SlotHandle<'_> {}
+unsafe impl Sync for SlotHandle<'_> {}
+
+impl<'domain> SlotHandle<'domain> {
+    /// Acquires an inactive slot of `hazards`, allocating a new chunk of slots if there is none.
+    /// Unlike `Shield::new`, this never takes a slot from the cache of the current thread.
+    pub fn acquire(hazards: &'domain HazardBag) -> Self {
+        let (chunk, index) = hazards.acquire_owned();
+        Self {
+            chunk: chunk.into(),
+            index,
+            hazards,
+        }
+    }
+
+    fn chunk(&self) -> &'domain SlotChunk {
+        // # Safety
+        // the chunk is in the bag, which outlives `'domain`.
+        unsafe { self.chunk.as_ref() }
+    }
+
+    fn slot(&self) -> &'domain HazardSlot {
+        &self.chunk().slots[usize::from(self.index)]
+    }
+
+    /// Publishes `pointer` as the hazard of the slot with `order`. The caller validates it as
+    /// needed, e.g. with `Shield::validate`.
+    pub fn publish<T>(&self, pointer: *mut T, order: atomic::Ordering) {
+        self.slot().publish(pointer.cast(), order);
+    }
+
+    /// Returns the hazard of the slot loaded with `order`.
+    pub fn hazard(&self, order: atomic::Ordering) -> *mut () {
+        self.slot().hazard.load(ordering::upgrade(order))
+    }
+
+    /// Clears the hazard of the slot with `order`.
+    pub fn clear(&self, order: atomic::Ordering) {
+        self.publish(ptr::null_mut::<()>(), order);
+    }
+
+    /// Clears and releases the slot, so that it may be acquired again.
+    ///
+    /// # Safety
+    ///
+    /// The slot must be released only once, and no copy of this handle may be used afterwards.
+    pub unsafe fn release(self) {
+        let slot = self.slot();
+        slot.publish(ptr::null_mut(), Ordering::Relaxed);
+        #[cfg(debug_assertions)]
+        slot.generation.fetch_add(1, Ordering::Relaxed);
+        release(self.hazards, self.chunk(), self.index);
+    }
+}
+
+#[cfg(feature = "global")]
+impl Shield<'static> {
+    /// Creates a new shield of the default domain `HAZARDS`, as `Shield::default`.
+    pub fn new_global() -> Self {
+        Self::new(HAZARDS.hazards())
+    }
+}
+
+#[cfg(feature = "global")]
+impl Default for Shield<'static> {
+    /// Creates a new shield of the default domain, see `new_global`.
     fn default() -> Self {
-        Self::new(&HAZARDS)
+        Self::new_global()
     }
 }
 
-impl Drop for Shield {
-    /// Clear and release the ownership of the hazard slot.
+impl Drop for Shield<'_> {
+    /// Clear and release the ownership of the hazard slot, or put it in the cache of the current
+    /// thread if there is room.
     fn drop(&mut self) {
-        let slot = unsafe { self.slot.as_ref() };
+        let slot = self.slot();
         slot.hazard.store(ptr::null_mut(), Ordering::Relaxed);
-        slot.active.store(false, Ordering::Release);
+        #[cfg(feature = "watchdog")]
+        slot.set_since(None);
+        #[cfg(debug_assertions)]
+        slot.generation.fetch_add(1, Ordering::Relaxed);
+        if self.cached
+            && CACHE
+                .try_with(|cache| {
+                    let mut cache = cache.borrow_mut();
+                    let room = cache.0.len() < CACHED_SHIELDS;
+                    if room {
+                        cache.0.push((self.chunk, self.index));
+                    }
+                    room
+                })
+                .unwrap_or(false)
+        {
+            return;
+        }
+        release(self.hazards, self.chunk(), self.index);
     }
 }
 
-impl fmt::Debug for Shield {
+// The slot is accessed only atomically, so it may be used and released by any thread.
+unsafe impl Send for Shield<'_> {}
+
+impl fmt::Debug for Shield<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Not `slot`, which panics for a stale shield.
+        let slot = &self.chunk().slots[usize::from(self.index)];
         f.debug_struct("Shield")
-            .field("slot address", &self.slot)
-            .field("slot data", unsafe { self.slot.as_ref() })
+            .field("slot address", &ptr::from_ref(slot))
+            .field("slot data", slot)
             .finish()
     }
 }
 
-/// Global bag (multiset) of hazards pointers.
-/// `HazardBag.head` and `HazardSlot.next` form a grow-only list of all hazard slots. Slots are
-/// never removed from this list. Instead, it gets deactivated and recycled for other `Shield`s.
+/// A pointer protected by a shield of its own. See `load_protected`.
+///
+/// It is `Send` for `T: Sync`, so it may be held across an `.await` by a task of a multi-threaded
+/// executor.
+#[derive(Debug)]
+#[must_use = "the pointer is unprotected as soon as the guard is dropped"]
+pub struct Protected<'domain, T> {
+    shield: Shield<'domain>,
+    pointer: *mut T,
+}
+
+// The pointer is protected by the shield wherever it is sent, and only shared references to the
+// object are handed out.
+unsafe impl<T: Sync> Send for Protected<'_, T> {}
+
+impl<'domain, T> Protected<'domain, T> {
+    /// Protects the pointer loaded from `src` with `shield`.
+    pub fn new(shield: Shield<'domain>, src: &AtomicPtr<T>) -> Self {
+        let pointer = shield.protect(src);
+        Self { shield, pointer }
+    }
+
+    /// Returns the protected pointer.
+    pub fn as_ptr(&self) -> *mut T {
+        self.pointer
+    }
+
+    /// Returns a reference to the protected object, or `None` if the pointer is null.
+    ///
+    /// # Safety
+    ///
+    /// The source must point only to valid objects that are retired before freed.
+    pub unsafe fn as_ref(&self) -> Option<&T> {
+        #[cfg(debug_assertions)]
+        self.shield.assert_validated(self.pointer);
+        unsafe { self.pointer.as_ref() }
+    }
+
+    /// Returns the shield protecting the pointer, to reuse it after the pointer is no longer
+    /// accessed.
+    pub fn into_shield(self) -> Shield<'domain> {
+        self.shield
+    }
+
+    /// Moves the protection to `shield` without a window where the pointer is unprotected, and
+    /// releases the previous shield. See `Shield::hand_over`.
+    pub fn reattach(mut self, mut shield: Shield<'domain>) -> Self {
+        self.shield.hand_over(&mut shield);
+        Self {
+            shield,
+            pointer: self.pointer,
+        }
+    }
+}
+
+/// A pointer published to a shield but not validated yet, so it may already be freed and cannot be
+/// dereferenced. See `Shield::set`.
+#[derive(Debug)]
+#[must_use = "the pointer must be validated before it is dereferenced"]
+pub struct Unvalidated<'s, T> {
+    shield: &'s Shield<'s>,
+    pointer: *mut T,
+}
+
+impl<'s, T> Unvalidated<'s, T> {
+    /// Returns the published pointer, which must not be dereferenced.
+    pub fn as_ptr(&self) -> *mut T {
+        self.pointer
+    }
+
+    /// Checks that `src` still points to the published pointer, which upgrades it to a guard that
+    /// can be dereferenced. Otherwise, clears the shield and returns an error with the current
+    /// value. See `Shield::validate`.
+    pub fn validate(self, src: &AtomicPtr<T>) -> Result<Validated<'s, T>, ProtectError<T>> {
+        match Shield::validate(self.pointer, src) {
+            Ok(()) => {
+                #[cfg(debug_assertions)]
+                self.shield.set_validated(true);
+                Ok(Validated {
+                    #[cfg(debug_assertions)]
+                    shield: self.shield,
+                    pointer: self.pointer,
+                    _marker: PhantomData,
+                })
+            }
+            Err(err) => {
+                self.shield.clear();
+                Err(err)
+            }
+        }
+    }
+}
+
+/// A pointer validated after being published to the shield borrowed for `'s`.
 #[derive(Debug)]
+#[must_use = "the pointer is validated only as long as the guard is borrowed"]
+pub struct Validated<'s, T> {
+    #[cfg(debug_assertions)]
+    shield: &'s Shield<'s>,
+    pointer: *mut T,
+    _marker: PhantomData<&'s ()>,
+}
+
+impl<'s, T> Validated<'s, T> {
+    /// Returns the protected pointer.
+    pub fn as_ptr(&self) -> *mut T {
+        self.pointer
+    }
+
+    /// Returns a reference to the protected object, or `None` if the pointer is null.
+    ///
+    /// # Safety
+    ///
+    /// The source must point only to valid objects that are retired before freed.
+    pub unsafe fn as_ref(&self) -> Option<&'s T> {
+        #[cfg(debug_assertions)]
+        self.shield.assert_validated(self.pointer);
+        unsafe { self.pointer.as_ref() }
+    }
+}
+
+/// Global bag (multiset) of hazards pointers.
+/// `HazardBag.head` and `SlotChunk.next` form a grow-only list of chunks of hazard slots. Slots
+/// are never removed from this list. Instead, it gets deactivated and recycled for other
+/// `Shield`s. Each chunk tracks which of its slots are active in a bitmap, so that searching for
+/// an inactive slot and skipping inactive chunks are cheap.
 pub struct HazardBag {
-    head: AtomicPtr<HazardSlot>,
+    head: AtomicPtr<SlotChunk>,
+    /// The number of active slots, see `active_slots`.
+    active: AtomicUsize,
+    /// The number of reserved slots, see `reserve`.
+    reserved: AtomicUsize,
+    /// `fn(DomainEvent)` called on the events of the domain owning the bag, or null.
+    event_hook: AtomicPtr<()>,
+    allocator: SlotAllocator,
+}
+
+/// The functions allocating and freeing the chunks of slots of a `HazardBag`, and the retired
+/// pointer lists of the domain owning it, e.g. to place them in an arena or to instrument them.
+///
+/// ```
+/// use std::alloc::{self, Layout};
+/// use hazard::{HazardBag, Shield, SlotAllocator};
+///
+/// unsafe fn alloc(layout: Layout) -> *mut u8 {
+///     unsafe { alloc::alloc(layout) }
+/// }
+///
+/// let hazards = HazardBag::with_allocator(SlotAllocator { alloc, ..SlotAllocator::GLOBAL });
+/// let shield = Shield::new(&hazards);
+/// # drop(shield);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct SlotAllocator {
+    /// Allocates memory of `layout` as `std::alloc::alloc`, or returns null if it fails.
+    pub alloc: unsafe fn(Layout) -> *mut u8,
+    /// Frees memory returned by `alloc` with the same layout, as `std::alloc::dealloc`.
+    pub dealloc: unsafe fn(*mut u8, Layout),
+}
+
+impl SlotAllocator {
+    /// The global allocator.
+    pub const GLOBAL: Self = Self {
+        alloc: alloc::alloc,
+        dealloc: alloc::dealloc,
+    };
+}
+
+impl Default for SlotAllocator {
+    fn default() -> Self {
+        Self::GLOBAL
+    }
+}
+
+/// The thread that acquired a hazard slot, recorded with the `owner-info` feature for
+/// diagnostics.
+#[cfg(feature = "owner-info")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotOwner {
+    /// Id of the owner thread.
+    pub id: ThreadId,
+    /// Name of the owner thread, if any.
+    pub name: Option<String>,
+    /// When the slot was acquired.
+    pub acquired: Instant,
+}
+
+#[cfg(feature = "owner-info")]
+impl SlotOwner {
+    /// Returns the owner information of the current thread.
+    fn current() -> Self {
+        let thread = thread::current();
+        Self {
+            id: thread.id(),
+            name: thread.name().map(String::from),
+            acquired: Instant::now(),
+        }
+    }
+}
+
+/// The number of slots in a `SlotChunk`, at most the number of bits in its bitmap.
+#[cfg(not(feature = "check-loom"))]
+const SLOTS_PER_CHUNK: usize = 64;
+// keep the models small
+#[cfg(feature = "check-loom")]
+const SLOTS_PER_CHUNK: usize = 4;
+
+/// See `HazardBag`
+#[derive(Debug)]
+struct SlotChunk {
+    // Bit `i` is set iff `slots[i]` is occupied by a `Shield`.
+    active: AtomicU64,
+    // Bit `i` is set iff `slots[i]` is reserved for `Shield::new_reserved`. Bits are only set
+    // while their slot is active.
+    reserved: AtomicU64,
+    slots: [HazardSlot; SLOTS_PER_CHUNK],
+    // Immutable pointer to the next chunk in the bag.
+    next: *const SlotChunk,
+    // The stable index of the first slot, counting from the first chunk allocated in the bag.
+    base: usize,
+}
+
+impl SlotChunk {
+    /// The layout of a chunk, allocated by `HazardBag::acquire_slot`.
+    const LAYOUT: Layout = Layout::new::<Self>();
+
+    /// Creates a new chunk whose slots set in `active` are active, and those set in `reserved` are
+    /// reserved.
+    fn new(active: u64, reserved: u64) -> Self {
+        Self {
+            active: AtomicU64::new(active),
+            reserved: AtomicU64::new(reserved),
+            slots: array::from_fn(|_| HazardSlot::new()),
+            next: ptr::null(),
+            base: 0,
+        }
+    }
+
+    /// Returns the index of `slot` in this chunk.
+    fn index_of(&self, slot: &HazardSlot) -> u8 {
+        // # Safety
+        // `slot` is in `self.slots`.
+        unsafe { (slot as *const HazardSlot).offset_from(self.slots.as_ptr()) as u8 }
+    }
+
+    /// Find an inactive slot, reserved or not as `reserved`, and activate it.
+    fn try_acquire_inactive(&self, reserved: bool) -> Option<usize> {
+        let kind = |mask: u64| if reserved { mask } else { !mask };
+        let mut mask = kind(self.reserved.load(Ordering::Relaxed));
+        let mut active = self.active.load(Ordering::Relaxed);
+        loop {
+            let index = (!active & mask).trailing_zeros() as usize;
+            if index >= SLOTS_PER_CHUNK {
+                return None;
+            }
+            let bit = 1 << index;
+            active = self.active.fetch_or(bit, Ordering::Acquire);
+            if active & bit != 0 {
+                continue;
+            }
+            // The slot may have been reserved since `mask` was loaded, which is then visible as
+            // it is done before the slot is released.
+            mask = kind(self.reserved.load(Ordering::Relaxed));
+            if mask & bit != 0 {
+                return Some(index);
+            }
+            active = self.active.fetch_and(!bit, Ordering::Release) & !bit;
+        }
+    }
 }
 
 /// See `HazardBag`
 #[derive(Debug)]
 struct HazardSlot {
-    // Whether this slot is occupied by a `Shield`.
-    active: AtomicBool,
     // Machine representation of the hazard pointer.
     hazard: AtomicPtr<()>,
-    // Immutable pointer to the next slot in the bag.
-    next: *const HazardSlot,
+    // Number of times this slot has been released. Used to detect stale shields.
+    #[cfg(debug_assertions)]
+    generation: AtomicUsize,
+    // The hazard last validated by the shield of this slot, if it is still published. Used to
+    // tell a protection that skipped validation from a bug of the collector.
+    #[cfg(debug_assertions)]
+    validated: AtomicPtr<()>,
+    // The thread owning this slot, if active.
+    #[cfg(feature = "owner-info")]
+    owner: Mutex<Option<SlotOwner>>,
+    // When the slot started protecting its hazard, or `None` if it is null or already warned
+    // about.
+    #[cfg(feature = "watchdog")]
+    since: Mutex<Option<Instant>>,
 }
 
 impl HazardSlot {
     fn new() -> Self {
         Self {
-            active: AtomicBool::new(true),
             hazard: AtomicPtr::new(ptr::null_mut()),
-            next: ptr::null(),
+            #[cfg(debug_assertions)]
+            generation: AtomicUsize::new(0),
+            #[cfg(debug_assertions)]
+            validated: AtomicPtr::new(ptr::null_mut()),
+            #[cfg(feature = "owner-info")]
+            owner: Mutex::new(None),
+            #[cfg(feature = "watchdog")]
+            since: Mutex::new(None),
+        }
+    }
+
+    /// Stores `pointer` as the hazard with `order`.
+    fn publish(&self, pointer: *mut (), order: atomic::Ordering) {
+        #[cfg(feature = "watchdog")]
+        if self.hazard.load(Ordering::Relaxed) != pointer {
+            self.set_since((!pointer.is_null()).then(Instant::now));
         }
+        self.hazard.store(pointer, ordering::upgrade(order));
+    }
+
+    #[cfg(feature = "watchdog")]
+    fn set_since(&self, since: Option<Instant>) {
+        *self.since.lock().unwrap_or_else(|e| e.into_inner()) = since;
+    }
+
+    #[cfg(feature = "owner-info")]
+    fn set_owner(&self, owner: Option<SlotOwner>) {
+        *self.owner.lock().unwrap_or_else(|e| e.into_inner()) = owner;
+    }
+
+    #[cfg(feature = "owner-info")]
+    fn owner(&self) -> Option<SlotOwner> {
+        self.owner.lock().unwrap_or_else(|e| e.into_inner()).clone()
     }
 }
 
@@ -128,75 +917,373 @@ impl HazardBag {
     #[cfg(not(feature = "check-loom"))]
     /// Creates a new global hazard set.
     pub const fn new() -> Self {
+        Self::with_allocator(SlotAllocator::GLOBAL)
+    }
+
+    #[cfg(feature = "check-loom")]
+    /// Creates a new global hazard set.
+    pub fn new() -> Self {
+        Self::with_allocator(SlotAllocator::GLOBAL)
+    }
+
+    #[cfg(not(feature = "check-loom"))]
+    /// Creates a new hazard set whose chunks of slots are allocated with `allocator`.
+    pub const fn with_allocator(allocator: SlotAllocator) -> Self {
         Self {
             head: AtomicPtr::new(ptr::null_mut()),
+            active: AtomicUsize::new(0),
+            reserved: AtomicUsize::new(0),
+            event_hook: AtomicPtr::new(ptr::null_mut()),
+            allocator,
         }
     }
 
     #[cfg(feature = "check-loom")]
-    /// Creates a new global hazard set.
-    pub fn new() -> Self {
+    /// Creates a new hazard set whose chunks of slots are allocated with `allocator`.
+    pub fn with_allocator(allocator: SlotAllocator) -> Self {
         Self {
             head: AtomicPtr::new(ptr::null_mut()),
+            active: AtomicUsize::new(0),
+            reserved: AtomicUsize::new(0),
+            event_hook: AtomicPtr::new(ptr::null_mut()),
+            allocator,
         }
     }
 
-    /// Acquires a slot in the hazard set, either by recycling an inactive slot or allocating a new
-    /// slot.
-    fn acquire_slot(&self) -> &HazardSlot {
-        if let Some(slot) = self.try_acquire_inactive() {
-            return slot;
+    /// Returns the allocator of the chunks of slots, which also allocates the retired pointer lists
+    /// of the domain owning the bag.
+    pub(crate) fn allocator(&self) -> SlotAllocator {
+        self.allocator
+    }
+
+    /// Sets `hook` to be called on the events of the domain owning the bag, or removes it if null.
+    pub(crate) fn set_event_hook(&self, hook: *mut ()) {
+        self.event_hook.store(hook, Ordering::Release);
+    }
+
+    /// Calls the event hook with `event`, if any.
+    pub(crate) fn event(&self, event: DomainEvent) {
+        let hook = self.event_hook.load(Ordering::Acquire);
+        if !hook.is_null() {
+            // # Safety
+            // only `Domain::set_event_hook` stores non-null pointers, which are `fn(DomainEvent)`.
+            let hook = unsafe { mem::transmute::<*mut (), fn(DomainEvent)>(hook) };
+            hook(event);
         }
+    }
 
-        // No inactive slot found, allocate a new slot.
-        let slot = Box::new(HazardSlot::new());
+    /// Returns an iterator over the chunks of slots in the bag. The next chunk is prefetched when
+    /// one is yielded, to hide the latency of chasing the list behind the work on the chunk.
+    fn chunks(&self) -> impl Iterator<Item = &SlotChunk> {
+        // # Safety
+        // chunks are never freed while the bag is borrowed.
+        let head = unsafe { self.head.load(Ordering::Acquire).as_ref() };
+        iter::successors(head, |chunk| unsafe { chunk.next.as_ref() }).inspect(|chunk| {
+            if let Some(next) = unsafe { chunk.next.as_ref() } {
+                prefetch(&next.active);
+            }
+        })
+    }
 
-        // Link the new slot to the head of the list.
-        let slot_ptr = Box::into_raw(slot);
+    /// Acquires a slot as `try_acquire_owned`, aborting if a new chunk cannot be allocated.
+    fn acquire_owned(&self) -> (&SlotChunk, u8) {
+        self.try_acquire_owned()
+            .unwrap_or_else(|_| alloc::handle_alloc_error(SlotChunk::LAYOUT))
+    }
+
+    /// Acquires an inactive reserved slot as `try_acquire_owned`, or returns `None` instead of
+    /// allocating.
+    fn try_acquire_reserved(&self) -> Option<(&SlotChunk, u8)> {
+        let (chunk, index) = self.try_acquire_inactive(true)?;
+        Some(self.own(chunk, index))
+    }
+
+    /// Acquires a slot as `acquire_slot`, counting it as active and recording the current thread as
+    /// its owner. Returns the chunk of the slot and its index in the chunk.
+    fn try_acquire_owned(&self) -> Result<(&SlotChunk, u8), AllocError> {
+        let (chunk, index) = self.acquire_slot()?;
+        Ok(self.own(chunk, index))
+    }
+
+    /// Counts the slot just acquired at `index` in `chunk` as active, and records the current
+    /// thread as its owner.
+    fn own<'s>(&self, chunk: &'s SlotChunk, index: usize) -> (&'s SlotChunk, u8) {
+        let _ = self.active.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "owner-info")]
+        chunk.slots[index].set_owner(Some(SlotOwner::current()));
+        (chunk, index as u8)
+    }
+
+    /// Acquires an unreserved slot in the hazard set, either by recycling an inactive slot or
+    /// allocating a new chunk of slots. Returns the chunk and the index of the slot in it, or an
+    /// error if the chunk cannot be allocated.
+    fn acquire_slot(&self) -> Result<(&SlotChunk, usize), AllocError> {
+        if let Some(acquired) = self.try_acquire_inactive(false) {
+            return Ok(acquired);
+        }
+        // No inactive slot found, allocate a new chunk and take its first slot.
+        Ok((self.push_chunk(1, 0)?, 0))
+    }
+
+    /// Allocates a new chunk whose slots set in `active` are active, and those set in `reserved`
+    /// are reserved, and links it to the bag. It is freed with the bag.
+    fn push_chunk(&self, active: u64, reserved: u64) -> Result<&SlotChunk, AllocError> {
+        // # Safety
+        // `SlotChunk` is not zero-sized.
+        let chunk_ptr = unsafe { (self.allocator.alloc)(SlotChunk::LAYOUT) }.cast::<SlotChunk>();
+        if chunk_ptr.is_null() {
+            return Err(AllocError);
+        }
+        unsafe { chunk_ptr.write(SlotChunk::new(active, reserved)) };
+
+        // Link the new chunk to the head of the list.
+        let mut backoff = Backoff::new();
         loop {
-            let head = self.head.load(Ordering::Relaxed);
-            unsafe { slot_ptr.as_mut().unwrap().next = head };
+            // Acquire the base of the head, which is written before it is linked.
+            let head = self.head.load(Ordering::Acquire);
+            let chunk = unsafe { chunk_ptr.as_mut().unwrap() };
+            chunk.next = head;
+            chunk.base = unsafe { head.as_ref() }.map_or(0, |head| head.base + SLOTS_PER_CHUNK);
             if self
                 .head
-                .compare_exchange_weak(head, slot_ptr, Ordering::AcqRel, Ordering::Relaxed)
+                .compare_exchange_weak(head, chunk_ptr, Ordering::AcqRel, Ordering::Relaxed)
                 .is_ok()
             {
-                return unsafe { &*slot_ptr };
+                self.event(DomainEvent::SlotsAllocated {
+                    slots: chunk.base + SLOTS_PER_CHUNK,
+                });
+                return Ok(unsafe { &*chunk_ptr });
             }
+            backoff.snooze();
         }
     }
 
-    /// Find an inactive slot and activate it.
-    fn try_acquire_inactive(&self) -> Option<&HazardSlot> {
-        let mut slot_ptr = self.head.load(Ordering::Relaxed);
-        while !slot_ptr.is_null() {
-            let slot = unsafe { &*slot_ptr };
-            if !slot.active.load(Ordering::Relaxed)
-                && slot
-                    .active
-                    .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
-                    .is_ok()
-            {
-                return Some(slot);
+    /// Reserves slots until at least `slots` of them are reserved, allocating chunks as needed.
+    /// Reserved slots are only taken by `Shield::new_reserved`, so that taking up to `slots` of
+    /// them at once never allocates nor competes with the other shields of the bag.
+    pub fn reserve(&self, slots: usize) -> Result<(), AllocError> {
+        while self
+            .reserved
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |reserved| {
+                (reserved < slots).then_some(reserved + 1)
+            })
+            .is_ok()
+        {
+            // Reserve an inactive slot while holding it, so that it is not taken meanwhile.
+            if let Some((chunk, index)) = self.try_acquire_inactive(false) {
+                let bit = 1 << index;
+                let _ = chunk.reserved.fetch_or(bit, Ordering::Relaxed);
+                let _ = chunk.active.fetch_and(!bit, Ordering::Release);
+            } else if let Err(err) = self.push_chunk(0, 1) {
+                let _ = self.reserved.fetch_sub(1, Ordering::Relaxed);
+                return Err(err);
             }
-            slot_ptr = slot.next as *mut HazardSlot;
         }
-        None
+        Ok(())
+    }
+
+    /// Returns the number of slots in the bag, active or not.
+    pub fn capacity(&self) -> usize {
+        // # Safety
+        // chunks are never freed while the bag is borrowed.
+        unsafe { self.head.load(Ordering::Acquire).as_ref() }
+            .map_or(0, |head| head.base + SLOTS_PER_CHUNK)
+    }
+
+    /// Find an inactive slot, reserved or not as `reserved`, and activate it.
+    fn try_acquire_inactive(&self, reserved: bool) -> Option<(&SlotChunk, usize)> {
+        #[cfg(feature = "fault-injection")]
+        if fault::one_in(4) {
+            return None;
+        }
+        self.chunks()
+            .find_map(|chunk| Some((chunk, chunk.try_acquire_inactive(reserved)?)))
     }
 
     /// Returns all the hazards in the set.
     pub fn all_hazards(&self) -> HashSet<*mut ()> {
         let mut hazards = HashSet::new();
-        let mut slot_ptr = self.head.load(Ordering::Relaxed);
-        while !slot_ptr.is_null() {
-            let slot = unsafe { &*slot_ptr };
+        self.for_each_hazard(|hazard| {
+            let _ = hazards.insert(hazard);
+        });
+        hazards
+    }
+
+    /// Blocks until no slot protects `pointer`, or `timeout` elapses. Returns `true` if `pointer`
+    /// is no longer protected, for deterministic assertions on reclamation in tests.
+    #[cfg(any(test, feature = "test-hooks"))]
+    pub fn wait_until_unprotected<T>(&self, pointer: *mut T, timeout: Duration) -> bool {
+        let start = Instant::now();
+        let mut backoff = Backoff::parking();
+        loop {
+            let mut protected = false;
+            self.for_each_hazard(|hazard| protected |= hazard == pointer.cast());
+            if !protected {
+                return true;
+            }
+            if start.elapsed() >= timeout {
+                return false;
+            }
+            backoff.snooze();
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    /// Calls `f` with each hazard in the set, and whether the shield publishing it validated it
+    /// against its source.
+    pub(crate) fn for_each_checked_hazard(&self, mut f: impl FnMut(*mut (), bool)) {
+        for chunk in self.chunks() {
+            let mut active = chunk.active.load(Ordering::Relaxed);
+            while active != 0 {
+                let slot = &chunk.slots[active.trailing_zeros() as usize];
+                active &= active - 1;
+                let hazard = slot.hazard.load(Ordering::Relaxed);
+                if !hazard.is_null() {
+                    // Pairs with the fence of `Shield::set`, so the slot is unmarked by then.
+                    fence(Ordering::Acquire);
+                    f(hazard, slot.validated.load(Ordering::Relaxed) == hazard);
+                }
+            }
+        }
+    }
+
+    /// Calls `f` with each hazard in the set, possibly more than once for the same hazard.
+    pub(crate) fn for_each_hazard(&self, mut f: impl FnMut(*mut ())) {
+        for chunk in self.chunks() {
+            let mut active = chunk.active.load(Ordering::Relaxed);
+            // a released slot is cleared before deactivated, so only active slots have hazards.
+            while active != 0 {
+                let index = active.trailing_zeros() as usize;
+                active &= active - 1;
+                let hazard = chunk.slots[index].hazard.load(Ordering::Relaxed);
+                if !hazard.is_null() {
+                    f(hazard);
+                }
+            }
+        }
+    }
+
+    /// Returns `(index, owner)` of all the slots in the set that have an owner, where `index` is
+    /// the same as in `slots`.
+    #[cfg(feature = "owner-info")]
+    pub fn owners(&self) -> Vec<(usize, SlotOwner)> {
+        self.indexed_slots()
+            .filter_map(|(index, slot)| Some((index, slot.owner()?)))
+            .collect()
+    }
+
+    /// Returns `(index, hazard, owner)` of all the owned slots with a hazard, as `slots` and
+    /// `owners` in one pass so that the indices agree.
+    #[cfg(feature = "owner-info")]
+    pub(crate) fn held(&self) -> Vec<(usize, *mut (), SlotOwner)> {
+        self.indexed_slots()
+            .filter_map(|(index, slot)| {
+                let hazard = slot.hazard.load(Ordering::Relaxed);
+                Some((index, hazard, slot.owner().filter(|_| !hazard.is_null())?))
+            })
+            .collect()
+    }
+
+    /// Describes the slots that have protected the same pointer for `limit` or longer, with their
+    /// owners if recorded, or returns `None` if there are none. Each protection is reported once.
+    #[cfg(feature = "watchdog")]
+    pub(crate) fn hold_report(&self, limit: Duration) -> Option<String> {
+        use core::fmt::Write;
+
+        let mut report = String::new();
+        for (index, slot) in self.indexed_slots() {
+            let mut since = slot.since.lock().unwrap_or_else(|e| e.into_inner());
+            let Some(held) = since
+                .map(|since| since.elapsed())
+                .filter(|&held| held >= limit)
+            else {
+                continue;
+            };
+            *since = None;
             let hazard = slot.hazard.load(Ordering::Relaxed);
-            if !hazard.is_null() {
-                hazards.insert(hazard);
+            let _ = write!(
+                report,
+                "\n  slot {index} has protected {hazard:p} for {held:?}"
+            );
+            #[cfg(feature = "owner-info")]
+            if let Some(owner) = slot.owner() {
+                let _ = write!(report, " ({:?} {:?})", owner.id, owner.name);
             }
-            slot_ptr = slot.next as *mut HazardSlot;
         }
-        hazards
+        (!report.is_empty())
+            .then(|| format!("hazard: shields hold protections for {limit:?} or longer{report}"))
+    }
+
+    /// Returns roughly how many slots are active, i.e. owned by shields or slot handles, or cached
+    /// by threads for reuse. Unlike counting the active `slots`, this takes constant time, e.g.
+    /// for threshold policies, but it is maintained with relaxed orderings, so it may lag behind
+    /// the concurrent acquires and releases.
+    pub fn active_slots(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Returns an iterator over `(index, active, hazard)` of all the slots in the set, where
+    /// `index` is the stable index of the slot as `Shield::slot_index`. The slots allocated most
+    /// recently come first.
+    ///
+    /// This is intended for diagnostics: the items are not a consistent snapshot of the bag.
+    pub fn slots(&self) -> Slots<'_> {
+        Slots {
+            chunk_ptr: self.head.load(Ordering::Acquire),
+            offset: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over the slots in the bag with their stable indices, as `slots`.
+    #[cfg(any(feature = "owner-info", feature = "watchdog"))]
+    fn indexed_slots(&self) -> impl Iterator<Item = (usize, &HazardSlot)> {
+        self.chunks().flat_map(|chunk| {
+            let base = chunk.base;
+            chunk
+                .slots
+                .iter()
+                .enumerate()
+                .map(move |(offset, slot)| (base + offset, slot))
+        })
+    }
+}
+
+/// Iterator over the slots of a `HazardBag`. See `HazardBag::slots`.
+#[derive(Debug)]
+pub struct Slots<'s> {
+    chunk_ptr: *const SlotChunk,
+    // The offset of the next slot in its chunk.
+    offset: usize,
+    _marker: PhantomData<&'s HazardBag>,
+}
+
+impl Iterator for Slots<'_> {
+    type Item = (usize, bool, *mut ());
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // # Safety
+        // chunks are never freed while the bag is borrowed.
+        let chunk = unsafe { self.chunk_ptr.as_ref() }?;
+        let offset = self.offset;
+        let item = (
+            chunk.base + offset,
+            chunk.active.load(Ordering::Acquire) & (1 << offset) != 0,
+            chunk.slots[offset].hazard.load(Ordering::Relaxed),
+        );
+        self.offset += 1;
+        if self.offset == SLOTS_PER_CHUNK {
+            self.offset = 0;
+            self.chunk_ptr = chunk.next;
+        }
+        Some(item)
+    }
+}
+
+impl fmt::Debug for HazardBag {
+    /// Lists all the chunks of slots in the bag.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.chunks()).finish()
     }
 }
 
@@ -207,36 +1294,64 @@ impl Default for HazardBag {
 }
 
 impl Drop for HazardBag {
-    /// Frees all slots.
+    /// Frees all chunks.
     fn drop(&mut self) {
         // # Safety
         // only one thread can own the `mut self`.
         unsafe {
-            let mut slot_ptr = self.head.load(Ordering::Relaxed);
-            while !slot_ptr.is_null() {
-                let slot = Box::from_raw(slot_ptr);
-                slot_ptr = slot.next as *mut HazardSlot;
+            let mut chunk_ptr = self.head.load(Ordering::Relaxed);
+            while !chunk_ptr.is_null() {
+                let next = (*chunk_ptr).next.cast_mut();
+                ptr::drop_in_place(chunk_ptr);
+                (self.allocator.dealloc)(chunk_ptr.cast(), SlotChunk::LAYOUT);
+                chunk_ptr = next;
             }
         }
     }
 }
 
-unsafe impl Send for HazardSlot {}
-unsafe impl Sync for HazardSlot {}
+unsafe impl Send for SlotChunk {}
+unsafe impl Sync for SlotChunk {}
 
 #[cfg(all(test, not(feature = "check-loom")))]
 mod tests {
     use std::collections::HashSet;
     use std::ops::Range;
-    use std::sync::Arc;
-    use std::sync::atomic::AtomicPtr;
-    use std::{mem, thread};
+    use std::sync::atomic::{AtomicPtr, Ordering};
+    use std::sync::{Arc, Barrier};
+    use std::time::Duration;
+    use std::{mem, ptr, thread};
 
-    use super::{HazardBag, Shield};
+    use super::{HazardBag, Protected, Shield, SlotHandle};
+    #[cfg(feature = "global")]
+    use crate::HAZARDS;
 
     const THREADS: usize = 8;
     const VALUES: Range<usize> = 1..1024;
 
+    // a bounded protect gives up with the number of retries when the source keeps changing.
+    #[test]
+    fn try_protect_for() {
+        use crate::hooks::{self, HookPoint};
+
+        static SRC: AtomicPtr<()> = AtomicPtr::new(0x10 as *mut ());
+        let _ = hooks::set_hook(|point| {
+            if point == HookPoint::Published {
+                let _ = SRC.fetch_xor(0x30, Ordering::Relaxed);
+            }
+        });
+        let hazard_bag = HazardBag::new();
+        let shield = Shield::new(&hazard_bag);
+        let err = shield.try_protect_for(&SRC, 2).unwrap_err();
+        assert_eq!(err.retries(), Some(2));
+        assert!(hazard_bag.all_hazards().is_empty());
+        assert!(hooks::take_hook().is_some());
+        assert_eq!(
+            shield.try_protect_for(&SRC, 0),
+            Ok(SRC.load(Ordering::Relaxed))
+        );
+    }
+
     // `all_hazards` should return hazards protected by shield(s).
     #[test]
     fn all_hazards_protected() {
@@ -287,6 +1402,7 @@ mod tests {
     }
 
     // `acquire_slot` should recycle existing slots.
+    #[cfg(not(feature = "fault-injection"))] // slots are not always reused
     #[test]
     fn recycle_slots() {
         let hazard_bag = HazardBag::new();
@@ -297,7 +1413,7 @@ mod tests {
         // slot addresses
         let old_slots = shields
             .iter()
-            .map(|s| s.slot.as_ptr() as usize)
+            .map(|s| s.slot() as *const _ as usize)
             .collect::<HashSet<_>>();
         // release the slots
         drop(shields);
@@ -307,10 +1423,481 @@ mod tests {
             .collect::<Vec<_>>();
         let new_slots = shields
             .iter()
-            .map(|s| s.slot.as_ptr() as usize)
+            .map(|s| s.slot() as *const _ as usize)
             .collect::<HashSet<_>>();
 
         // no new slots should've been created
         assert!(new_slots.is_subset(&old_slots));
     }
+
+    // chunks are filled before a new one is allocated, and released slots are found in the
+    // bitmap.
+    #[cfg(not(feature = "fault-injection"))] // slots are not always reused
+    #[test]
+    fn chunk_bitmap() {
+        let hazard_bag = HazardBag::new();
+        let mut shields = (0..super::SLOTS_PER_CHUNK)
+            .map(|_| Shield::new(&hazard_bag))
+            .collect::<Vec<_>>();
+        assert_eq!(hazard_bag.chunks().count(), 1);
+
+        let released = shields.swap_remove(1);
+        let index = released.index;
+        drop(released);
+        shields.push(Shield::new(&hazard_bag));
+        assert_eq!(shields.last().unwrap().index, index);
+        assert_eq!(hazard_bag.chunks().count(), 1);
+
+        shields.push(Shield::new(&hazard_bag));
+        assert_eq!(hazard_bag.chunks().count(), 2);
+    }
+
+    // using a bitwise copy of a dropped shield should be detected.
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "stale shield")]
+    fn stale_shield() {
+        use std::mem::ManuallyDrop;
+        use std::ptr;
+
+        let hazard_bag = HazardBag::new();
+        let shield = Shield::new(&hazard_bag);
+        // the copy must not be dropped as it releases the slot again.
+        let stale = ManuallyDrop::new(unsafe { ptr::read(&shield) });
+        drop(shield);
+        // another shield recycles the slot
+        let _shield = Shield::new(&hazard_bag);
+        stale.clear();
+    }
+
+    // a released shield of the default domain is reused by the next shield of the same thread,
+    // while its slot stays unprotected.
+    #[cfg(feature = "global")]
+    #[test]
+    fn cached_shield() {
+        thread::spawn(|| {
+            let slot = Shield::default().slot_index();
+            let shield = Shield::default();
+            assert_eq!(shield.slot_index(), slot);
+            let hazard = shield.slot().hazard.load(Ordering::Relaxed);
+            assert_eq!(hazard, ptr::null_mut());
+        })
+        .join()
+        .unwrap();
+    }
+
+    // `load_protected` protects the loaded pointer until dropped.
+    #[cfg(feature = "global")]
+    #[test]
+    fn load_protected() {
+        let pointer = Box::into_raw(Box::new(7));
+        let src = AtomicPtr::new(pointer);
+        let protected = crate::load_protected(&src);
+        assert_eq!(protected.as_ptr(), pointer);
+        assert_eq!(unsafe { protected.as_ref() }, Some(&7));
+        assert!(HAZARDS.hazards().all_hazards().contains(&pointer.cast()));
+
+        let shield = protected.into_shield();
+        shield.clear();
+        assert!(!HAZARDS.hazards().all_hazards().contains(&pointer.cast()));
+        drop(unsafe { Box::from_raw(pointer) });
+    }
+
+    // a protected pointer stays protected while it is sent to another thread.
+    #[test]
+    fn send_protected() {
+        let hazard_bag = HazardBag::new();
+        let pointer = Box::into_raw(Box::new(7));
+        let protected = Protected::new(Shield::new(&hazard_bag), &AtomicPtr::new(pointer));
+        thread::scope(|s| {
+            let hazard_bag = &hazard_bag;
+            let _ = s.spawn(move || {
+                assert!(
+                    hazard_bag
+                        .all_hazards()
+                        .contains(&protected.as_ptr().cast())
+                );
+                assert_eq!(unsafe { protected.as_ref() }, Some(&7));
+                drop(protected);
+                assert!(hazard_bag.all_hazards().is_empty());
+            });
+        });
+        drop(unsafe { Box::from_raw(pointer) });
+    }
+
+    // a protection handed over to the shield of another thread is never cleared in between.
+    #[test]
+    fn reattach() {
+        let hazard_bag = HazardBag::new();
+        let pointer = Box::into_raw(Box::new(7));
+        let protected = Protected::new(Shield::new(&hazard_bag), &AtomicPtr::new(pointer));
+        thread::scope(|s| {
+            let hazard_bag = &hazard_bag;
+            let _ = s.spawn(move || {
+                let hazards = || {
+                    hazard_bag
+                        .slots()
+                        .filter(|&(_, _, hazard)| !hazard.is_null())
+                };
+                let protected = protected.reattach(Shield::new(hazard_bag));
+                assert_eq!(hazards().count(), 1);
+                assert_eq!(unsafe { protected.as_ref() }, Some(&7));
+            });
+        });
+        assert!(hazard_bag.all_hazards().is_empty());
+        drop(unsafe { Box::from_raw(pointer) });
+    }
+
+    // a collection run in the middle of a hand over still finds the pointer protected.
+    #[test]
+    fn reattach_collect() {
+        use crate::Domain;
+        use crate::hooks::{self, HookPoint};
+
+        let domain: &'static Domain = Box::leak(Box::new(Domain::new()));
+        let pointer = Box::into_raw(Box::new(7));
+        let src = AtomicPtr::new(pointer);
+        let protected = Protected::new(Shield::new(domain.hazards()), &src);
+        src.store(ptr::null_mut(), Ordering::Relaxed);
+        unsafe { domain.retire(pointer) };
+        let _ = hooks::set_hook(move |point| {
+            if point == HookPoint::HandedOver {
+                domain.collect();
+            }
+        });
+        let protected = protected.reattach(Shield::new(domain.hazards()));
+        drop(hooks::take_hook());
+        assert_eq!(domain.pending_objects(), 1);
+        assert_eq!(unsafe { protected.as_ref() }, Some(&7));
+        drop(protected);
+        domain.collect();
+        assert_eq!(domain.pending_objects(), 0);
+    }
+
+    // `protect_global` protects one pointer at a time with the slot of the current thread.
+    #[cfg(feature = "global")]
+    #[test]
+    fn protect_global() {
+        let (first, second) = (Box::into_raw(Box::new(1)), Box::into_raw(Box::new(2)));
+        let src = AtomicPtr::new(first);
+        assert_eq!(crate::protect_global(&src), first);
+        assert!(HAZARDS.hazards().all_hazards().contains(&first.cast()));
+
+        src.store(second, Ordering::Relaxed);
+        assert_eq!(crate::protect_global(&src), second);
+        let hazards = HAZARDS.hazards().all_hazards();
+        assert!(!hazards.contains(&first.cast()) && hazards.contains(&second.cast()));
+
+        crate::clear_global();
+        assert!(!HAZARDS.hazards().all_hazards().contains(&second.cast()));
+        drop(unsafe { (Box::from_raw(first), Box::from_raw(second)) });
+    }
+
+    // a shield turned into a token keeps its protection, also on another thread, until it is
+    // reconstituted and dropped.
+    #[test]
+    fn shield_raw() {
+        let hazard_bag = HazardBag::new();
+        let pointer = Box::into_raw(Box::new(1));
+        let shield = Shield::new(&hazard_bag);
+        let _ = shield.protect(&AtomicPtr::new(pointer));
+        let raw = shield.into_raw() as usize;
+        assert!(hazard_bag.all_hazards().contains(&pointer.cast()));
+        thread::scope(|s| {
+            let _ = s.spawn(|| drop(unsafe { Shield::from_raw(&hazard_bag, raw as *mut ()) }));
+        });
+        assert!(!hazard_bag.all_hazards().contains(&pointer.cast()));
+        drop(unsafe { Box::from_raw(pointer) });
+    }
+
+    // a slot handle publishes and clears its hazard explicitly, and is deactivated once released.
+    #[test]
+    fn slot_handle() {
+        let hazard_bag = HazardBag::new();
+        let pointer = Box::into_raw(Box::new(1));
+        let handle = SlotHandle::acquire(&hazard_bag);
+        handle.publish(pointer, Ordering::SeqCst);
+        assert!(hazard_bag.all_hazards().contains(&pointer.cast()));
+        handle.clear(Ordering::SeqCst);
+        assert!(hazard_bag.all_hazards().is_empty());
+        handle.publish(pointer, Ordering::SeqCst);
+        unsafe { handle.release() };
+        assert!(
+            hazard_bag
+                .slots()
+                .all(|(_, active, hazard)| !active && hazard.is_null())
+        );
+        drop(unsafe { Box::from_raw(pointer) });
+    }
+
+    // slot indices stay the same when new chunks are allocated.
+    #[cfg(not(feature = "fault-injection"))]
+    #[test]
+    fn slot_index() {
+        let hazard_bag = HazardBag::new();
+        let first = Shield::new(&hazard_bag);
+        let others = (1..super::SLOTS_PER_CHUNK + 2)
+            .map(|_| Shield::new(&hazard_bag))
+            .collect::<Vec<_>>();
+        assert_eq!(first.slot_index(), 0);
+        for (index, shield) in others.iter().enumerate() {
+            assert_eq!(shield.slot_index(), index + 1);
+        }
+        // the indices of `slots` and `owners` agree with `slot_index` across chunks.
+        let pointer = 0x10 as *mut ();
+        let last = others.last().unwrap();
+        let _ = last.set(pointer);
+        let published = hazard_bag
+            .slots()
+            .filter(|&(_, _, hazard)| hazard == pointer)
+            .map(|(index, _, _)| index)
+            .collect::<Vec<_>>();
+        assert_eq!(published, [last.slot_index()]);
+        let mut indices = hazard_bag
+            .slots()
+            .map(|(index, _, _)| index)
+            .collect::<Vec<_>>();
+        indices.sort_unstable();
+        assert_eq!(indices, (0..2 * super::SLOTS_PER_CHUNK).collect::<Vec<_>>());
+        #[cfg(feature = "owner-info")]
+        {
+            let mut owned = hazard_bag
+                .owners()
+                .into_iter()
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>();
+            owned.sort_unstable();
+            assert_eq!(owned, (0..super::SLOTS_PER_CHUNK + 2).collect::<Vec<_>>());
+        }
+        last.clear();
+        drop(others);
+        assert_eq!(
+            Shield::new(&hazard_bag).slot_index(),
+            super::SLOTS_PER_CHUNK
+        );
+    }
+
+    // `new_global` and `try_new` acquire slots of the default domain and of other bags.
+    #[cfg(feature = "global")]
+    #[test]
+    fn new_global() {
+        let pointer = Box::into_raw(Box::new(1));
+        let shield = Shield::new_global();
+        let _ = shield.set(pointer);
+        assert!(HAZARDS.hazards().all_hazards().contains(&pointer.cast()));
+        drop(shield);
+
+        let hazard_bag = HazardBag::new();
+        let shield = Shield::try_new(&hazard_bag).unwrap();
+        let _ = shield.set(pointer);
+        assert!(hazard_bag.all_hazards().contains(&pointer.cast()));
+        drop(shield);
+        drop(unsafe { Box::from_raw(pointer) });
+    }
+
+    // the chunks of a bag are allocated and freed with its allocator.
+    #[cfg(not(feature = "fault-injection"))]
+    #[test]
+    fn slot_allocator() {
+        use std::alloc::{self, Layout};
+        use std::sync::atomic::AtomicUsize;
+
+        use super::SlotAllocator;
+
+        static CHUNKS: AtomicUsize = AtomicUsize::new(0);
+        unsafe fn alloc(layout: Layout) -> *mut u8 {
+            let _ = CHUNKS.fetch_add(1, Ordering::Relaxed);
+            unsafe { alloc::alloc(layout) }
+        }
+        unsafe fn dealloc(pointer: *mut u8, layout: Layout) {
+            let _ = CHUNKS.fetch_sub(1, Ordering::Relaxed);
+            unsafe { alloc::dealloc(pointer, layout) }
+        }
+        let hazard_bag = HazardBag::with_allocator(SlotAllocator { alloc, dealloc });
+        let shields = (0..super::SLOTS_PER_CHUNK + 1)
+            .map(|_| Shield::new(&hazard_bag))
+            .collect::<Vec<_>>();
+        assert_eq!(CHUNKS.load(Ordering::Relaxed), 2);
+        drop(shields);
+        drop(hazard_bag);
+        assert_eq!(CHUNKS.load(Ordering::Relaxed), 0);
+    }
+
+    // `active_slots` counts the slots owned by shields and slot handles.
+    #[test]
+    fn active_slots() {
+        let hazard_bag = HazardBag::new();
+        let shields = (0..3).map(|_| Shield::new(&hazard_bag)).collect::<Vec<_>>();
+        let handle = SlotHandle::acquire(&hazard_bag);
+        assert_eq!(hazard_bag.active_slots(), 4);
+        drop(shields);
+        unsafe { handle.release() };
+        assert_eq!(hazard_bag.active_slots(), 0);
+    }
+
+    // `protect_ref` borrows the protected object, and returns `None` for null.
+    #[test]
+    fn protect_ref() {
+        let hazard_bag = HazardBag::new();
+        let shield = Shield::new(&hazard_bag);
+        let pointer = Box::into_raw(Box::new(5));
+        let src = AtomicPtr::new(pointer);
+        assert_eq!(unsafe { shield.protect_ref(&src) }, Some(&5));
+        assert!(hazard_bag.all_hazards().contains(&pointer.cast()));
+        src.store(ptr::null_mut(), Ordering::Relaxed);
+        assert_eq!(unsafe { shield.protect_ref(&src) }, None);
+        drop(shield);
+        drop(unsafe { Box::from_raw(pointer) });
+    }
+
+    // `validate_all` publishes every pointer, and reports and clears the ones that changed.
+    #[test]
+    fn validate_all() {
+        let hazard_bag = HazardBag::new();
+        let shields = [(); 3].map(|_| Shield::new(&hazard_bag));
+        let pointers = [1, 2, 3].map(|i| Box::into_raw(Box::new(i)));
+        let srcs = pointers.map(AtomicPtr::new);
+        srcs[1].store(pointers[0], Ordering::Relaxed);
+        let entries = [0, 1, 2].map(|i| (&shields[i], &srcs[i], pointers[i]));
+        assert_eq!(Shield::validate_all(&entries), Err(vec![1]));
+        let hazards = hazard_bag.all_hazards();
+        assert!(hazards.contains(&pointers[2].cast()) && !hazards.contains(&pointers[1].cast()));
+        drop(shields);
+        drop(pointers.map(|pointer| unsafe { Box::from_raw(pointer) }));
+    }
+
+    // only a validated pointer is upgraded, and a failed validation clears the shield.
+    #[test]
+    fn validate_token() {
+        let hazard_bag = HazardBag::new();
+        let shield = Shield::new(&hazard_bag);
+        let (first, second) = (Box::into_raw(Box::new(1)), Box::into_raw(Box::new(2)));
+        let src = AtomicPtr::new(first);
+        let validated = shield.set(first).validate(&src).unwrap();
+        assert_eq!(unsafe { validated.as_ref() }, Some(&1));
+
+        src.store(second, Ordering::Relaxed);
+        let err = shield.set(first).validate(&src).unwrap_err();
+        assert_eq!(err.observed(), second);
+        assert!(hazard_bag.all_hazards().is_empty());
+        drop(shield);
+        drop(unsafe { (Box::from_raw(first), Box::from_raw(second)) });
+    }
+
+    // `wait_until_unprotected` returns once the shield of another thread is dropped, and times out
+    // while the pointer is protected.
+    #[test]
+    fn wait_until_unprotected() {
+        let hazard_bag = HazardBag::new();
+        let src = AtomicPtr::new(Box::into_raw(Box::new(0)));
+        let pointer = src.load(Ordering::Relaxed);
+        let barrier = Barrier::new(2);
+        thread::scope(|s| {
+            let _ = s.spawn(|| {
+                let shield = Shield::new(&hazard_bag);
+                let _ = shield.protect(&src);
+                let _ = barrier.wait();
+                let _ = barrier.wait();
+            });
+            let _ = barrier.wait();
+            assert!(!hazard_bag.wait_until_unprotected(pointer, Duration::from_millis(10)));
+            let _ = barrier.wait();
+            assert!(hazard_bag.wait_until_unprotected(pointer, Duration::from_secs(10)));
+        });
+        drop(unsafe { Box::from_raw(pointer) });
+    }
+
+    // `slots` should report every allocated slot, and none of them is active after all shields
+    // are dropped.
+    #[cfg(not(feature = "fault-injection"))] // slots are not always reused
+    #[test]
+    fn slots_inactive_after_drop() {
+        let hazard_bag = HazardBag::new();
+        let shields = (0..16)
+            .map(|_| Shield::new(&hazard_bag))
+            .collect::<Vec<_>>();
+        let _ = shields[3].set(3 as *mut ());
+        assert_eq!(hazard_bag.slots().count(), super::SLOTS_PER_CHUNK);
+        assert_eq!(
+            hazard_bag.slots().filter(|(_, active, _)| *active).count(),
+            16
+        );
+        assert!(
+            hazard_bag
+                .slots()
+                .any(|(_, _, hazard)| hazard == 3 as *mut ())
+        );
+        assert!(
+            hazard_bag
+                .slots()
+                .enumerate()
+                .all(|(i, (index, _, _))| i == index)
+        );
+
+        drop(shields);
+        assert!(
+            hazard_bag
+                .slots()
+                .all(|(_, active, hazard)| !active && hazard.is_null())
+        );
+    }
+
+    // a protection held for too long is reported once, until the shield protects another pointer.
+    #[cfg(feature = "watchdog")]
+    #[test]
+    fn hold_report() {
+        let hazard_bag = HazardBag::new();
+        let shield = Shield::new(&hazard_bag);
+        let (mut first, mut second) = (1, 2);
+        let _ = shield.set(&mut first);
+        assert_eq!(hazard_bag.hold_report(Duration::from_secs(60)), None);
+        let report = hazard_bag.hold_report(Duration::ZERO).unwrap();
+        assert!(report.contains(&format!("{:p}", &first)), "{report}");
+        assert_eq!(hazard_bag.hold_report(Duration::ZERO), None);
+        let _ = shield.set(&mut second);
+        assert!(hazard_bag.hold_report(Duration::ZERO).is_some());
+        shield.clear();
+        assert_eq!(hazard_bag.hold_report(Duration::ZERO), None);
+    }
+
+    // the owner of a slot is recorded while it is active.
+    #[cfg(feature = "owner-info")]
+    #[test]
+    fn slot_owner() {
+        let hazard_bag = HazardBag::new();
+        thread::scope(|s| {
+            thread::Builder::new()
+                .name("owner".into())
+                .spawn_scoped(s, || {
+                    let _shield = Shield::new(&hazard_bag);
+                    let owners = hazard_bag.owners();
+                    assert_eq!(owners.len(), 1);
+                    assert_eq!(owners[0].1.id, thread::current().id());
+                    assert_eq!(owners[0].1.name.as_deref(), Some("owner"));
+                    assert!(format!("{hazard_bag:?}").contains("\"owner\""));
+                })
+                .unwrap()
+                .join()
+                .unwrap()
+        });
+        assert!(hazard_bag.owners().is_empty());
+    }
+
+    // in debug builds, dereferencing a validated pointer after publishing another one panics.
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "without validating it")]
+    fn deref_after_set() {
+        let hazard_bag = HazardBag::new();
+        let shield = Shield::new(&hazard_bag);
+        let (mut first, mut second) = (1, 2);
+        let src = AtomicPtr::new(&mut first as *mut i32);
+        let validated = shield
+            .set(src.load(Ordering::Relaxed))
+            .validate(&src)
+            .unwrap();
+        assert_eq!(unsafe { validated.as_ref() }, Some(&1));
+        let _ = shield.set(&mut second as *mut i32);
+        let _ = unsafe { validated.as_ref() };
+    }
 }
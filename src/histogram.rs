@@ -0,0 +1,177 @@
+//! Histograms of reclamation latencies, from `retire` to the actual free.
+
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::AtomicU64;
+use std::time::Duration;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicU64;
+
+use super::ordering::Ordering;
+
+/// The number of bits of each value kept exactly, after its most significant bit.
+const SUB_BITS: u32 = 4;
+/// The number of buckets between consecutive powers of two.
+const SUB_BUCKETS: usize = 1 << SUB_BITS;
+/// The number of buckets covering all `u64` nanoseconds.
+const BUCKETS: usize = (64 - SUB_BITS as usize + 1) * SUB_BUCKETS;
+
+/// Returns the bucket of `value`. Buckets are exact below `SUB_BUCKETS`, and above, each power of
+/// two is split into `SUB_BUCKETS` buckets, as in HDR histograms.
+fn bucket(value: u64) -> usize {
+    if value < SUB_BUCKETS as u64 {
+        return value as usize;
+    }
+    let exponent = 63 - value.leading_zeros();
+    let mantissa = (value >> (exponent - SUB_BITS)) as usize & (SUB_BUCKETS - 1);
+    (exponent - SUB_BITS + 1) as usize * SUB_BUCKETS + mantissa
+}
+
+/// Returns the largest value in `bucket`.
+fn highest(bucket: usize) -> u64 {
+    if bucket < SUB_BUCKETS {
+        return bucket as u64;
+    }
+    let shift = (bucket / SUB_BUCKETS - 1) as u32;
+    let lowest = ((SUB_BUCKETS + bucket % SUB_BUCKETS) as u64) << shift;
+    lowest + ((1 << shift) - 1)
+}
+
+/// Latencies recorded by a domain.
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    counts: [AtomicU64; BUCKETS],
+    // In nanoseconds.
+    sum: AtomicU64,
+    max: AtomicU64,
+}
+
+impl Recorder {
+    #[cfg(not(feature = "check-loom"))]
+    pub(crate) const fn new() -> Self {
+        Self {
+            counts: [const { AtomicU64::new(0) }; BUCKETS],
+            sum: AtomicU64::new(0),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    #[cfg(feature = "check-loom")]
+    pub(crate) fn new() -> Self {
+        Self {
+            counts: core::array::from_fn(|_| AtomicU64::new(0)),
+            sum: AtomicU64::new(0),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn record(&self, latency: Duration) {
+        let nanos = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+        let _ = self.counts[bucket(nanos)].fetch_add(1, Ordering::Relaxed);
+        let _ = self.sum.fetch_add(nanos, Ordering::Relaxed);
+        let _ = self.max.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> LatencyHistogram {
+        LatencyHistogram {
+            counts: self
+                .counts
+                .iter()
+                .map(|count| count.load(Ordering::Relaxed))
+                .collect(),
+            sum: self.sum.load(Ordering::Relaxed),
+            max: self.max.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A snapshot of the latencies from `retire` to the free of the objects reclaimed in a domain.
+/// See `Domain::reclaim_latencies`.
+///
+/// Each latency is kept with a relative error below 1/16, so that the tail is visible without
+/// storing every sample.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    counts: Box<[u64]>,
+    sum: u64,
+    max: u64,
+}
+
+impl LatencyHistogram {
+    /// Returns the number of latencies recorded.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Returns `true` if no latency is recorded.
+    pub fn is_empty(&self) -> bool {
+        self.count() == 0
+    }
+
+    /// Returns the longest latency recorded exactly.
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max)
+    }
+
+    /// Returns the sum of the latencies recorded.
+    pub fn sum(&self) -> Duration {
+        Duration::from_nanos(self.sum)
+    }
+
+    /// Returns the mean latency, or zero if none is recorded.
+    pub fn mean(&self) -> Duration {
+        Duration::from_nanos(self.sum.checked_div(self.count()).unwrap_or(0))
+    }
+
+    /// Returns the latency below which the fraction `quantile` of the latencies are, e.g. 0.99 for
+    /// the 99th percentile, or zero if none is recorded. Panics unless `quantile` is in `0..=1`.
+    pub fn quantile(&self, quantile: f64) -> Duration {
+        assert!(
+            (0.0..=1.0).contains(&quantile),
+            "`quantile` must be in 0..=1"
+        );
+        let rank = (quantile * self.count() as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_nanos(highest(bucket).min(self.max));
+            }
+        }
+        Duration::ZERO
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::time::Duration;
+
+    use super::{BUCKETS, Recorder, bucket, highest};
+
+    // each value is in a bucket whose highest value is within 1/16 above it.
+    #[test]
+    fn buckets() {
+        for value in (0..1 << 12).chain([u64::MAX / 3, u64::MAX]) {
+            let index = bucket(value);
+            assert!(index < BUCKETS);
+            assert!(highest(index) >= value && highest(index) - value <= value / 16);
+            assert!(index == 0 || highest(index - 1) < value);
+        }
+    }
+
+    // quantiles come from the buckets, capped by the exact maximum.
+    #[test]
+    fn quantiles() {
+        let recorder = Recorder::new();
+        for micros in 1..=100 {
+            recorder.record(Duration::from_micros(micros));
+        }
+        let histogram = recorder.snapshot();
+        assert_eq!(histogram.count(), 100);
+        assert_eq!(histogram.max(), Duration::from_micros(100));
+        assert_eq!(histogram.quantile(1.0), Duration::from_micros(100));
+        let median = histogram.quantile(0.5);
+        assert!(median >= Duration::from_micros(50) && median <= Duration::from_micros(54));
+        assert_eq!(histogram.mean(), Duration::from_nanos(50_500));
+    }
+}
@@ -0,0 +1,110 @@
+//! Injection points in the protocol, so that tests can force interleavings deterministically
+//! without loom.
+//!
+//! A hook is set per thread, and is called with each [`HookPoint`] the thread passes. For example,
+//! a hook blocking at [`HookPoint::Loaded`] until another thread retires the loaded pointer forces
+//! the validation to fail.
+
+use core::cell::Cell;
+#[cfg(not(feature = "check-loom"))]
+use std::thread_local;
+
+#[cfg(feature = "check-loom")]
+use loom::thread_local;
+
+/// Points in the protocol where the hook of the current thread is called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HookPoint {
+    /// `Shield::protect` loaded the pointer from the source, and is about to publish it.
+    Loaded,
+    /// `Shield::try_protect` published the hazard, and is about to revalidate the source.
+    Published,
+    /// A pointer is retired, and the retired pointers are about to be collected if needed.
+    Retired,
+    /// A collection scanned the hazards, and is about to find the unprotected pointers.
+    Scanned,
+    /// `Shield::hand_over` swapped the slots of the shields, and is about to clear the slot left
+    /// to the first one.
+    HandedOver,
+}
+
+/// A hook called at each `HookPoint`.
+pub type Hook = Box<dyn FnMut(HookPoint)>;
+
+thread_local! {
+    static HOOK: Cell<Option<Hook>> = const { Cell::new(None) };
+}
+
+/// Sets the hook of the current thread, returning the previous one.
+pub fn set_hook(hook: impl FnMut(HookPoint) + 'static) -> Option<Hook> {
+    HOOK.with(|h| h.replace(Some(Box::new(hook))))
+}
+
+/// Removes the hook of the current thread and returns it.
+pub fn take_hook() -> Option<Hook> {
+    HOOK.with(Cell::take)
+}
+
+/// Calls the hook of the current thread, if any. The hook is not called recursively.
+pub(crate) fn run(point: HookPoint) {
+    let _ = HOOK.try_with(|h| {
+        if let Some(mut hook) = h.take() {
+            hook(point);
+            // keep the hook set by `hook` itself, if any.
+            let new = h.take();
+            h.set(new.or(Some(hook)));
+        }
+    });
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::sync::atomic::{AtomicPtr, Ordering};
+    use std::sync::mpsc::channel;
+    use std::sync::{Arc, Mutex};
+    use std::thread::scope;
+
+    use super::{HookPoint, set_hook, take_hook};
+    use crate::{Domain, Shield};
+
+    // a pointer swapped and freed between loading and publishing fails the validation, and the
+    // protect retries with the new pointer.
+    #[test]
+    fn retire_before_publish() {
+        let domain = Domain::builder().threshold(usize::MAX).build();
+        let old = Box::into_raw(Box::new(1));
+        let new = Box::into_raw(Box::new(2));
+        let src = AtomicPtr::new(old);
+        let (loaded, wait_loaded) = channel();
+        let (retired, wait_retired) = channel();
+        let points = Arc::new(Mutex::new(Vec::new()));
+        let expected = new as usize;
+        scope(|s| {
+            let (points, domain, src) = (points.clone(), &domain, &src);
+            let _ = s.spawn(move || {
+                let _ = set_hook(move |point| {
+                    points.lock().unwrap().push(point);
+                    if point == HookPoint::Loaded && points.lock().unwrap().len() == 1 {
+                        loaded.send(()).unwrap();
+                        wait_retired.recv().unwrap();
+                    }
+                });
+                let shield = Shield::new(domain.hazards());
+                assert_eq!(shield.protect(src) as usize, expected);
+                assert!(take_hook().is_some());
+            });
+            wait_loaded.recv().unwrap();
+            src.store(new, Ordering::Relaxed);
+            unsafe { domain.retire(old) };
+            domain.collect();
+            retired.send(()).unwrap();
+        });
+        use HookPoint::*;
+        assert_eq!(
+            *points.lock().unwrap(),
+            [Loaded, Published, Loaded, Published]
+        );
+        drop(unsafe { Box::from_raw(new) });
+    }
+}
@@ -0,0 +1,339 @@
+//! Hyaline-style reference-counted batch reclamation, an alternative to hazard pointers behind the
+//! same [`Reclaimer`] interface.
+//!
+//! Retired pointers are gathered into batches. A batch is handed to every slot occupied by a guard
+//! at that time, and counts the guards it is handed to. Each guard releases the batches handed to
+//! it when dropped, and the last one frees the batch. As with epochs, protecting a pointer is
+//! free, but the guards do not need to agree on a global epoch: a stalled guard only keeps the
+//! batches retired while it lives.
+//!
+//! ```
+//! use hazard::collections::Stack;
+//! use hazard::hyaline::Collector;
+//!
+//! let collector = Collector::new();
+//! let stack = Stack::with_reclaimer(&collector);
+//! stack.push(1);
+//! assert_eq!(stack.try_pop(), Some(1));
+//! ```
+
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, fence};
+#[cfg(not(feature = "check-loom"))]
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::Mutex;
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, fence};
+
+use super::backoff::Backoff;
+use super::ordering::Ordering;
+use super::retire::Retired;
+use super::{DomainConfig, Protect, Reclaimer};
+
+/// A reference-counted batch collector.
+///
+/// Of [`DomainConfig`], `threshold` sets the size of the batches.
+#[derive(Debug)]
+pub struct Collector {
+    /// Grow-only list of the slots, like `HazardBag`.
+    head: AtomicPtr<Slot>,
+    config: OnceLock<DomainConfig>,
+    /// Retired pointers that are not in a batch yet.
+    pending: Mutex<Pending>,
+}
+
+/// A slot occupied by a guard, which receives the batches retired while it is occupied.
+#[derive(Debug)]
+struct Slot {
+    // Whether this slot is occupied by a `Guard`.
+    active: AtomicBool,
+    // Null if no guard entered this slot. Otherwise, the list of batches handed to the guard,
+    // terminated by `Link::empty()`.
+    batches: AtomicPtr<Link>,
+    // Immutable pointer to the next slot.
+    next: *const Slot,
+}
+
+/// An entry of the list of batches of a slot.
+#[derive(Debug)]
+struct Link {
+    batch: *const Batch,
+    next: *mut Link,
+}
+
+/// Retired pointers freed together by the last guard releasing them.
+#[derive(Debug)]
+struct Batch {
+    refs: AtomicUsize,
+    retired: Vec<Retired>,
+}
+
+#[derive(Debug, Default)]
+struct Pending {
+    inner: Vec<Retired>,
+}
+
+// Retired pointers are freed by any thread, as required by `Reclaimer::retire`.
+unsafe impl Send for Pending {}
+unsafe impl Send for Slot {}
+unsafe impl Sync for Slot {}
+
+/// A guard protecting every object reachable while it lives.
+#[derive(Debug)]
+#[must_use = "the objects are unprotected as soon as the guard is dropped"]
+pub struct Guard<'c> {
+    slot: &'c Slot,
+    _marker: PhantomData<*const ()>, // !Send + !Sync
+}
+
+impl Link {
+    /// The end of the list of an entered slot.
+    fn empty() -> *mut Link {
+        ptr::dangling_mut()
+    }
+}
+
+impl Batch {
+    /// Releases a reference to the batch, or `refs` references if negative in two's complement.
+    /// Frees the batch if it was the last.
+    unsafe fn release(batch: *const Batch, refs: usize) {
+        let prev = unsafe { &*batch }.refs.fetch_add(refs, Ordering::AcqRel);
+        if prev.wrapping_add(refs) == 0 {
+            let batch = unsafe { Box::from_raw(batch.cast_mut()) };
+            for retired in batch.retired {
+                unsafe { retired.free() };
+            }
+        }
+    }
+}
+
+impl Collector {
+    #[cfg(not(feature = "check-loom"))]
+    /// Creates a new collector. Its configuration is fixed to the default on first use.
+    pub const fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+            config: OnceLock::new(),
+            pending: Mutex::new(Pending { inner: Vec::new() }),
+        }
+    }
+
+    #[cfg(feature = "check-loom")]
+    /// Creates a new collector. Its configuration is fixed to the default on first use.
+    pub fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+            config: OnceLock::new(),
+            pending: Mutex::new(Pending { inner: Vec::new() }),
+        }
+    }
+
+    /// Creates a new collector with the given configuration.
+    pub fn with_config(config: DomainConfig) -> Self {
+        let collector = Self::new();
+        let _ = collector.config.set(config);
+        collector
+    }
+
+    /// Returns the configuration of this collector.
+    pub fn config(&self) -> &DomainConfig {
+        self.config.get_or_init(DomainConfig::default)
+    }
+
+    /// Acquires an inactive slot or allocates a new one.
+    fn acquire_slot(&self) -> &Slot {
+        let mut slot_ptr = self.head.load(Ordering::Acquire);
+        while let Some(slot) = unsafe { slot_ptr.as_ref() } {
+            if !slot.active.load(Ordering::Relaxed)
+                && slot
+                    .active
+                    .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return slot;
+            }
+            slot_ptr = slot.next as *mut Slot;
+        }
+
+        let slot = Box::into_raw(Box::new(Slot {
+            active: AtomicBool::new(true),
+            batches: AtomicPtr::new(ptr::null_mut()),
+            next: ptr::null(),
+        }));
+        let mut backoff = Backoff::new();
+        loop {
+            let head = self.head.load(Ordering::Relaxed);
+            unsafe { (*slot).next = head };
+            if self
+                .head
+                .compare_exchange_weak(head, slot, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+            {
+                return unsafe { &*slot };
+            }
+            backoff.snooze();
+        }
+    }
+
+    /// Hands `retired` as a batch to the entered slots. If there is none, frees it right away.
+    fn hand_over(&self, retired: Vec<Retired>) {
+        let batch = Box::into_raw(Box::new(Batch {
+            refs: AtomicUsize::new(0),
+            retired,
+        }));
+        // The pointers are unlinked before any guard entering a slot after this fence.
+        fence(Ordering::SeqCst);
+        let mut handed = 0usize;
+        let mut slot_ptr = self.head.load(Ordering::Acquire);
+        while let Some(slot) = unsafe { slot_ptr.as_ref() } {
+            let link = Box::into_raw(Box::new(Link {
+                batch,
+                next: ptr::null_mut(),
+            }));
+            let mut batches = slot.batches.load(Ordering::Relaxed);
+            loop {
+                if batches.is_null() {
+                    drop(unsafe { Box::from_raw(link) });
+                    break;
+                }
+                unsafe { (*link).next = batches };
+                match slot.batches.compare_exchange_weak(
+                    batches,
+                    link,
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        handed += 1;
+                        break;
+                    }
+                    Err(current) => batches = current,
+                }
+            }
+            slot_ptr = slot.next as *mut Slot;
+        }
+        // The guards that already released the batch made the count negative.
+        unsafe { Batch::release(batch, handed) };
+    }
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Collector {
+    /// Frees all the retired pointers and the slots. No guard of this collector may exist at this
+    /// point, so all the batches are already freed.
+    fn drop(&mut self) {
+        let pending = self.pending.get_mut().unwrap_or_else(|e| e.into_inner());
+        for retired in pending.inner.drain(..) {
+            unsafe { retired.free() };
+        }
+        let mut slot_ptr = self.head.load(Ordering::Relaxed);
+        while !slot_ptr.is_null() {
+            let slot = unsafe { Box::from_raw(slot_ptr) };
+            slot_ptr = slot.next as *mut Slot;
+        }
+    }
+}
+
+impl Drop for Guard<'_> {
+    /// Releases the batches handed to this guard and the slot.
+    fn drop(&mut self) {
+        let mut link = self.slot.batches.swap(ptr::null_mut(), Ordering::AcqRel);
+        while link != Link::empty() {
+            let curr = unsafe { Box::from_raw(link) };
+            unsafe { Batch::release(curr.batch, usize::MAX) };
+            link = curr.next;
+        }
+        self.slot.active.store(false, Ordering::Release);
+    }
+}
+
+impl Protect for Guard<'_> {
+    /// Does nothing: all the objects are protected as long as the guard lives.
+    fn set<T>(&self, _: *mut T) {}
+
+    /// Does nothing: all the objects are protected as long as the guard lives.
+    fn clear(&self) {}
+}
+
+unsafe impl Reclaimer for Collector {
+    type Guard<'r> = Guard<'r>;
+
+    fn guard(&self) -> Guard<'_> {
+        let slot = self.acquire_slot();
+        slot.batches.store(Link::empty(), Ordering::Relaxed);
+        // Make the entry visible before any load of shared pointers.
+        fence(Ordering::SeqCst);
+        Guard {
+            slot,
+            _marker: PhantomData,
+        }
+    }
+
+    unsafe fn retire<T>(&self, pointer: *mut T) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.inner.push(Retired::new(pointer));
+        if pending.inner.len() >= self.config().threshold {
+            let retired = mem::take(&mut pending.inner);
+            drop(pending);
+            self.hand_over(retired);
+        }
+    }
+
+    /// Hands the pending retired pointers over as a batch, which is freed once the current guards
+    /// are dropped.
+    fn collect(&self) {
+        let retired = mem::take(&mut self.pending.lock().unwrap_or_else(|e| e.into_inner()).inner);
+        if !retired.is_empty() {
+            self.hand_over(retired);
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::Collector;
+    use crate::Reclaimer;
+    use crate::test::common::{Tester, exercise_collections};
+
+    // a batch is freed by the last guard that was alive when it was handed over.
+    #[test]
+    fn last_guard_frees() {
+        let collector = Collector::new();
+        let freed = Arc::new(AtomicUsize::new(0));
+        let first = collector.guard();
+        let second = collector.guard();
+        unsafe { collector.retire(Box::into_raw(Box::new(Tester(freed.clone())))) };
+        collector.collect();
+        let third = collector.guard();
+        drop(first);
+        drop(third);
+        assert_eq!(freed.load(Ordering::Relaxed), 0);
+
+        drop(second);
+        assert_eq!(freed.load(Ordering::Relaxed), 1);
+
+        unsafe { collector.retire(Box::into_raw(Box::new(Tester(freed.clone())))) };
+        collector.collect();
+        assert_eq!(freed.load(Ordering::Relaxed), 2);
+    }
+
+    // the collections work with Hyaline, and their retired items are freed.
+    #[test]
+    fn collections() {
+        exercise_collections(&Collector::new());
+    }
+}
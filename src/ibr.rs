@@ -0,0 +1,356 @@
+//! Interval-based reclamation (2GEIBR), an alternative to hazard pointers behind the same
+//! [`Reclaimer`] interface.
+//!
+//! Each object is tagged with its birth era when allocated and with its retire era when retired.
+//! A guard reserves an interval of eras, from the era at its creation up to the latest era it has
+//! observed when protecting a pointer. An object is freed once its lifetime interval does not
+//! intersect any reservation. Like hazard pointers, a stalled guard only keeps the objects alive
+//! during its interval, but protecting a pointer needs a fence only when the era has changed.
+//!
+//! ```
+//! use hazard::collections::Stack;
+//! use hazard::ibr::Collector;
+//!
+//! let collector = Collector::new();
+//! let stack = Stack::with_reclaimer(&collector);
+//! stack.push(1);
+//! assert_eq!(stack.try_pop(), Some(1));
+//! ```
+
+use core::marker::PhantomData;
+use core::mem::{self, offset_of};
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, fence};
+#[cfg(not(feature = "check-loom"))]
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::Mutex;
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, fence};
+
+use super::backoff::Backoff;
+use super::ordering::Ordering;
+use super::retire::Retired;
+use super::{DomainConfig, Protect, Reclaimer};
+
+/// An interval-based garbage collector.
+///
+/// Of [`DomainConfig`], `threshold` sets both the number of retired pointers that triggers
+/// `collect` and the number of allocations after which the global era advances.
+#[derive(Debug)]
+pub struct Collector {
+    /// The global era.
+    era: AtomicUsize,
+    /// The number of allocations since the era last advanced.
+    allocs: AtomicUsize,
+    /// Grow-only list of the reservations, like `HazardBag`.
+    head: AtomicPtr<Reservation>,
+    config: OnceLock<DomainConfig>,
+    garbage: Mutex<Garbage>,
+}
+
+/// An interval of eras reserved by a guard.
+#[derive(Debug)]
+struct Reservation {
+    // Whether this record is occupied by a `Guard`.
+    active: AtomicBool,
+    lower: AtomicUsize,
+    upper: AtomicUsize,
+    // Immutable pointer to the next record.
+    next: *const Reservation,
+}
+
+/// An object tagged with its birth era. Pointers handed out point to `value`.
+#[repr(C)]
+struct Tagged<T> {
+    birth: usize,
+    value: T,
+}
+
+/// Retired pointers tagged with their birth and retire eras.
+#[derive(Debug, Default)]
+struct Garbage {
+    inner: Vec<(usize, usize, Retired)>,
+    /// The length at which `collect` is triggered next. Pointers that are not expired yet stay
+    /// in the list, so this keeps `collect` from running on every `retire`.
+    trigger: usize,
+}
+
+// Retired pointers are freed by any thread collecting the collector, as required by
+// `Reclaimer::retire`.
+unsafe impl Send for Garbage {}
+unsafe impl Send for Reservation {}
+unsafe impl Sync for Reservation {}
+
+/// A guard reserving the eras of the pointers it protects.
+#[derive(Debug)]
+#[must_use = "the objects are unprotected as soon as the guard is dropped"]
+pub struct Guard<'c> {
+    collector: &'c Collector,
+    reservation: &'c Reservation,
+    _marker: PhantomData<*const ()>, // !Send + !Sync
+}
+
+impl Collector {
+    #[cfg(not(feature = "check-loom"))]
+    /// Creates a new collector. Its configuration is fixed to the default on first use.
+    pub const fn new() -> Self {
+        Self {
+            era: AtomicUsize::new(0),
+            allocs: AtomicUsize::new(0),
+            head: AtomicPtr::new(ptr::null_mut()),
+            config: OnceLock::new(),
+            garbage: Mutex::new(Garbage {
+                inner: Vec::new(),
+                trigger: 0,
+            }),
+        }
+    }
+
+    #[cfg(feature = "check-loom")]
+    /// Creates a new collector. Its configuration is fixed to the default on first use.
+    pub fn new() -> Self {
+        Self {
+            era: AtomicUsize::new(0),
+            allocs: AtomicUsize::new(0),
+            head: AtomicPtr::new(ptr::null_mut()),
+            config: OnceLock::new(),
+            garbage: Mutex::new(Garbage {
+                inner: Vec::new(),
+                trigger: 0,
+            }),
+        }
+    }
+
+    /// Creates a new collector with the given configuration.
+    pub fn with_config(config: DomainConfig) -> Self {
+        let collector = Self::new();
+        let _ = collector.config.set(config);
+        collector
+    }
+
+    /// Returns the configuration of this collector.
+    pub fn config(&self) -> &DomainConfig {
+        self.config.get_or_init(DomainConfig::default)
+    }
+
+    /// Returns the global era.
+    pub fn era(&self) -> usize {
+        self.era.load(Ordering::Relaxed)
+    }
+
+    /// Acquires an inactive reservation record or allocates a new one.
+    fn acquire_reservation(&self) -> &Reservation {
+        let mut record_ptr = self.head.load(Ordering::Acquire);
+        while let Some(record) = unsafe { record_ptr.as_ref() } {
+            if !record.active.load(Ordering::Relaxed)
+                && record
+                    .active
+                    .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return record;
+            }
+            record_ptr = record.next as *mut Reservation;
+        }
+
+        let record = Box::into_raw(Box::new(Reservation {
+            active: AtomicBool::new(true),
+            lower: AtomicUsize::new(usize::MAX),
+            upper: AtomicUsize::new(usize::MAX),
+            next: ptr::null(),
+        }));
+        let mut backoff = Backoff::new();
+        loop {
+            let head = self.head.load(Ordering::Relaxed);
+            unsafe { (*record).next = head };
+            if self
+                .head
+                .compare_exchange_weak(head, record, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+            {
+                return unsafe { &*record };
+            }
+            backoff.snooze();
+        }
+    }
+
+    /// Returns the intervals reserved by the active guards.
+    fn reservations(&self) -> Vec<(usize, usize)> {
+        let mut reservations = Vec::new();
+        let mut record_ptr = self.head.load(Ordering::Acquire);
+        while let Some(record) = unsafe { record_ptr.as_ref() } {
+            if record.active.load(Ordering::Relaxed) {
+                let lower = record.lower.load(Ordering::Relaxed);
+                let upper = record.upper.load(Ordering::Relaxed);
+                reservations.push((lower, upper));
+            }
+            record_ptr = record.next as *mut Reservation;
+        }
+        reservations
+    }
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Collector {
+    /// Frees all the retired pointers and the reservation records. No guard of this collector may
+    /// exist at this point.
+    fn drop(&mut self) {
+        let garbage = self.garbage.get_mut().unwrap_or_else(|e| e.into_inner());
+        for (_, _, retired) in garbage.inner.drain(..) {
+            unsafe { retired.free() };
+        }
+        let mut record_ptr = self.head.load(Ordering::Relaxed);
+        while !record_ptr.is_null() {
+            let record = unsafe { Box::from_raw(record_ptr) };
+            record_ptr = record.next as *mut Reservation;
+        }
+    }
+}
+
+impl Drop for Guard<'_> {
+    /// Releases the reservation.
+    fn drop(&mut self) {
+        self.reservation.lower.store(usize::MAX, Ordering::Relaxed);
+        self.reservation.upper.store(usize::MAX, Ordering::Relaxed);
+        self.reservation.active.store(false, Ordering::Release);
+    }
+}
+
+impl Protect for Guard<'_> {
+    /// Extends the reservation to the current era, which covers the birth era of `pointer` if it
+    /// is loaded before.
+    fn set<T>(&self, _: *mut T) {
+        let era = self.collector.era.load(Ordering::Acquire);
+        if self.reservation.upper.load(Ordering::Relaxed) != era {
+            self.reservation.upper.store(era, Ordering::Relaxed);
+            fence(Ordering::SeqCst);
+        }
+    }
+
+    /// Does nothing: the reservation only shrinks when the guard is dropped.
+    fn clear(&self) {}
+}
+
+unsafe impl Reclaimer for Collector {
+    type Guard<'r> = Guard<'r>;
+
+    fn guard(&self) -> Guard<'_> {
+        let reservation = self.acquire_reservation();
+        let era = self.era.load(Ordering::Acquire);
+        reservation.lower.store(era, Ordering::Relaxed);
+        reservation.upper.store(era, Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+        Guard {
+            collector: self,
+            reservation,
+            _marker: PhantomData,
+        }
+    }
+
+    fn alloc<T>(&self, value: T) -> *mut T {
+        let allocs = self.allocs.fetch_add(1, Ordering::Relaxed) + 1;
+        if allocs.is_multiple_of(self.config().threshold.max(1)) {
+            let _ = self.era.fetch_add(1, Ordering::Release);
+        }
+        let birth = self.era.load(Ordering::Acquire);
+        let tagged = Box::into_raw(Box::new(Tagged { birth, value }));
+        unsafe { &raw mut (*tagged).value }
+    }
+
+    unsafe fn dealloc<T>(&self, pointer: *mut T) {
+        drop(unsafe { Box::from_raw(tagged(pointer)) })
+    }
+
+    unsafe fn retire<T>(&self, pointer: *mut T) {
+        let tagged = tagged(pointer);
+        let birth = unsafe { (*tagged).birth };
+        fence(Ordering::SeqCst);
+        let era = self.era.load(Ordering::Relaxed);
+        let mut garbage = self.garbage.lock().unwrap_or_else(|e| e.into_inner());
+        garbage.inner.push((birth, era, Retired::new(tagged)));
+        if garbage.inner.len() >= garbage.trigger.max(self.config().threshold) {
+            drop(garbage);
+            self.collect();
+        }
+    }
+
+    fn collect(&self) {
+        fence(Ordering::SeqCst);
+        let reservations = self.reservations();
+        // Take the expired pointers out so that the destructors run without the lock, as they
+        // may retire other pointers.
+        let expired = {
+            let mut garbage = self.garbage.lock().unwrap_or_else(|e| e.into_inner());
+            let (expired, pending) =
+                mem::take(&mut garbage.inner)
+                    .into_iter()
+                    .partition(|(birth, retired, _)| {
+                        reservations
+                            .iter()
+                            .all(|(lower, upper)| birth > upper || retired < lower)
+                    });
+            garbage.inner = pending;
+            garbage.trigger = garbage.inner.len() + self.config().threshold;
+            expired
+        };
+        for (_, _, retired) in Vec::into_iter(expired) {
+            unsafe { retired.free() };
+        }
+    }
+}
+
+/// Returns the tagged object containing `pointer`, which is allocated by `Collector::alloc`.
+fn tagged<T>(pointer: *mut T) -> *mut Tagged<T> {
+    pointer
+        .wrapping_byte_sub(offset_of!(Tagged<T>, value))
+        .cast()
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+    use super::Collector;
+    use crate::test::common::{Tester, exercise_collections};
+    use crate::{DomainConfig, Protect, Reclaimer};
+
+    // a guard only keeps the objects whose lifetime intersects its reservation.
+    #[test]
+    fn reservation_interval() {
+        let collector = Collector::with_config(DomainConfig::builder().threshold(1).config());
+        let freed = Arc::new(AtomicUsize::new(0));
+        let old = collector.alloc(Tester(freed.clone()));
+        let src = AtomicPtr::new(old);
+        let guard = collector.guard();
+        assert_eq!(guard.protect(&src), old);
+
+        // born after the guard observed the era, so not reserved.
+        let young = collector.alloc(Tester(freed.clone()));
+        unsafe { collector.retire(young) };
+        assert_eq!(freed.load(Ordering::Relaxed), 1);
+
+        unsafe { collector.retire(old) };
+        collector.collect();
+        assert_eq!(freed.load(Ordering::Relaxed), 1);
+
+        drop(guard);
+        collector.collect();
+        assert_eq!(freed.load(Ordering::Relaxed), 2);
+    }
+
+    // the collections work with IBR, and their retired items are freed.
+    #[test]
+    fn collections() {
+        exercise_collections(&Collector::new());
+    }
+}
@@ -0,0 +1,157 @@
+//! Incremental destruction of huge retired objects, spread over multiple collections.
+
+use core::fmt;
+use std::collections::{BTreeMap, VecDeque};
+#[cfg(not(feature = "check-loom"))]
+use std::sync::Mutex;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::Mutex;
+
+/// An object that can be destroyed in steps, e.g. a node owning a map of millions of entries,
+/// whose `Drop` would otherwise stall the collecting thread.
+///
+/// When retired by `RetiredSet::retire_incremental` to a domain with
+/// `DomainConfig::drop_budget`, the object is handed to the domain once it is unprotected, and each
+/// later `collect` calls `drop_some` on it until it returns `true`. Then, it is dropped as usual.
+///
+/// ```
+/// use hazard::{Domain, RetiredSet};
+///
+/// let domain = Domain::builder().drop_budget(Some(2)).build();
+/// let mut retired = RetiredSet::new(&domain);
+/// unsafe { retired.retire_incremental(Box::into_raw(Box::new(vec![0; 5]))) };
+/// retired.collect();
+/// assert_eq!(domain.incremental_drops(), 1);
+/// retired.collect();
+/// retired.collect();
+/// assert_eq!(domain.incremental_drops(), 0);
+/// ```
+pub trait IncrementalDrop: Send {
+    /// Drops at most `budget` parts of the object, e.g. entries of a collection. Returns `true` if
+    /// the rest of the object is cheap to drop.
+    fn drop_some(&mut self, budget: usize) -> bool;
+}
+
+impl<T: Send> IncrementalDrop for Vec<T> {
+    fn drop_some(&mut self, budget: usize) -> bool {
+        self.truncate(self.len().saturating_sub(budget));
+        self.is_empty()
+    }
+}
+
+impl<T: Send> IncrementalDrop for VecDeque<T> {
+    fn drop_some(&mut self, budget: usize) -> bool {
+        self.truncate(self.len().saturating_sub(budget));
+        self.is_empty()
+    }
+}
+
+impl<K: Ord + Send, V: Send> IncrementalDrop for BTreeMap<K, V> {
+    fn drop_some(&mut self, budget: usize) -> bool {
+        for _ in 0..budget {
+            if self.pop_last().is_none() {
+                break;
+            }
+        }
+        self.is_empty()
+    }
+}
+
+/// The objects of a domain being destroyed incrementally, in the order they are unprotected.
+pub(crate) struct IncrementalDrops {
+    queue: Mutex<VecDeque<Box<dyn IncrementalDrop>>>,
+}
+
+impl IncrementalDrops {
+    #[cfg(not(feature = "check-loom"))]
+    pub(crate) const fn new() -> Self {
+        Self {
+            queue: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    #[cfg(feature = "check-loom")]
+    pub(crate) fn new() -> Self {
+        Self {
+            queue: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Queues `object` to be destroyed by the next `step`s.
+    pub(crate) fn push(&self, object: Box<dyn IncrementalDrop>) {
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        queue.push_back(object);
+    }
+
+    /// Drops at most `budget` parts of the first queued object, and the object itself once it is
+    /// done.
+    pub(crate) fn step(&self, budget: usize) {
+        // Take the object out so that it is dropped without the lock, as it may retire other
+        // pointers.
+        let Some(mut object) = self
+            .queue
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front()
+        else {
+            return;
+        };
+        if !object.drop_some(budget) {
+            let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+            queue.push_front(object);
+        }
+    }
+
+    /// Returns the number of queued objects.
+    pub(crate) fn len(&self) -> usize {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+impl fmt::Debug for IncrementalDrops {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IncrementalDrops")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+    use crate::test::common::Tester;
+    use crate::{Domain, RetiredSet};
+
+    // each collection drops at most `drop_budget` elements of an unprotected vector.
+    #[test]
+    fn drop_in_steps() {
+        let freed = Arc::new(AtomicUsize::new(0));
+        let domain = Domain::builder().drop_budget(Some(10)).build();
+        let mut retired = RetiredSet::new(&domain);
+        let vector = (0..25).map(|_| Tester(freed.clone())).collect::<Vec<_>>();
+        unsafe { retired.retire_incremental(Box::into_raw(Box::new(vector))) };
+        for expected in [10, 20, 25, 25] {
+            retired.collect();
+            assert_eq!(freed.load(Relaxed), expected);
+        }
+        assert_eq!(domain.incremental_drops(), 0);
+    }
+
+    // objects still queued are dropped with the domain.
+    #[test]
+    fn drop_with_domain() {
+        let freed = Arc::new(AtomicUsize::new(0));
+        let domain = Domain::builder().drop_budget(Some(1)).build();
+        let mut retired = RetiredSet::new(&domain);
+        let vector = (0..3).map(|_| Tester(freed.clone())).collect::<Vec<_>>();
+        unsafe { retired.retire_incremental(Box::into_raw(Box::new(vector))) };
+        retired.collect();
+        assert_eq!(freed.load(Relaxed), 1);
+        drop(retired);
+        drop(domain);
+        assert_eq!(freed.load(Relaxed), 3);
+    }
+}
@@ -20,37 +20,225 @@
 //! collect();
 //! ```
 
+#![cfg_attr(feature = "nightly", feature(thread_local))]
+
+#[cfg(all(feature = "global", feature = "nightly", not(feature = "check-loom")))]
+use core::cell::Cell;
+#[cfg(feature = "global")]
 use core::cell::RefCell;
-#[cfg(not(feature = "check-loom"))]
+#[cfg(all(feature = "global", feature = "nightly", not(feature = "check-loom")))]
+use core::ptr;
+#[cfg(all(feature = "global", not(feature = "check-loom")))]
+use core::sync::atomic::AtomicPtr;
+#[cfg(all(feature = "global", not(feature = "check-loom")))]
 use std::thread_local;
 
-#[cfg(feature = "check-loom")]
+#[cfg(all(feature = "global", feature = "check-loom"))]
+use loom::sync::atomic::AtomicPtr;
+#[cfg(all(feature = "global", feature = "check-loom"))]
 use loom::thread_local;
 
+#[cfg(feature = "asan")]
+mod asan;
+mod atomic;
+mod backoff;
+mod bloom;
+pub mod collections;
+mod counted;
+mod domain;
+pub mod ebr;
+mod error;
+#[cfg(feature = "fault-injection")]
+mod fault;
+mod future;
 mod hazard;
+#[cfg(feature = "stats-histogram")]
+mod histogram;
+#[cfg(any(test, feature = "test-hooks"))]
+pub mod hooks;
+pub mod hyaline;
+pub mod ibr;
+mod incremental;
+mod macros;
+#[cfg(feature = "prometheus")]
+mod metrics;
+mod ordering;
+#[cfg(feature = "global")]
+mod pool;
+pub mod prelude;
+mod quiescent;
+mod realtime;
+mod reclaim;
 mod retire;
+mod retired_list;
+mod revocable;
+mod seq_cell;
+mod shield_vec;
+#[cfg(feature = "simd")]
+mod simd;
+mod slab;
+mod table;
+#[cfg(feature = "task-local")]
+pub mod task;
 pub mod test;
+mod traversal;
+#[cfg(feature = "valgrind")]
+mod valgrind;
 
-pub use hazard::{HazardBag, Shield};
-pub use retire::RetiredSet;
-
+pub use atomic::{Atomic, Owned, Shared};
+pub use backoff::WaitStrategy;
 #[cfg(not(feature = "check-loom"))]
-/// Default global bag of all hazard pointers.
-pub static HAZARDS: HazardBag = HazardBag::new();
+pub use backoff::{set_wait_strategy, wait_strategy};
+pub use counted::{AtomicCounted, CountedRef};
+pub use domain::{
+    Domain, DomainBuilder, DomainConfig, DomainEvent, DomainHandle, DropPolicy, PendingPolicy,
+    StallPolicy,
+};
+pub use error::{AllocError, ProtectError};
+pub use future::ProtectedFuture;
+#[cfg(feature = "owner-info")]
+pub use hazard::SlotOwner;
+pub use hazard::{
+    HazardBag, Protected, Shield, SlotAllocator, SlotHandle, Slots, Unvalidated, Validated,
+};
+#[cfg(feature = "stats-histogram")]
+pub use histogram::LatencyHistogram;
+pub use incremental::IncrementalDrop;
+#[cfg(feature = "global")]
+pub use pool::Pool;
+pub use quiescent::Quiescent;
+pub use realtime::{OverflowPolicy, RetiredRing};
+pub use reclaim::{Protect, Reclaimer, RetireOnDrop};
+pub use retire::{ReclaimHandle, RetiredBatch, RetiredSet};
+pub use revocable::{Revocable, RevocableGuard};
+pub use seq_cell::SeqCell;
+pub use shield_vec::ShieldVec;
+pub use slab::Slab;
+#[cfg(feature = "global")]
+pub use slab::retire_slot;
+pub use traversal::{MARK, Traversal};
 
-#[cfg(feature = "check-loom")]
+#[cfg(all(feature = "global", not(feature = "check-loom")))]
+/// Default global domain of all hazard pointers.
+pub static HAZARDS: Domain = Domain::new();
+
+#[cfg(all(feature = "global", feature = "check-loom"))]
 // FIXME: loom does not currently provide the equivalent of Lazy:
 // https://github.com/tokio-rs/loom/issues/263
 loom::lazy_static! {
-    /// Default global bag of all hazard pointers.
-    pub static ref HAZARDS: HazardBag = HazardBag::new();
+    /// Default global domain of all hazard pointers.
+    pub static ref HAZARDS: Domain = Domain::new();
 }
 
+#[cfg(feature = "global")]
 thread_local! {
     /// Default thread-local retired pointer list.
-    static RETIRED: RefCell<RetiredSet<'static>> = RefCell::new(RetiredSet::default());
+    static RETIRED: LocalRetired = LocalRetired(RefCell::new(RetiredSet::default()));
+}
+
+/// Fast path to `RETIRED` of the current thread, set on the first access to `RETIRED` and cleared
+/// when it is destroyed. Unlike `thread_local!`, accessing it needs no lazy initialization.
+#[cfg(all(feature = "global", feature = "nightly", not(feature = "check-loom")))]
+#[thread_local]
+static RETIRED_FAST: Cell<*const LocalRetired> = Cell::new(ptr::null());
+
+#[cfg(feature = "global")]
+thread_local! {
+    /// Hazard slot of the current thread used by `protect_global`, acquired on the first use.
+    static GLOBAL_SHIELD: Shield<'static> = Shield::default();
+}
+
+#[cfg(feature = "global")]
+/// Storage of `RETIRED`.
+struct LocalRetired(RefCell<RetiredSet>);
+
+#[cfg(all(feature = "global", feature = "nightly", not(feature = "check-loom")))]
+impl Drop for LocalRetired {
+    fn drop(&mut self) {
+        RETIRED_FAST.set(ptr::null());
+    }
+}
+
+#[cfg(feature = "global")]
+/// Runs `f` with the retired pointer list of the current thread.
+fn with_retired<R>(f: impl FnOnce(&mut RetiredSet) -> R) -> R {
+    #[cfg(all(feature = "nightly", not(feature = "check-loom")))]
+    // # Safety
+    // `RETIRED_FAST` is non-null only while `RETIRED` of the current thread is alive.
+    if let Some(local) = unsafe { RETIRED_FAST.get().as_ref() } {
+        return f(&mut local.0.borrow_mut());
+    }
+    RETIRED.with(|local| {
+        #[cfg(all(feature = "nightly", not(feature = "check-loom")))]
+        RETIRED_FAST.set(local);
+        f(&mut local.0.borrow_mut())
+    })
+}
+
+#[cfg(feature = "global")]
+/// Configures the default domain `HAZARDS`.
+///
+/// This must be called before the first use of the default domain, e.g. at the start of `main`.
+/// Otherwise, the default configuration is already in effect and `config` is returned back. With
+/// `DomainConfig::background_reclaim`, this also starts the thread collecting the default domain.
+///
+/// ```
+/// use hazard::DomainConfig;
+///
+/// hazard::init(DomainConfig::builder().threshold(128).shards(4).config()).unwrap();
+/// ```
+// `config` is returned back by value, as by `Domain::configure`.
+#[allow(clippy::result_large_err)]
+pub fn init(config: DomainConfig) -> Result<(), DomainConfig> {
+    HAZARDS.configure(config)?;
+    #[cfg(not(feature = "check-loom"))]
+    HAZARDS.reclaim_in_background();
+    Ok(())
+}
+
+#[cfg(feature = "global")]
+/// Protects the pointer loaded from `src` with a new shield of the default domain, which is
+/// released when the returned guard is dropped.
+///
+/// ```
+/// use std::sync::atomic::AtomicPtr;
+///
+/// let src = AtomicPtr::new(Box::into_raw(Box::new(1)));
+/// let protected = hazard::load_protected(&src);
+/// assert_eq!(unsafe { protected.as_ref() }, Some(&1));
+/// # drop(protected);
+/// # drop(unsafe { Box::from_raw(src.into_inner()) });
+/// ```
+pub fn load_protected<T>(src: &AtomicPtr<T>) -> Protected<'static, T> {
+    Protected::new(Shield::default(), src)
+}
+
+#[cfg(feature = "global")]
+/// Protects the pointer loaded from `src` with the single hazard slot of the current thread in the
+/// default domain, which skips acquiring a slot. The pointer stays protected until the next call
+/// to `protect_global` or `clear_global` on the current thread, so it fits readers that protect
+/// one pointer at a time.
+///
+/// ```
+/// use std::sync::atomic::AtomicPtr;
+///
+/// let src = AtomicPtr::new(Box::into_raw(Box::new(1)));
+/// let pointer = hazard::protect_global(&src);
+/// assert_eq!(unsafe { *pointer }, 1);
+/// hazard::clear_global();
+/// # drop(unsafe { Box::from_raw(src.into_inner()) });
+/// ```
+pub fn protect_global<T>(src: &AtomicPtr<T>) -> *mut T {
+    GLOBAL_SHIELD.with(|shield| shield.protect(src))
 }
 
+#[cfg(feature = "global")]
+/// Clears the hazard slot of the current thread used by `protect_global`.
+pub fn clear_global() {
+    let _ = GLOBAL_SHIELD.try_with(Shield::clear);
+}
+
+#[cfg(feature = "global")]
 /// Retires a pointer.
 ///
 /// # Safety
@@ -58,11 +246,58 @@ thread_local! {
 /// * `pointer` must be removed from shared memory before calling this function, and must be valid.
 /// * The same `pointer` should only be retired once.
 pub unsafe fn retire<T>(pointer: *mut T) {
-    RETIRED.with(|r| unsafe { r.borrow_mut().retire(pointer) });
+    with_retired(|r| unsafe { r.retire(pointer) });
+}
+
+#[cfg(feature = "global")]
+/// Retires a pointer as `retire`, and returns a handle telling whether it is reclaimed.
+///
+/// # Safety
+///
+/// See `retire`.
+pub unsafe fn retire_notify<T>(pointer: *mut T) -> ReclaimHandle {
+    with_retired(|r| unsafe { r.retire_notify(pointer) })
 }
 
-/// Frees the pointers that are `retire`d by the current thread and not `protect`ed by any other
-/// threads.
+#[cfg(feature = "global")]
+/// Retires a pointer to be passed to `deleter` with `context` instead of being freed, e.g. to
+/// recycle it.
+///
+/// # Safety
+///
+/// * `pointer` must be removed from shared memory before calling this function.
+/// * The same `pointer` should only be retired once.
+/// * It must be safe to call `deleter` with `pointer` and `context` once it is no longer
+///   protected.
+pub unsafe fn retire_with(
+    pointer: *mut (),
+    deleter: unsafe fn(*mut (), *const ()),
+    context: *const (),
+) {
+    with_retired(|r| unsafe { r.retire_with(pointer, deleter, context) });
+}
+
+#[cfg(feature = "global")]
+/// Hands the pointers retired by the current thread so far over to the default domain, to be freed
+/// by the next `collect` of any thread. See `RetiredSet::hand_off`.
+///
+/// # Safety
+///
+/// The pointers retired by the current thread must be safe to free in any thread.
+pub unsafe fn hand_off() {
+    with_retired(|r| unsafe { r.hand_off() });
+}
+
+#[cfg(feature = "global")]
+/// Frees the pointers that are `retire`d by the current thread or handed off by any thread, and not
+/// `protect`ed by any other threads.
 pub fn collect() {
-    RETIRED.with(|r| r.borrow_mut().collect());
+    with_retired(|r| r.collect());
+}
+
+#[cfg(feature = "global")]
+/// Frees at most `max_frees` of the pointers `retire`d by the current thread that are not
+/// `protect`ed, and returns the number of pointers freed. See `RetiredSet::collect_budgeted`.
+pub fn collect_budgeted(max_frees: usize) -> usize {
+    with_retired(|r| r.collect_budgeted(max_frees))
 }
@@ -0,0 +1,73 @@
+//! Macros for protecting pointers without spelling out the protection loop.
+
+/// Protects the pointer loaded from `src`, a place of type `AtomicPtr<T>`, with `shield`, and
+/// evaluates to the protected `*mut T`.
+///
+/// This expands to the load/publish/validate loop of `Shield::protect`, retrying until the
+/// published hazard is validated against `src`.
+///
+/// ```
+/// use std::sync::atomic::AtomicPtr;
+/// use hazard::{Shield, protect};
+///
+/// let src = AtomicPtr::new(Box::into_raw(Box::new(1)));
+/// let shield = Shield::default();
+/// let pointer = protect!(shield, src);
+/// assert_eq!(unsafe { *pointer }, 1);
+/// # drop(shield);
+/// # drop(unsafe { Box::from_raw(src.into_inner()) });
+/// ```
+#[macro_export]
+macro_rules! protect {
+    ($shield:expr, $src:expr $(,)?) => {
+        ($shield).protect(&$src)
+    };
+}
+
+/// Protects the pointer loaded from `src`, a place of type `AtomicPtr<T>`, with a shield of the
+/// default domain taken from the cache of the current thread, and evaluates to the `Protected`
+/// guard.
+///
+/// ```
+/// use std::sync::atomic::AtomicPtr;
+/// use hazard::protected;
+///
+/// let src = AtomicPtr::new(Box::into_raw(Box::new(1)));
+/// let protected = protected!(src);
+/// assert_eq!(unsafe { protected.as_ref() }, Some(&1));
+/// # drop(protected);
+/// # drop(unsafe { Box::from_raw(src.into_inner()) });
+/// ```
+#[cfg(feature = "global")]
+#[macro_export]
+macro_rules! protected {
+    ($src:expr $(,)?) => {
+        $crate::load_protected(&$src)
+    };
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::sync::atomic::AtomicPtr;
+
+    use crate::{HazardBag, Shield};
+
+    struct Node {
+        next: AtomicPtr<usize>,
+    }
+
+    // `protect!` accepts field places and borrowed shields, and publishes the loaded pointer.
+    #[test]
+    fn protect_place() {
+        let hazards = HazardBag::new();
+        let target = Box::into_raw(Box::new(3));
+        let node = Node {
+            next: AtomicPtr::new(target),
+        };
+        let shield = &Shield::new(&hazards);
+        let pointer = protect!(shield, node.next);
+        assert_eq!(pointer, target);
+        assert!(hazards.all_hazards().contains(&target.cast()));
+        drop(unsafe { Box::from_raw(target) });
+    }
+}
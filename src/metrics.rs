@@ -0,0 +1,150 @@
+//! Metrics of a domain in the Prometheus text exposition format.
+
+use core::fmt::Write;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::AtomicU64;
+use std::time::Duration;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicU64;
+
+use super::Domain;
+use super::ordering::Ordering;
+
+/// Counters of a domain exported only as metrics.
+#[derive(Debug)]
+pub(crate) struct Counters {
+    scans: AtomicU64,
+    // In nanoseconds.
+    scan_time: AtomicU64,
+    reclaimed: AtomicU64,
+}
+
+impl Counters {
+    #[cfg(not(feature = "check-loom"))]
+    pub(crate) const fn new() -> Self {
+        Self {
+            scans: AtomicU64::new(0),
+            scan_time: AtomicU64::new(0),
+            reclaimed: AtomicU64::new(0),
+        }
+    }
+
+    #[cfg(feature = "check-loom")]
+    pub(crate) fn new() -> Self {
+        Self {
+            scans: AtomicU64::new(0),
+            scan_time: AtomicU64::new(0),
+            reclaimed: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts a scan of the hazards taking `time`.
+    pub(crate) fn scanned(&self, time: Duration) {
+        let _ = self.scans.fetch_add(1, Ordering::Relaxed);
+        let nanos = u64::try_from(time.as_nanos()).unwrap_or(u64::MAX);
+        let _ = self.scan_time.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    /// Counts a reclaimed object.
+    pub(crate) fn reclaimed(&self) {
+        let _ = self.reclaimed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Appends a metric with its help and type lines, and one sample per `(suffix, value)`.
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(&str, f64)]) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    for (suffix, value) in samples {
+        let _ = writeln!(out, "{name}{suffix} {value}");
+    }
+}
+
+/// Renders the metrics of `domain`. See `Domain::metrics_prometheus`.
+pub(crate) fn prometheus(domain: &Domain, counters: &Counters) -> String {
+    let (mut slots, mut active, mut protecting) = (0, 0, 0);
+    for (_, is_active, hazard) in domain.hazards().slots() {
+        slots += 1;
+        active += usize::from(is_active);
+        protecting += usize::from(!hazard.is_null());
+    }
+    let mut out = String::new();
+    for (name, help, value) in [
+        ("hazard_slots", "Hazard slots allocated.", slots),
+        (
+            "hazard_slots_active",
+            "Hazard slots owned by shields.",
+            active,
+        ),
+        (
+            "hazard_slots_protecting",
+            "Hazard slots protecting a pointer.",
+            protecting,
+        ),
+        (
+            "hazard_pending_objects",
+            "Objects retired and not reclaimed yet.",
+            domain.pending_objects(),
+        ),
+        (
+            "hazard_pending_bytes",
+            "Bytes of the objects retired and not reclaimed yet.",
+            domain.pending_bytes(),
+        ),
+        (
+            "hazard_pending_bytes_high_watermark",
+            "The largest hazard_pending_bytes so far.",
+            domain.high_watermark(),
+        ),
+    ] {
+        metric(&mut out, name, "gauge", help, &[("", value as f64)]);
+    }
+    let reclaimed = counters.reclaimed.load(Ordering::Relaxed);
+    metric(
+        &mut out,
+        "hazard_reclaimed_objects_total",
+        "counter",
+        "Objects reclaimed.",
+        &[("", reclaimed as f64)],
+    );
+    metric(
+        &mut out,
+        "hazard_reclaimed_bytes_total",
+        "counter",
+        "Bytes of the objects reclaimed.",
+        &[("", domain.reclaimed_bytes() as f64)],
+    );
+    let scan_time = Duration::from_nanos(counters.scan_time.load(Ordering::Relaxed));
+    metric(
+        &mut out,
+        "hazard_scan_duration_seconds",
+        "summary",
+        "Time spent scanning the hazards to reclaim retired objects.",
+        &[
+            ("_sum", scan_time.as_secs_f64()),
+            ("_count", counters.scans.load(Ordering::Relaxed) as f64),
+        ],
+    );
+    #[cfg(feature = "stats-histogram")]
+    {
+        let latencies = domain.reclaim_latencies();
+        metric(
+            &mut out,
+            "hazard_reclaim_latency_seconds",
+            "summary",
+            "Time from retire to the free of reclaimed objects.",
+            &[
+                ("{quantile=\"0.5\"}", latencies.quantile(0.5).as_secs_f64()),
+                ("{quantile=\"0.9\"}", latencies.quantile(0.9).as_secs_f64()),
+                (
+                    "{quantile=\"0.99\"}",
+                    latencies.quantile(0.99).as_secs_f64(),
+                ),
+                ("_sum", latencies.sum().as_secs_f64()),
+                ("_count", latencies.count() as f64),
+            ],
+        );
+    }
+    out
+}
@@ -0,0 +1,28 @@
+//! Memory orderings of the atomics of the crate, which are all `SeqCst` with the `seqcst-debug`
+//! feature. Comparing a test under both modes tells whether a failure is due to a too weak
+//! ordering of the protocol.
+
+#[cfg(not(feature = "seqcst-debug"))]
+pub(crate) use core::sync::atomic::Ordering;
+
+#[cfg(feature = "seqcst-debug")]
+#[allow(non_snake_case, non_upper_case_globals)]
+/// Stand-in for the variants of `core::sync::atomic::Ordering`, all upgraded to `SeqCst`.
+pub(crate) mod Ordering {
+    use core::sync::atomic::Ordering;
+
+    pub(crate) const Relaxed: Ordering = Ordering::SeqCst;
+    pub(crate) const Release: Ordering = Ordering::SeqCst;
+    pub(crate) const Acquire: Ordering = Ordering::SeqCst;
+    pub(crate) const AcqRel: Ordering = Ordering::SeqCst;
+    pub(crate) const SeqCst: Ordering = Ordering::SeqCst;
+}
+
+/// Returns `order` given by the user, upgraded to `SeqCst` with the `seqcst-debug` feature.
+pub(crate) fn upgrade(order: core::sync::atomic::Ordering) -> core::sync::atomic::Ordering {
+    if cfg!(feature = "seqcst-debug") {
+        core::sync::atomic::Ordering::SeqCst
+    } else {
+        order
+    }
+}
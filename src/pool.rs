@@ -0,0 +1,221 @@
+//! Pool of objects recycled with hazard pointers.
+
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr::{self, NonNull};
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicPtr, AtomicUsize};
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, AtomicUsize};
+
+use super::Shield;
+use super::ordering::Ordering;
+
+/// A lock-free pool of objects of type `T` in the default domain.
+///
+/// An object allocated from the pool is retired back into the pool instead of the allocator, and
+/// is reused only once no shield protects it. The free list is itself protected with hazard
+/// pointers, so it is free from ABA.
+///
+/// ```
+/// use hazard::Pool;
+///
+/// let pool = Pool::new();
+/// let pointer = pool.alloc(1);
+/// unsafe { pool.retire(pointer) };
+/// hazard::collect();
+/// assert_eq!(pool.alloc(2), pointer);
+/// # unsafe { pool.retire(pointer) };
+/// ```
+#[derive(Debug)]
+pub struct Pool<T> {
+    inner: NonNull<Inner<T>>,
+    _marker: PhantomData<T>,
+}
+
+/// Storage shared by the pool and its objects, freed by the last of them.
+#[derive(Debug)]
+struct Inner<T> {
+    free: AtomicPtr<Node<T>>,
+    // 1 for the `Pool` plus the number of nodes that are not in `free`.
+    refs: AtomicUsize,
+}
+
+// `value` must be the first for the pointers to values to be the pointers to nodes.
+#[repr(C)]
+#[derive(Debug)]
+struct Node<T> {
+    value: MaybeUninit<T>,
+    next: *mut Node<T>,
+    pool: NonNull<Inner<T>>,
+}
+
+unsafe impl<T: Send> Send for Pool<T> {}
+unsafe impl<T: Send> Sync for Pool<T> {}
+
+impl<T> Pool<T> {
+    /// Creates a new empty pool.
+    pub fn new() -> Self {
+        let inner = Box::new(Inner {
+            free: AtomicPtr::new(ptr::null_mut()),
+            refs: AtomicUsize::new(1),
+        });
+        Self {
+            inner: NonNull::from(Box::leak(inner)),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Allocates `value` in a recycled object, or in a new one if there is none.
+    pub fn alloc(&self, value: T) -> *mut T {
+        let inner = unsafe { self.inner.as_ref() };
+        let _ = inner.refs.fetch_add(1, Ordering::Relaxed);
+        let shield = Shield::default();
+        let node = loop {
+            let head = shield.protect(&inner.free);
+            if head.is_null() {
+                break Box::into_raw(Box::new(Node {
+                    value: MaybeUninit::uninit(),
+                    next: ptr::null_mut(),
+                    pool: self.inner,
+                }));
+            }
+            // SAFETY: `head` is protected, so it is not recycled into the list again.
+            let next = unsafe { (*head).next };
+            if inner
+                .free
+                .compare_exchange(head, next, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                break head;
+            }
+        };
+        unsafe { (*node).value.write(value) }
+    }
+
+    /// Retires a pointer allocated from this pool. It is dropped and recycled into the pool once
+    /// no longer protected, even if the pool is dropped before.
+    ///
+    /// # Safety
+    ///
+    /// * `pointer` must be allocated by `alloc` of this pool.
+    /// * `pointer` must be removed from shared memory before calling this function.
+    /// * The same `pointer` should only be retired once.
+    pub unsafe fn retire(&self, pointer: *mut T) {
+        unsafe { super::retire_with(pointer.cast(), recycle::<T>, ptr::null()) };
+    }
+}
+
+impl<T> Default for Pool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Pool<T> {
+    fn drop(&mut self) {
+        unsafe { Inner::release(self.inner) };
+    }
+}
+
+impl<T> Inner<T> {
+    /// Releases a reference, freeing the pool if it was the last.
+    unsafe fn release(inner: NonNull<Self>) {
+        if unsafe { inner.as_ref() }
+            .refs
+            .fetch_sub(1, Ordering::AcqRel)
+            != 1
+        {
+            return;
+        }
+        let inner = unsafe { Box::from_raw(inner.as_ptr()) };
+        let mut node = inner.free.load(Ordering::Relaxed);
+        while !node.is_null() {
+            let curr = unsafe { Box::from_raw(node) };
+            node = curr.next;
+        }
+    }
+}
+
+/// Drops the value of a retired node and pushes the node to the free list of its pool.
+unsafe fn recycle<T>(data: *mut (), _: *const ()) {
+    let node = data.cast::<Node<T>>();
+    unsafe { (*node).value.assume_init_drop() };
+    let pool = unsafe { (*node).pool };
+    let inner = unsafe { pool.as_ref() };
+    let mut head = inner.free.load(Ordering::Relaxed);
+    loop {
+        unsafe { (*node).next = head };
+        match inner
+            .free
+            .compare_exchange(head, node, Ordering::Release, Ordering::Relaxed)
+        {
+            Ok(_) => break,
+            Err(current) => head = current,
+        }
+    }
+    unsafe { Inner::release(pool) };
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+    use std::thread;
+
+    use super::Pool;
+    use crate::Shield;
+    use crate::test::common::Tester;
+
+    // an object is recycled only once it is no longer protected.
+    #[test]
+    fn recycle_unprotected() {
+        thread::spawn(|| {
+            let pool = Pool::new();
+            let pointer = pool.alloc(1);
+            let shield = Shield::default();
+            let _ = shield.protect(&AtomicPtr::new(pointer));
+            unsafe { pool.retire(pointer) };
+            crate::collect();
+            let other = pool.alloc(2);
+            assert_ne!(other, pointer);
+
+            drop(shield);
+            crate::collect();
+            assert_eq!(pool.alloc(3), pointer);
+            unsafe { pool.retire(pointer) };
+            unsafe { pool.retire(other) };
+        })
+        .join()
+        .unwrap();
+    }
+
+    // all the values are dropped, even after the pool.
+    #[test]
+    fn drop_values() {
+        const THREADS: usize = 8;
+        const ITER: usize = 1024;
+
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let pool = Arc::new(Pool::new());
+        // unlike scoped threads, joining waits for the thread-local retired lists to be freed.
+        let handles = (0..THREADS)
+            .map(|_| {
+                let (pool, dropped) = (pool.clone(), dropped.clone());
+                thread::spawn(move || {
+                    for _ in 0..ITER {
+                        let pointer = pool.alloc(Tester(dropped.clone()));
+                        unsafe { pool.retire(pointer) };
+                    }
+                    crate::collect();
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        drop(pool);
+        assert_eq!(dropped.load(Ordering::Relaxed), THREADS * ITER);
+    }
+}
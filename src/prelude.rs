@@ -0,0 +1,20 @@
+//! The common types and traits of the crate, to be glob-imported.
+//!
+//! ```
+//! use hazard::prelude::*;
+//!
+//! let domain = Domain::new();
+//! let atomic = Atomic::from(Owned::new(1));
+//! let mut shield = Shield::new(domain.hazards());
+//! let shared = atomic.load_shared(&mut shield);
+//! assert_eq!(unsafe { shared.as_ref() }, Some(&1));
+//! # drop(shield);
+//! # drop(unsafe { Owned::from_raw(atomic.into_inner()) });
+//! ```
+
+#[cfg(feature = "global")]
+pub use crate::HAZARDS;
+pub use crate::{
+    Atomic, Domain, DomainConfig, DomainHandle, HazardBag, Owned, Protect, Protected, Reclaimer,
+    RetireOnDrop, RetiredSet, Shared, Shield, ShieldVec,
+};
@@ -0,0 +1,137 @@
+//! Quiescent-state-based reclamation (QSBR), complementing the shields of a domain.
+//!
+//! A thread registered with `Domain::register_quiescent` may access the pointers of the domain
+//! without shields, as long as it announces a quiescent state, i.e. a point where it holds no such
+//! pointer, from time to time, e.g. at each iteration of its event loop. A retired pointer is then
+//! freed only once no shield protects it and every registered thread has announced a quiescent
+//! state since it was retired.
+
+use core::marker::PhantomData;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::AtomicUsize;
+#[cfg(not(feature = "check-loom"))]
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicUsize;
+#[cfg(feature = "check-loom")]
+use loom::sync::{Arc, Mutex};
+
+use super::Domain;
+use super::ordering::Ordering;
+
+/// The grace periods of a domain.
+#[derive(Debug)]
+pub(crate) struct Quiescence {
+    /// Advanced by each `collect` while threads are registered.
+    epoch: AtomicUsize,
+    /// The epoch each registered thread observed at its last quiescent state.
+    observed: Mutex<Vec<Arc<AtomicUsize>>>,
+}
+
+impl Quiescence {
+    #[cfg(not(feature = "check-loom"))]
+    pub(crate) const fn new() -> Self {
+        Self {
+            epoch: AtomicUsize::new(0),
+            observed: Mutex::new(Vec::new()),
+        }
+    }
+
+    #[cfg(feature = "check-loom")]
+    pub(crate) fn new() -> Self {
+        Self {
+            epoch: AtomicUsize::new(0),
+            observed: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns the current epoch, with which retired pointers are tagged.
+    pub(crate) fn epoch(&self) -> usize {
+        self.epoch.load(Ordering::SeqCst)
+    }
+
+    /// Starts a new grace period, and returns the one that registered threads may still be in.
+    pub(crate) fn advance(&self) -> GracePeriod {
+        let observed = self.observed.lock().unwrap_or_else(|e| e.into_inner());
+        if observed.is_empty() {
+            return GracePeriod(usize::MAX);
+        }
+        let _ = self.epoch.fetch_add(1, Ordering::SeqCst);
+        let start = observed
+            .iter()
+            .map(|epoch| epoch.load(Ordering::SeqCst))
+            .min()
+            .unwrap_or(usize::MAX);
+        GracePeriod(start)
+    }
+}
+
+/// The epochs from the earliest one observed by a registered thread, returned by
+/// `Quiescence::advance`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GracePeriod(usize);
+
+impl GracePeriod {
+    /// Returns `true` if a pointer retired at `epoch` is retired in the grace period, so that it
+    /// may still be accessed by quiescent threads and must not be freed even if unprotected.
+    pub(crate) fn covers(self, epoch: usize) -> bool {
+        epoch >= self.0
+    }
+}
+
+/// The registration of a thread in the quiescent-state-based reclamation of a domain. See
+/// `Domain::register_quiescent`.
+///
+/// ```
+/// use hazard::Domain;
+///
+/// let domain = Domain::new();
+/// let quiescent = domain.register_quiescent();
+/// for _ in 0..3 {
+///     // access the pointers of `domain` without shields
+///     quiescent.quiescent_state();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Quiescent<'d> {
+    domain: &'d Domain,
+    observed: Arc<AtomicUsize>,
+    _marker: PhantomData<*const ()>, // !Send + !Sync
+}
+
+impl<'d> Quiescent<'d> {
+    pub(crate) fn new(domain: &'d Domain) -> Self {
+        let quiescence = domain.quiescence();
+        let mut observed = quiescence
+            .observed
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let epoch = Arc::new(AtomicUsize::new(quiescence.epoch()));
+        observed.push(epoch.clone());
+        Self {
+            domain,
+            observed: epoch,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Announces that the current thread holds no pointer of the domain that is not protected by
+    /// a shield, so that the pointers retired before may be freed.
+    pub fn quiescent_state(&self) {
+        self.observed
+            .store(self.domain.quiescence().epoch(), Ordering::SeqCst);
+    }
+}
+
+impl Drop for Quiescent<'_> {
+    /// Unregisters the thread, which is then quiescent until registered again.
+    fn drop(&mut self) {
+        self.domain
+            .quiescence()
+            .observed
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|epoch| !Arc::ptr_eq(epoch, &self.observed));
+    }
+}
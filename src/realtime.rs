@@ -0,0 +1,231 @@
+//! Allocation-free, bounded-time retirement for real-time threads, e.g. audio or control loops.
+//!
+//! A real-time thread `HazardBag::reserve`s the slots it needs up front, takes its shields with
+//! `Shield::new_reserved`, and retires to a `RetiredRing` of a fixed capacity. Then, `protect`,
+//! shield construction and `RetiredRing::retire` never allocate, and `RetiredRing::collect` takes
+//! time bounded by the capacity and the number of slots. The reserved slots are never taken by the
+//! shields of other threads, so a burst of them cannot starve the real-time thread.
+
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+
+use super::Domain;
+use super::retire::{self, Retired};
+use super::retired_list::RetiredList;
+
+/// What `RetiredRing::retire` does when the ring is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum OverflowPolicy {
+    /// Collect the ring once, and reject the pointer if it is still full.
+    #[default]
+    Collect,
+    /// Reject the pointer at once, leaving the caller to retire it later, e.g. outside the
+    /// real-time loop.
+    Reject,
+}
+
+/// A retired pointer list of a fixed capacity `N`, stored inline so that retiring never allocates.
+///
+/// ```
+/// use hazard::{Domain, OverflowPolicy, RetiredRing};
+///
+/// let domain = Domain::new();
+/// let mut retired = RetiredRing::<4>::new(&domain);
+/// retired.set_overflow_policy(OverflowPolicy::Reject);
+/// for i in 0..4 {
+///     unsafe { retired.retire(Box::into_raw(Box::new(i))) }.unwrap();
+/// }
+/// let pointer = Box::into_raw(Box::new(4));
+/// assert_eq!(unsafe { retired.retire(pointer) }, Err(pointer));
+/// assert_eq!(retired.collect(), 4);
+/// assert_eq!(unsafe { retired.retire(pointer) }, Ok(()));
+/// ```
+pub struct RetiredRing<'d, const N: usize> {
+    domain: &'d Domain,
+    entries: [MaybeUninit<Retired>; N],
+    /// The entries before `len` are initialized.
+    len: usize,
+    policy: OverflowPolicy,
+    _marker: PhantomData<*const ()>, // !Send + !Sync
+}
+
+impl<'d, const N: usize> RetiredRing<'d, N> {
+    /// Creates a new ring of pointers retired to `domain`.
+    pub fn new(domain: &'d Domain) -> Self {
+        const { assert!(N > 0, "a `RetiredRing` must have a positive capacity") };
+        Self {
+            domain,
+            entries: [const { MaybeUninit::uninit() }; N],
+            len: 0,
+            policy: OverflowPolicy::Collect,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets what `retire` does when the ring is full.
+    pub fn set_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self.policy = policy;
+    }
+
+    /// Returns the number of pointers in the ring.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the ring has no pointers.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Retires a pointer, or returns it back if the ring is full, following the overflow policy.
+    ///
+    /// # Safety
+    ///
+    /// * `pointer` must be removed from shared memory before calling this function, and must be
+    ///   valid.
+    /// * The same `pointer` should only be retired once.
+    pub unsafe fn retire<T: Send>(&mut self, pointer: *mut T) -> Result<(), *mut T> {
+        if self.len == N {
+            if self.policy == OverflowPolicy::Reject {
+                return Err(pointer);
+            }
+            let _ = self.collect();
+            if self.len == N {
+                return Err(pointer);
+            }
+        }
+        let mut retired = retire::retired(self.domain.hazards(), pointer);
+        retired.epoch = self.domain.quiescence().epoch();
+        let _ = self.domain.add_pending(retired.size);
+        self.entries[self.len].write(retired);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Frees the pointers in the ring that are not protected, and returns the number of pointers
+    /// freed. The hazards are scanned once, without allocating.
+    pub fn collect(&mut self) -> usize {
+        let mut protected = [false; N];
+        self.domain.hazards().for_each_hazard(|hazard| {
+            for (protected, retired) in protected.iter_mut().zip(self.retired()) {
+                *protected |= retired.pointer == hazard;
+            }
+        });
+        let grace_period = self.domain.quiescence().advance();
+        let len = self.len;
+        self.len = 0;
+        for (index, &protected) in protected[..len].iter().enumerate() {
+            // # Safety
+            // the entries before `len` are initialized, and each is read once.
+            let retired = unsafe { self.entries[index].assume_init_read() };
+            if protected || grace_period.covers(retired.epoch) {
+                self.entries[self.len].write(retired);
+                self.len += 1;
+            } else {
+                // # Safety
+                // `retired` is not protected, and it is removed from the ring.
+                unsafe { self.domain.free(retired) };
+            }
+        }
+        len - self.len
+    }
+
+    /// Returns the pointers in the ring.
+    fn retired(&self) -> impl Iterator<Item = &Retired> {
+        // # Safety
+        // the entries before `len` are initialized.
+        self.entries[..self.len]
+            .iter()
+            .map(|retired| unsafe { retired.assume_init_ref() })
+    }
+}
+
+impl<const N: usize> Drop for RetiredRing<'_, N> {
+    /// Hands the pointers still retired over to the domain, to be freed by the next `collect` of
+    /// any thread. Unlike the other operations, this allocates.
+    fn drop(&mut self) {
+        if self.len != 0 {
+            let mut retired = RetiredList::new(self.domain.hazards().allocator());
+            for &entry in self.retired() {
+                retired.push(entry);
+            }
+            self.domain.hand_off(&mut retired);
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use super::RetiredRing;
+    use crate::{Domain, Shield};
+
+    // a full ring collects once, and rejects the pointer if its entries stay protected.
+    #[test]
+    fn overflow_collect() {
+        let domain = Domain::new();
+        let mut retired = RetiredRing::<2>::new(&domain);
+        let pointers = [1, 2, 3].map(|i| Box::into_raw(Box::new(i)));
+        let shields = [0, 2].map(|i| {
+            let shield = Shield::new(domain.hazards());
+            let _ = shield.set(pointers[i]);
+            shield
+        });
+        for pointer in pointers {
+            unsafe { retired.retire(pointer) }.unwrap();
+        }
+        assert_eq!(retired.len(), 2);
+        let pointer = Box::into_raw(Box::new(4));
+        assert_eq!(unsafe { retired.retire(pointer) }, Err(pointer));
+        drop(shields);
+        assert_eq!(retired.collect(), 2);
+        drop(unsafe { Box::from_raw(pointer) });
+    }
+
+    // shields of reserved slots are taken without allocating, until the reserved slots run out,
+    // and other shields allocate instead of taking them.
+    #[cfg(not(feature = "fault-injection"))]
+    #[test]
+    fn new_reserved() {
+        let domain = Domain::new();
+        let hazards = domain.hazards();
+        assert!(Shield::new_reserved(hazards).is_none());
+        hazards.reserve(2).unwrap();
+        let capacity = hazards.capacity();
+        let shields = (0..capacity)
+            .map(|_| Shield::new(hazards))
+            .collect::<Vec<_>>();
+        assert!(hazards.capacity() > capacity);
+        let capacity = hazards.capacity();
+        let reserved = [(); 2].map(|_| Shield::new_reserved(hazards).unwrap());
+        assert!(Shield::new_reserved(hazards).is_none());
+        assert_eq!(hazards.capacity(), capacity);
+        drop(reserved);
+        hazards.reserve(2).unwrap();
+        assert_eq!(hazards.capacity(), capacity);
+        drop(shields);
+    }
+
+    // reserved slots stay available while other threads keep taking shields.
+    #[cfg(not(feature = "fault-injection"))]
+    #[test]
+    fn new_reserved_contended() {
+        use std::thread;
+
+        let domain = Domain::new();
+        let hazards = domain.hazards();
+        hazards.reserve(4).unwrap();
+        thread::scope(|s| {
+            for _ in 0..4 {
+                let _ = s.spawn(|| {
+                    for _ in 0..1000 {
+                        drop([(); 8].map(|_| Shield::new(hazards)));
+                    }
+                });
+            }
+            for _ in 0..1000 {
+                drop([(); 4].map(|_| Shield::new_reserved(hazards).unwrap()));
+            }
+        });
+    }
+}
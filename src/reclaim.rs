@@ -0,0 +1,215 @@
+//! Abstraction over memory reclamation schemes, so that data structures can be written once for
+//! all schemes.
+
+use core::mem;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::AtomicPtr;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicPtr;
+
+use super::backoff::Backoff;
+use super::ordering::Ordering;
+use super::{Domain, ProtectError, Shield};
+
+/// Protection of pointers to shared objects from being freed, in the style of `Shield`.
+///
+/// Schemes in which all objects are protected at once as long as the guard lives (e.g. epochs)
+/// may implement `set` and `clear` as no-ops.
+pub trait Protect {
+    /// Protects `pointer`. It is not validated yet.
+    fn set<T>(&self, pointer: *mut T);
+
+    /// Stops protecting the pointer set to this guard.
+    fn clear(&self);
+
+    /// Check if `src` still points to `pointer`. If not, returns an error with the current value.
+    ///
+    /// For a pointer `p`, if "`src` still pointing to `pointer`" implies that `p` is not retired,
+    /// then `Ok(())` means that guards set to `p` are validated.
+    fn validate<T>(&self, pointer: *mut T, src: &AtomicPtr<T>) -> Result<(), ProtectError<T>> {
+        let current = src.load(Ordering::Relaxed);
+        if current == pointer {
+            Ok(())
+        } else {
+            Err(ProtectError::new(pointer, current))
+        }
+    }
+
+    /// Try protecting `pointer` obtained from `src`. If not, returns an error with the current
+    /// value.
+    ///
+    /// If "`src` still pointing to `pointer`" implies that `pointer` is not retired, then `Ok(())`
+    /// means that this guard is validated.
+    fn try_protect<T>(&self, pointer: *mut T, src: &AtomicPtr<T>) -> Result<(), ProtectError<T>> {
+        self.set(pointer);
+        self.validate(pointer, src).inspect_err(|_| self.clear())
+    }
+
+    /// Get a protected pointer from `src`.
+    ///
+    /// See `try_protect()`.
+    #[must_use = "protecting a pointer without using it is a no-op"]
+    fn protect<T>(&self, src: &AtomicPtr<T>) -> *mut T {
+        let mut pointer = src.load(Ordering::Relaxed);
+        let mut backoff = Backoff::new();
+        while let Err(err) = self.try_protect(pointer, src) {
+            pointer = err.observed();
+            backoff.snooze();
+        }
+        pointer
+    }
+}
+
+/// A memory reclamation scheme.
+///
+/// # Safety
+///
+/// A pointer that is retired must not be freed while a guard of the reclaimer protects it, i.e.
+/// after it is validated by the guard and until the guard is cleared or dropped.
+pub unsafe trait Reclaimer {
+    /// The guard protecting pointers from being freed.
+    type Guard<'r>: Protect
+    where
+        Self: 'r;
+
+    /// Creates a new guard.
+    fn guard(&self) -> Self::Guard<'_>;
+
+    /// Allocates `value`. Schemes that tag objects (e.g. with their birth era) override this.
+    fn alloc<T>(&self, value: T) -> *mut T {
+        Box::into_raw(Box::new(value))
+    }
+
+    /// Frees a pointer immediately.
+    ///
+    /// # Safety
+    ///
+    /// * `pointer` must be allocated by `alloc` of this reclaimer.
+    /// * Subsumes the safety requirements of [`Box::from_raw`].
+    unsafe fn dealloc<T>(&self, pointer: *mut T) {
+        drop(unsafe { Box::from_raw(pointer) })
+    }
+
+    /// Retires a pointer, which will be freed once it is no longer protected.
+    ///
+    /// # Safety
+    ///
+    /// * `pointer` must be allocated by `alloc` of this reclaimer.
+    /// * `pointer` must be removed from shared memory before calling this function, and must be
+    ///   valid.
+    /// * The same `pointer` should only be retired once.
+    /// * `pointer` may be freed by any thread, so it must be safe to drop it there (e.g.
+    ///   `T: Send`).
+    unsafe fn retire<T>(&self, pointer: *mut T);
+
+    /// Frees the retired pointers that are no longer protected, if possible.
+    fn collect(&self);
+}
+
+impl Protect for Shield<'_> {
+    fn set<T>(&self, pointer: *mut T) {
+        let _ = Shield::set(self, pointer);
+    }
+
+    fn clear(&self) {
+        Shield::clear(self)
+    }
+}
+
+unsafe impl Reclaimer for Domain {
+    type Guard<'r> = Shield<'r>;
+
+    fn guard(&self) -> Shield<'_> {
+        Shield::new(self.hazards())
+    }
+
+    unsafe fn retire<T>(&self, pointer: *mut T) {
+        unsafe { Domain::retire(self, pointer) }
+    }
+
+    fn collect(&self) {
+        Domain::collect(self)
+    }
+}
+
+/// A pointer owned until it is dropped, when it is retired to `reclaimer`, so that early returns
+/// and unwinding after unlinking a node do not leak it.
+///
+/// ```
+/// use hazard::{Domain, RetireOnDrop};
+///
+/// let domain = Domain::new();
+/// let pointer = Box::into_raw(Box::new(1));
+/// // `pointer` is unlinked from shared memory here.
+/// let node = unsafe { RetireOnDrop::new(&domain, pointer) };
+/// assert_eq!(node.as_ptr(), pointer);
+/// ```
+#[derive(Debug)]
+pub struct RetireOnDrop<'r, T, R: Reclaimer + ?Sized = Domain> {
+    reclaimer: &'r R,
+    pointer: *mut T,
+}
+
+impl<'r, T, R: Reclaimer + ?Sized> RetireOnDrop<'r, T, R> {
+    /// Takes `pointer`, to retire it to `reclaimer` when dropped.
+    ///
+    /// # Safety
+    ///
+    /// `pointer` must satisfy the requirements of `Reclaimer::retire` by the time this is dropped,
+    /// unless it is taken back with `into_inner`.
+    pub unsafe fn new(reclaimer: &'r R, pointer: *mut T) -> Self {
+        Self { reclaimer, pointer }
+    }
+
+    /// Returns the pointer.
+    pub fn as_ptr(&self) -> *mut T {
+        self.pointer
+    }
+
+    /// Returns the pointer without retiring it, e.g. when it turns out to be still linked.
+    pub fn into_inner(self) -> *mut T {
+        let pointer = self.pointer;
+        mem::forget(self);
+        pointer
+    }
+}
+
+impl<T, R: Reclaimer + ?Sized> Drop for RetireOnDrop<'_, T, R> {
+    fn drop(&mut self) {
+        unsafe { self.reclaimer.retire(self.pointer) };
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+    use super::RetireOnDrop;
+    use crate::Domain;
+    use crate::test::common::Tester;
+
+    // the pointer is retired when unwinding, but not after `into_inner`.
+    #[test]
+    fn retire_on_unwind() {
+        let freed = Arc::new(AtomicUsize::new(0));
+        let domain = Domain::new();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let pointer = Box::into_raw(Box::new(Tester(freed.clone())));
+            let _node = unsafe { RetireOnDrop::new(&domain, pointer) };
+            panic!("unlinked node dropped on unwind");
+        }));
+        assert!(result.is_err());
+        domain.collect();
+        assert_eq!(freed.load(Relaxed), 1);
+
+        let pointer = Box::into_raw(Box::new(Tester(freed.clone())));
+        let node = unsafe { RetireOnDrop::new(&domain, pointer) };
+        assert_eq!(node.into_inner(), pointer);
+        domain.collect();
+        assert_eq!(freed.load(Relaxed), 1);
+        drop(unsafe { Box::from_raw(pointer) });
+    }
+}
@@ -1,34 +1,162 @@
+use core::fmt::Write;
 use core::marker::PhantomData;
+use core::ops::Deref;
+use core::ptr;
 #[cfg(not(feature = "check-loom"))]
-use core::sync::atomic::{Ordering, fence};
+use core::sync::atomic::AtomicBool;
+#[cfg(all(feature = "double-scan", not(feature = "check-loom")))]
+use core::sync::atomic::fence;
+#[cfg(not(feature = "check-loom"))]
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "check-loom")]
-use loom::sync::atomic::{Ordering, fence};
+use loom::sync::Arc;
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicBool;
+#[cfg(all(feature = "double-scan", feature = "check-loom"))]
+use loom::sync::atomic::fence;
+
+#[cfg(feature = "global")]
+use super::HAZARDS;
+#[cfg(feature = "asan")]
+use super::asan;
+use super::backoff::Backoff;
+use super::bloom::BloomFilter;
+#[cfg(feature = "fault-injection")]
+use super::fault;
+#[cfg(any(test, feature = "test-hooks"))]
+use super::hooks::{self, HookPoint};
+use super::incremental::IncrementalDrop;
+use super::ordering::Ordering;
+use super::retired_list::RetiredList;
+#[cfg(feature = "simd")]
+use super::simd;
+use super::table::HazardTable;
+#[cfg(feature = "valgrind")]
+use super::valgrind;
+use super::{Domain, DomainEvent, DropPolicy, HazardBag, StallPolicy};
+
+/// A retired pointer with the function freeing it.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Retired {
+    /// The machine representation of the pointer, which is compared with the hazards.
+    pub(crate) pointer: *mut (),
+    /// `free::<T>` where `T` is the type of the object, or a custom deleter.
+    deleter: unsafe fn(*mut (), *const ()),
+    /// Passed to `deleter`, e.g. the slab that the pointer is allocated from.
+    context: *const (),
+    /// Frees a run of entries with the same `deleter`. `free_run::<T>` with `deleter` statically
+    /// known for the pointers retired as `Box<T>`, e.g. the nodes of the crate's collections, and
+    /// `free_each` otherwise.
+    free_run: unsafe fn(&Domain, &[Retired]),
+    /// The size of the object, or 0 if it is unknown to a custom deleter.
+    pub(crate) size: usize,
+    /// The epoch of the domain's quiescent-state-based reclamation when it was retired.
+    pub(crate) epoch: usize,
+    /// When the pointer was retired.
+    #[cfg(feature = "stats-histogram")]
+    pub(crate) retired_at: Instant,
+}
+
+impl Retired {
+    /// Creates an entry freeing `pointer` as a `Box<T>`.
+    pub(crate) fn new<T>(pointer: *mut T) -> Self {
+        Self {
+            size: size_of::<T>(),
+            free_run: free_run::<T>,
+            ..Self::with_deleter(pointer.cast(), free::<T>, ptr::null())
+        }
+    }
+
+    /// Creates an entry freeing `pointer` as a `Box<T>`, and then marking `handle` as reclaimed.
+    pub(crate) fn notify<T>(pointer: *mut T, handle: &ReclaimHandle) -> Self {
+        let flag = Arc::into_raw(handle.reclaimed.clone());
+        Self {
+            size: size_of::<T>(),
+            ..Self::with_deleter(pointer.cast(), free_notify::<T>, flag.cast())
+        }
+    }
+
+    /// Creates an entry passing `pointer` and `context` to `deleter`.
+    pub(crate) fn with_deleter(
+        pointer: *mut (),
+        deleter: unsafe fn(*mut (), *const ()),
+        context: *const (),
+    ) -> Self {
+        Self {
+            pointer,
+            deleter,
+            context,
+            free_run: free_each,
+            size: 0,
+            epoch: 0,
+            #[cfg(feature = "stats-histogram")]
+            retired_at: Instant::now(),
+        }
+    }
+
+    /// Frees the pointer.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must no longer be protected, and this must be called only once per retire.
+    pub(crate) unsafe fn free(self) {
+        unsafe { (self.deleter)(self.pointer, self.context) }
+    }
+}
+
+/// A handle telling whether a retired object is reclaimed, e.g. to account memory precisely or to
+/// assert reclamation in tests. See `RetiredSet::retire_notify`.
+#[derive(Debug, Clone)]
+pub struct ReclaimHandle {
+    reclaimed: Arc<AtomicBool>,
+}
 
-use super::{HAZARDS, HazardBag};
+impl ReclaimHandle {
+    pub(crate) fn new() -> Self {
+        Self {
+            reclaimed: Arc::new(AtomicBool::new(false)),
+        }
+    }
 
-type Retired = (*mut (), unsafe fn(*mut ()));
+    /// Returns `true` if the object is reclaimed. Its destructor has finished running by then.
+    pub fn is_reclaimed(&self) -> bool {
+        self.reclaimed.load(Ordering::Acquire)
+    }
+}
 
 /// Thread-local list of retired pointers.
 #[derive(Debug)]
-pub struct RetiredSet<'s> {
-    hazards: &'s HazardBag,
-    /// The first element of the pair is the machine representation of the pointer and the second
-    /// is the function pointer to `free::<T>` where `T` is the type of the object.
-    inner: Vec<Retired>,
+pub struct RetiredSet<D: Deref<Target = Domain> = &'static Domain> {
+    domain: D,
+    inner: RetiredList,
+    /// Hazards found by the last `collect`, kept to reuse its allocation.
+    hazards: HazardTable,
+    /// The number of times the threshold is exceeded since the last `collect`.
+    exceeded: usize,
+    /// The length at which the threshold is exceeded next, if larger than the threshold.
+    trigger: usize,
+    /// Overrides `DomainConfig::drop_policy`.
+    drop_policy: Option<DropPolicy>,
+    /// The max number of pointers freed by a `collect` triggered by `retire`. See
+    /// `set_collect_budget`.
+    collect_budget: Option<usize>,
     _marker: PhantomData<*const ()>, // !Send + !Sync
 }
 
-impl<'s> RetiredSet<'s> {
-    /// The max length of retired pointer list. `collect` is triggered when `THRESHOLD` pointers
-    /// are retired.
-    const THRESHOLD: usize = 64;
-
-    /// Create a new retired pointer list protected by the given `HazardBag`.
-    pub fn new(hazards: &'s HazardBag) -> Self {
+impl<D: Deref<Target = Domain>> RetiredSet<D> {
+    /// Create a new retired pointer list protected by the given `Domain`, either borrowed (e.g.
+    /// `&Domain`) or owned (e.g. `DomainHandle`).
+    pub fn new(domain: D) -> Self {
         Self {
-            hazards,
-            inner: Vec::new(),
+            inner: RetiredList::new(domain.hazards().allocator()),
+            domain,
+            hazards: HazardTable::new(),
+            exceeded: 0,
+            trigger: 0,
+            drop_policy: None,
+            collect_budget: None,
             _marker: PhantomData,
         }
     }
@@ -40,49 +168,538 @@ impl<'s> RetiredSet<'s> {
     /// * `pointer` must be removed from shared memory before calling this function, and must be
     ///   valid.
     /// * The same `pointer` should only be retired once.
+    /// * With `DropPolicy::HandOff`, `pointer` must be safe to free in any thread.
     ///
     /// # Note
     ///
-    /// `T: Send` is not required because the retired pointers are not sent to other threads.
+    /// `T: Send` is not required because the retired pointers are not sent to other threads,
+    /// unless they are handed off.
     pub unsafe fn retire<T>(&mut self, pointer: *mut T) {
-        /// Frees a pointer. This function is defined here instead of `collect()` as we know about
-        /// the type of `pointer` only at the time of retiring it.
-        ///
-        /// # Safety
-        ///
-        /// * Subsumes the safety requirements of [`Box::from_raw`]. In particular, one must have
-        ///   unique ownership to `data`.
-        ///
-        /// [`Box::from_raw`]: https://doc.rust-lang.org/std/boxed/struct.Box.html#method.from_raw
-        unsafe fn free<T>(data: *mut ()) {
-            drop(unsafe { Box::from_raw(data.cast::<T>()) })
-        }
-        self.inner.push((pointer.cast(), free::<T>));
-        if self.inner.len() >= Self::THRESHOLD {
-            self.collect();
+        self.push(retired(self.domain.hazards(), pointer));
+    }
+
+    /// Retires a pointer as `retire`, and returns a handle telling whether it is reclaimed.
+    ///
+    /// # Safety
+    ///
+    /// See `retire`.
+    pub unsafe fn retire_notify<T>(&mut self, pointer: *mut T) -> ReclaimHandle {
+        let handle = ReclaimHandle::new();
+        poison(self.domain.hazards(), pointer);
+        self.push(Retired::notify(pointer, &handle));
+        handle
+    }
+
+    /// Retires a pointer to be passed to `deleter` with `context` instead of being freed, e.g. to
+    /// recycle it.
+    ///
+    /// # Safety
+    ///
+    /// * `pointer` must be removed from shared memory before calling this function.
+    /// * The same `pointer` should only be retired once.
+    /// * It must be safe to call `deleter` with `pointer` and `context` once it is no longer
+    ///   protected.
+    pub unsafe fn retire_with(
+        &mut self,
+        pointer: *mut (),
+        deleter: unsafe fn(*mut (), *const ()),
+        context: *const (),
+    ) {
+        self.push(Retired::with_deleter(pointer, deleter, context));
+    }
+
+    /// Retires a pointer to be dropped incrementally by the domain once it is unprotected, e.g.
+    /// a node owning a huge map. See `DomainConfig::drop_budget`.
+    ///
+    /// # Safety
+    ///
+    /// See `retire`. `pointer` is dropped in any thread.
+    pub unsafe fn retire_incremental<T: IncrementalDrop + 'static>(&mut self, pointer: *mut T) {
+        poison(self.domain.hazards(), pointer);
+        self.push(Retired {
+            free_run: free_incremental::<T>,
+            ..Retired::new(pointer)
+        });
+    }
+
+    /// Adds a retired pointer, and collects if the threshold or `max_pending_bytes` is exceeded.
+    fn push(&mut self, mut retired: Retired) {
+        retired.epoch = self.domain.quiescence().epoch();
+        let over = self.domain.add_pending(retired.size);
+        self.inner.push(retired);
+        #[cfg(any(test, feature = "test-hooks"))]
+        hooks::run(HookPoint::Retired);
+        if over {
+            self.exceeded = 0;
+            self.trigger = 0;
+            let max_frees = self.collect_budget.unwrap_or(usize::MAX);
+            return self.domain.relieve(|| {
+                let _ =
+                    reclaim_at_most(&self.domain, &mut self.inner, &mut self.hazards, max_frees);
+            });
+        }
+        let config = self.domain.config();
+        if self.inner.len() >= self.trigger.max(config.threshold) {
+            self.exceeded += 1;
+            if self.exceeded >= config.collect_every {
+                match self.collect_budget {
+                    Some(max_frees) => {
+                        let _ = self.collect_budgeted(max_frees);
+                    }
+                    None => self.collect(),
+                }
+            } else {
+                self.trigger = self.inner.len() + config.threshold;
+            }
+        }
+    }
+
+    /// Free the pointers that are `retire`d by the current thread or handed off to the domain by
+    /// any thread, and not `protect`ed by any other threads. The hazards are scanned once for both.
+    pub fn collect(&mut self) {
+        self.exceeded = 0;
+        self.trigger = 0;
+        self.domain.collect_with(&mut self.inner, &mut self.hazards);
+    }
+
+    /// Frees at most `max_frees` of the pointers `retire`d to this list that are not `protect`ed,
+    /// and returns the number of pointers freed. The others stay in the list for the next
+    /// collection. Unlike `collect`, the pointers handed off to the domain are left to other
+    /// threads, so that the pause of a latency-sensitive thread is bounded.
+    ///
+    /// ```
+    /// use hazard::{Domain, RetiredSet};
+    ///
+    /// let domain = Domain::new();
+    /// let mut retired = RetiredSet::new(&domain);
+    /// for i in 0..3 {
+    ///     unsafe { retired.retire(Box::into_raw(Box::new(i))) };
+    /// }
+    /// assert_eq!(retired.collect_budgeted(2), 2);
+    /// assert_eq!(retired.collect_budgeted(2), 1);
+    /// ```
+    pub fn collect_budgeted(&mut self, max_frees: usize) -> usize {
+        self.exceeded = 0;
+        self.trigger = 0;
+        reclaim_at_most(&self.domain, &mut self.inner, &mut self.hazards, max_frees)
+    }
+
+    /// Makes the collections triggered by `retire` free at most `max_frees` pointers each, as
+    /// `collect_budgeted`. `None` to free all the unprotected pointers as `collect`.
+    pub fn set_collect_budget(&mut self, max_frees: Option<usize>) {
+        self.collect_budget = max_frees;
+    }
+
+    /// Hands the pointers retired so far over to the domain as a sealed batch, which is freed by
+    /// the next `collect` of any thread instead of the current one, e.g. for a dedicated writer
+    /// that retires far more than the other threads.
+    ///
+    /// # Safety
+    ///
+    /// The pointers retired to this list must be safe to free in any thread, e.g. `T: Send` for
+    /// `retire`.
+    pub unsafe fn hand_off(&mut self) {
+        self.exceeded = 0;
+        self.trigger = 0;
+        self.domain.hand_off(&mut self.inner);
+    }
+
+    /// Seals the pointers retired so far into a batch, which may be sent to another thread to be
+    /// adopted or collected there.
+    ///
+    /// # Safety
+    ///
+    /// The pointers retired to this list must be safe to free in any thread, e.g. `T: Send` for
+    /// `retire`.
+    pub unsafe fn seal(&mut self) -> RetiredBatch<D>
+    where
+        D: Clone,
+    {
+        self.exceeded = 0;
+        self.trigger = 0;
+        RetiredBatch {
+            domain: self.domain.clone(),
+            inner: self.inner.take(),
+        }
+    }
+
+    /// Adds the pointers of `batch` to this list, to be freed by its `collect`. Panics if `batch`
+    /// is of another domain.
+    pub fn adopt<B: Deref<Target = Domain>>(&mut self, mut batch: RetiredBatch<B>) {
+        assert!(
+            ptr::eq(&*batch.domain, &*self.domain),
+            "the batch is of another domain"
+        );
+        self.inner.append(&mut batch.inner);
+    }
+
+    /// Sets what dropping this list does with the pointers that are still protected, overriding
+    /// `DomainConfig::drop_policy`.
+    pub fn set_drop_policy(&mut self, drop_policy: DropPolicy) {
+        self.drop_policy = Some(drop_policy);
+    }
+
+    /// Describes the pointers that are still retired after waiting for `waited`, with the slots
+    /// protecting them.
+    fn stall_report(&self, waited: Duration) -> String {
+        let hazards = self.domain.hazards();
+        #[cfg(feature = "owner-info")]
+        let owners = hazards.owners();
+        let mut report = format!(
+            "hazard: {} retired pointers are still protected after {waited:?}",
+            self.inner.len()
+        );
+        for &Retired { pointer: ptr, .. } in &self.inner {
+            let _ = write!(report, "\n  {ptr:p} protected by");
+            for (index, _, _) in hazards
+                .slots()
+                .filter(|&(_, active, hazard)| active && hazard == ptr)
+            {
+                let _ = write!(report, " slot {index}");
+                #[cfg(feature = "owner-info")]
+                if let Some((_, owner)) = owners.iter().find(|(i, _)| *i == index) {
+                    let _ = write!(report, " ({:?} {:?})", owner.id, owner.name);
+                }
+            }
         }
+        report
+    }
+}
+
+/// Retired pointers sealed by `RetiredSet::seal`, which may be sent to another thread.
+///
+/// The pointers are freed once they are adopted by a list and collected, or collected by
+/// `RetiredBatch::collect`. If the batch is dropped before, the remaining pointers are handed off
+/// to the domain, to be freed by the next `collect` of any thread.
+///
+/// ```
+/// use hazard::{Domain, RetiredSet};
+///
+/// let domain = Domain::new();
+/// let mut retired = RetiredSet::new(&domain);
+/// unsafe { retired.retire(Box::into_raw(Box::new(1))) };
+/// let batch = unsafe { retired.seal() };
+/// std::thread::scope(|s| {
+///     let _ = s.spawn(|| RetiredSet::new(&domain).adopt(batch));
+/// });
+/// assert_eq!(domain.pending_objects(), 0);
+/// ```
+#[derive(Debug)]
+pub struct RetiredBatch<D: Deref<Target = Domain> = &'static Domain> {
+    domain: D,
+    inner: RetiredList,
+}
+
+// The pointers are safe to free in any thread, as required by `RetiredSet::seal`.
+unsafe impl<D: Deref<Target = Domain> + Send> Send for RetiredBatch<D> {}
+
+impl<D: Deref<Target = Domain>> RetiredBatch<D> {
+    /// Returns the number of pointers in the batch.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns `true` if the batch has no pointers.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
     }
 
-    /// Free the pointers that are `retire`d by the current thread and not `protect`ed by any other
-    /// threads.
+    /// Frees the pointers in the batch that are not protected, keeping the others.
     pub fn collect(&mut self) {
-        let hazerd_ptrs = self.hazards.all_hazards();
-        let mut can_free = Vec::new();
-        self.inner.retain(|(ptr, deleter)| {
-            if hazerd_ptrs.contains(ptr) {
+        reclaim(&self.domain, &mut self.inner, &mut HazardTable::new());
+    }
+}
+
+impl<D: Deref<Target = Domain>> Drop for RetiredBatch<D> {
+    fn drop(&mut self) {
+        if !self.inner.is_empty() {
+            self.domain.hand_off(&mut self.inner);
+        }
+    }
+}
+
+/// Frees a pointer. This function is instantiated when retiring `data` as we know about the type
+/// of `data` only at that time.
+///
+/// # Safety
+///
+/// * Subsumes the safety requirements of [`Box::from_raw`]. In particular, one must have unique
+///   ownership to `data`.
+///
+/// [`Box::from_raw`]: https://doc.rust-lang.org/std/boxed/struct.Box.html#method.from_raw
+#[inline]
+unsafe fn free<T>(data: *mut (), _: *const ()) {
+    #[cfg(feature = "asan")]
+    asan::unpoison(data, size_of::<T>());
+    #[cfg(feature = "valgrind")]
+    valgrind::make_defined(data, size_of::<T>());
+    drop(unsafe { Box::from_raw(data.cast::<T>()) })
+}
+
+/// Frees `data` as `free::<T>`, and then marks the `ReclaimHandle` flag at `context` as reclaimed.
+unsafe fn free_notify<T>(data: *mut (), context: *const ()) {
+    unsafe { free::<T>(data, ptr::null()) };
+    let reclaimed = unsafe { Arc::from_raw(context.cast::<AtomicBool>()) };
+    reclaimed.store(true, Ordering::Release);
+}
+
+/// Frees a run of `free_all` retired as `Box<T>`, where `free::<T>` is inlined instead of called
+/// through `Retired::deleter` for each pointer.
+unsafe fn free_run<T>(domain: &Domain, run: &[Retired]) {
+    for &retired in run {
+        unsafe { domain.free_with(retired, |retired| free::<T>(retired.pointer, ptr::null())) };
+    }
+}
+
+/// Frees a run of `free_all` retired by `RetiredSet::retire_incremental`, handing the objects over
+/// to `domain` to be dropped incrementally. Outside `free_all`, they are dropped at once by their
+/// deleter `free::<T>`.
+unsafe fn free_incremental<T: IncrementalDrop + 'static>(domain: &Domain, run: &[Retired]) {
+    for &retired in run {
+        let free = |retired: Retired| {
+            #[cfg(feature = "asan")]
+            asan::unpoison(retired.pointer, size_of::<T>());
+            #[cfg(feature = "valgrind")]
+            valgrind::make_defined(retired.pointer, size_of::<T>());
+            domain.drop_incrementally(unsafe { Box::from_raw(retired.pointer.cast::<T>()) });
+        };
+        unsafe { domain.free_with(retired, free) };
+    }
+}
+
+/// Frees a run of `free_all` with the deleter of each entry.
+unsafe fn free_each(domain: &Domain, run: &[Retired]) {
+    for &retired in run {
+        unsafe { domain.free(retired) };
+    }
+}
+
+/// Frees `can_free` retired to `domain`, calling `Retired::free_run` once per run of entries
+/// sharing it. `unprotected` sorts the entries so that the runs are long.
+///
+/// # Safety
+///
+/// See `Retired::free`.
+pub(crate) unsafe fn free_all(domain: &Domain, can_free: &[Retired]) {
+    for run in can_free.chunk_by(|a, b| a.free_run as usize == b.free_run as usize) {
+        unsafe { (run[0].free_run)(domain, run) };
+    }
+}
+
+/// Returns the entry of a retired pointer protected by `hazards`.
+pub(crate) fn retired<T>(hazards: &HazardBag, pointer: *mut T) -> Retired {
+    poison(hazards, pointer);
+    Retired::new(pointer)
+}
+
+/// Poisons a retired pointer unless it is protected by `hazards`, if a memory checker is enabled.
+pub(crate) fn poison<T>(hazards: &HazardBag, pointer: *mut T) {
+    // Nobody may access `pointer` from now on unless it is already protected, so poison it to
+    // catch the accesses that are not protected by a shield. This requires an additional scan of
+    // the hazards.
+    #[cfg(any(feature = "asan", feature = "valgrind"))]
+    if !hazards.all_hazards().contains(&pointer.cast::<()>()) {
+        #[cfg(feature = "asan")]
+        asan::poison(pointer.cast(), size_of::<T>());
+        #[cfg(feature = "valgrind")]
+        valgrind::make_noaccess(pointer.cast(), size_of::<T>());
+    }
+    #[cfg(not(any(feature = "asan", feature = "valgrind")))]
+    let _ = (hazards, pointer);
+}
+
+/// Frees the pointers in `retired` that are not protected by the hazards of `domain`. `table` is
+/// used to store the hazards.
+pub(crate) fn reclaim(domain: &Domain, retired: &mut RetiredList, table: &mut HazardTable) {
+    let _ = reclaim_at_most(domain, retired, table, usize::MAX);
+}
+
+/// Frees at most `max_frees` of the pointers in `retired` that are not protected, as `reclaim`,
+/// and returns the number of pointers freed. The other unprotected pointers are put back.
+pub(crate) fn reclaim_at_most(
+    domain: &Domain,
+    retired: &mut RetiredList,
+    table: &mut HazardTable,
+    max_frees: usize,
+) -> usize {
+    let mut can_free = unprotected(domain, &mut [&mut *retired], table);
+    if can_free.len() > max_frees {
+        retired.extend_from_slice(&can_free[max_frees..]);
+        can_free.truncate(max_frees);
+    }
+    let reclaimed = can_free.len();
+    unsafe { free_all(domain, &can_free) };
+    domain.step_drops();
+    domain.event(DomainEvent::CollectEnd {
+        reclaimed,
+        remaining: retired.len(),
+    });
+    reclaimed
+}
+
+/// Removes the pointers that are not protected by the hazards of `domain` from each list of
+/// `lists`, and returns them sorted by destructor. The hazards are scanned once for all the lists,
+/// reusing `table`.
+pub(crate) fn unprotected(
+    domain: &Domain,
+    lists: &mut [&mut RetiredList],
+    table: &mut HazardTable,
+) -> RetiredList {
+    #[cfg(feature = "fault-injection")]
+    {
+        fault::delay();
+        for retired in lists.iter_mut() {
+            fault::shuffle(retired);
+        }
+    }
+    domain.event(DomainEvent::CollectStart {
+        retired: lists.iter().map(|retired| retired.len()).sum(),
+    });
+    #[cfg(feature = "prometheus")]
+    let start = Instant::now();
+    let hazerd_ptrs = table;
+    hazerd_ptrs.clear();
+    domain
+        .hazards()
+        .for_each_hazard(|hazard| hazerd_ptrs.insert(hazard));
+    #[cfg(any(test, feature = "test-hooks"))]
+    hooks::run(HookPoint::Scanned);
+    #[cfg(feature = "double-scan")]
+    let second = {
+        fence(Ordering::SeqCst);
+        let mut second = HazardTable::new();
+        domain
+            .hazards()
+            .for_each_hazard(|hazard| second.insert(hazard));
+        second
+    };
+    #[cfg(feature = "watchdog")]
+    if let Some(report) = domain
+        .config()
+        .hold_warning
+        .and_then(|limit| domain.hazards().hold_report(limit))
+    {
+        eprintln!("{report}");
+    }
+    // For many hazards, rule out most of the unprotected pointers with a cheaper test first.
+    let filter = (hazerd_ptrs.len() >= BloomFilter::MIN_HAZARDS)
+        .then(|| BloomFilter::new(hazerd_ptrs.len(), hazerd_ptrs.iter()));
+    // For a few hazards, comparing with all of them at once is faster than probing the table.
+    #[cfg(feature = "simd")]
+    let flat =
+        (hazerd_ptrs.len() <= simd::MAX_HAZARDS).then(|| hazerd_ptrs.iter().collect::<Vec<_>>());
+    let grace_period = domain.quiescence().advance();
+    let mut can_free = RetiredList::new(domain.hazards().allocator());
+    for retired in lists.iter_mut() {
+        retired.retain(|retired| {
+            let ptr = retired.pointer;
+            let in_table =
+                || filter.as_ref().is_none_or(|f| f.may_contain(ptr)) && hazerd_ptrs.contains(ptr);
+            #[cfg(not(feature = "simd"))]
+            let protected = in_table();
+            #[cfg(feature = "simd")]
+            let protected = flat
+                .as_ref()
+                .map_or_else(in_table, |flat| simd::contains(flat, ptr));
+            #[cfg(feature = "double-scan")]
+            let protected = protected || missed_by_first_scan(&second, ptr);
+            if grace_period.covers(retired.epoch) || protected {
                 true
             } else {
-                can_free.push((*ptr, *deleter));
+                can_free.push(*retired);
                 false
             }
         });
-        for (ptr, deleter) in can_free {
-            unsafe { deleter(ptr) };
+    }
+    #[cfg(feature = "prometheus")]
+    domain.scanned(start.elapsed());
+    #[cfg(debug_assertions)]
+    assert_unprotected(domain, &can_free, hazerd_ptrs);
+    // Run the same destructors back-to-back.
+    can_free.sort_unstable_by_key(|retired| (retired.free_run as usize, retired.deleter as usize));
+    can_free
+}
+
+#[cfg(feature = "double-scan")]
+/// Returns `true` if the second scan of `double-scan` finds `pointer` protected although the first
+/// did not, and reports it, as a single scan would have freed it.
+fn missed_by_first_scan(second: &HazardTable, pointer: *mut ()) -> bool {
+    let missed = second.contains(pointer);
+    if missed {
+        eprintln!(
+            "hazard: {pointer:p} is protected in the second hazard scan only, so a single scan \
+             would free it; check the orderings between publishing and validating hazards"
+        );
+    }
+    missed
+}
+
+#[cfg(debug_assertions)]
+/// Re-scans the hazards of `domain`, and panics if a pointer in `can_free` is now protected by a
+/// shield that validated it, as the validation must then have failed after the pointer was
+/// retired. `table` is reused to look the pointers up.
+///
+/// A pointer published without validation, e.g. by a reader whose validation is about to fail and
+/// retry, or by a raw `SlotHandle`, is left alone.
+fn assert_unprotected(domain: &Domain, can_free: &[Retired], table: &mut HazardTable) {
+    if can_free.is_empty() {
+        return;
+    }
+    table.clear();
+    for retired in can_free {
+        table.insert(retired.pointer);
+    }
+    domain
+        .hazards()
+        .for_each_checked_hazard(|hazard, validated| {
+            assert!(
+                !validated || !table.contains(hazard),
+                "hazard: {hazard:p} is validated after it was retired and found unprotected, so \
+                 its source still pointed to it after it was retired"
+            );
+        });
+}
+
+#[cfg(feature = "parallel-collect")]
+/// Frees `can_free` retired to `domain` on up to `available_parallelism` threads. Each thread frees
+/// a contiguous chunk, so that the same destructors still run back-to-back.
+pub(crate) fn free_parallel(domain: &Domain, can_free: RetiredList) {
+    /// The least number of pointers freed by each thread, below which spawning costs more.
+    const MIN_CHUNK: usize = 4096;
+
+    let threads = std::thread::available_parallelism()
+        .map_or(1, usize::from)
+        .min(can_free.len() / MIN_CHUNK);
+    if threads <= 1 {
+        unsafe { free_all(domain, &can_free) };
+        return;
+    }
+    let chunk = can_free.len().div_ceil(threads);
+    std::thread::scope(|s| {
+        for batch in can_free.chunks(chunk).map(Batch) {
+            let _ = s.spawn(move || batch.free(domain));
         }
+    });
+}
+
+#[cfg(feature = "parallel-collect")]
+/// A chunk of `free_parallel` freed by another thread.
+struct Batch<'r>(&'r [Retired]);
+
+// Pointers retired to a domain are freed by any thread collecting it, as required by
+// `Domain::retire`.
+#[cfg(feature = "parallel-collect")]
+unsafe impl Send for Batch<'_> {}
+
+#[cfg(feature = "parallel-collect")]
+impl Batch<'_> {
+    fn free(self, domain: &Domain) {
+        unsafe { free_all(domain, self.0) };
     }
 }
 
-impl Default for RetiredSet<'static> {
+#[cfg(feature = "global")]
+impl Default for RetiredSet {
     fn default() -> Self {
         Self::new(&HAZARDS)
     }
@@ -90,14 +707,40 @@ impl Default for RetiredSet<'static> {
 
 // this triggers loom internal bug
 #[cfg(not(feature = "check-loom"))]
-impl Drop for RetiredSet<'_> {
+impl<D: Deref<Target = Domain>> Drop for RetiredSet<D> {
     fn drop(&mut self) {
-        // In a production-quality implementation of hazard pointers, the remaining local retired
-        // pointers will be moved to a global list of retired pointers, which are then reclaimed by
-        // the other threads. For pedagogical purposes, here we simply wait for all retired pointers
-        // are no longer protected.
+        // By default, wait for all retired pointers are no longer protected. If it takes too long,
+        // report the stuck pointers and follow the configured policy.
+        let config = *self.domain.config();
+        match self.drop_policy.unwrap_or(config.drop_policy) {
+            DropPolicy::Wait => {}
+            DropPolicy::HandOff => {
+                self.collect();
+                // # Safety
+                // the pointers are safe to free in any thread with this policy, as required by
+                // `retire`.
+                return unsafe { self.hand_off() };
+            }
+            DropPolicy::Leak => {
+                self.collect();
+                return self.domain.leak(&mut self.inner);
+            }
+        }
+        let start = Instant::now();
+        let mut deadline = config.stall_timeout;
+        let mut backoff = Backoff::parking();
         while !self.inner.is_empty() {
             self.collect();
+            if deadline.is_some_and(|timeout| start.elapsed() >= timeout) {
+                deadline = None;
+                eprintln!("{}", self.stall_report(start.elapsed()));
+                match config.stall_policy {
+                    StallPolicy::Wait => {}
+                    StallPolicy::Leak => return self.domain.leak(&mut self.inner),
+                    StallPolicy::Panic => panic!("hazard: retired pointers are stuck"),
+                }
+            }
+            backoff.snooze();
         }
     }
 }
@@ -106,9 +749,11 @@ impl Drop for RetiredSet<'_> {
 mod tests {
     use std::cell::RefCell;
     use std::collections::HashSet;
+    use std::ptr;
     use std::rc::Rc;
 
-    use super::{HazardBag, RetiredSet};
+    use super::RetiredSet;
+    use crate::Domain;
 
     // retire `THRESHOLD` pointers to trigger collection
     #[test]
@@ -119,14 +764,420 @@ mod tests {
                 let _ = self.0.borrow_mut().insert(self.1);
             }
         }
-        let hazards = HazardBag::new();
-        let mut retires = RetiredSet::new(&hazards);
+        let domain = Domain::builder().threshold(16).build();
+        let mut retires = RetiredSet::new(&domain);
         let freed = Rc::new(RefCell::new(HashSet::new()));
-        for i in 0..RetiredSet::THRESHOLD {
+        for i in 0..16 {
             unsafe { retires.retire(Box::leak(Box::new(Tester(freed.clone(), i)))) };
         }
         let freed = Rc::try_unwrap(freed).unwrap().into_inner();
 
-        assert_eq!(freed, (0..RetiredSet::THRESHOLD).collect())
+        assert_eq!(freed, (0..16).collect())
+    }
+
+    // runs of boxed pointers and of custom deleters interleaved in a collection are all freed.
+    #[test]
+    fn free_runs() {
+        use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+        static DELETED: AtomicUsize = AtomicUsize::new(0);
+        unsafe fn delete(pointer: *mut (), _: *const ()) {
+            drop(unsafe { Box::from_raw(pointer.cast::<u64>()) });
+            let _ = DELETED.fetch_add(1, Relaxed);
+        }
+        let domain = Domain::new();
+        let mut retires = RetiredSet::new(&domain);
+        for i in 0..12 {
+            match i % 3 {
+                0 => unsafe { retires.retire(Box::into_raw(Box::new(i as u8))) },
+                1 => unsafe { retires.retire(Box::into_raw(Box::new([i; 4]))) },
+                _ => unsafe {
+                    retires.retire_with(
+                        Box::into_raw(Box::new(i as u64)).cast(),
+                        delete,
+                        ptr::null(),
+                    )
+                },
+            }
+        }
+        retires.collect();
+        assert_eq!(DELETED.load(Relaxed), 4);
+        assert_eq!(domain.pending_objects(), 0);
+    }
+
+    // a collection scans the hazards once for the local list and the batches handed off.
+    #[test]
+    fn collect_scans_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+        use crate::DomainEvent;
+
+        static SCANS: AtomicUsize = AtomicUsize::new(0);
+        static RETIRED: AtomicUsize = AtomicUsize::new(0);
+        let domain = Domain::new();
+        domain.set_event_hook(|event| {
+            if let DomainEvent::CollectStart { retired } = event {
+                let _ = SCANS.fetch_add(1, Relaxed);
+                RETIRED.store(retired, Relaxed);
+            }
+        });
+        let mut writer = RetiredSet::new(&domain);
+        unsafe {
+            writer.retire(Box::into_raw(Box::new(1)));
+            writer.hand_off();
+        }
+        let mut retires = RetiredSet::new(&domain);
+        unsafe { retires.retire(Box::into_raw(Box::new(2))) };
+        retires.collect();
+        assert_eq!((SCANS.load(Relaxed), RETIRED.load(Relaxed)), (1, 2));
+        assert_eq!(domain.pending_objects(), 0);
+    }
+
+    // with `collect_every`, collection is triggered after retiring multiples of the threshold.
+    #[test]
+    fn collect_every() {
+        let domain = Domain::builder().threshold(4).collect_every(3).build();
+        let mut retires = RetiredSet::new(&domain);
+        for i in 0..12 {
+            unsafe { retires.retire(Box::into_raw(Box::new(i))) };
+            assert_eq!(retires.inner.len(), (i + 1) % 12);
+        }
+    }
+
+    // the handle of a retired pointer tells when it is reclaimed, in a set or in a domain.
+    #[test]
+    fn retire_notify() {
+        use std::sync::atomic::AtomicPtr;
+
+        use crate::Shield;
+
+        let domain = Domain::new();
+        let mut retires = RetiredSet::new(&domain);
+        let pointer = Box::into_raw(Box::new(1));
+        let shield = Shield::new(domain.hazards());
+        let _ = shield.protect(&AtomicPtr::new(pointer));
+        let handle = unsafe { retires.retire_notify(pointer) };
+        retires.collect();
+        assert!(!handle.is_reclaimed());
+        drop(shield);
+        retires.collect();
+        assert!(handle.is_reclaimed());
+
+        let handle = unsafe { domain.retire_notify(Box::into_raw(Box::new(2))) };
+        assert!(!handle.is_reclaimed());
+        domain.collect();
+        assert!(handle.is_reclaimed());
+    }
+
+    // a batch handed off by a writer is freed by the collect of another thread.
+    #[test]
+    fn hand_off() {
+        use std::thread;
+
+        let domain = Domain::builder().threshold(usize::MAX).build();
+        let handles = thread::scope(|s| {
+            s.spawn(|| {
+                let mut writer = RetiredSet::new(&domain);
+                let handles = (0..4)
+                    .map(|i| unsafe { writer.retire_notify(Box::into_raw(Box::new(i))) })
+                    .collect::<Vec<_>>();
+                unsafe { writer.hand_off() };
+                handles
+            })
+            .join()
+            .unwrap()
+        });
+        assert!(handles.iter().all(|handle| !handle.is_reclaimed()));
+        RetiredSet::new(&domain).collect();
+        assert!(handles.iter().all(|handle| handle.is_reclaimed()));
+    }
+
+    // a sealed batch is adopted by a list of another thread, or handed off to the domain when it
+    // is dropped.
+    #[test]
+    fn seal_adopt() {
+        use std::thread;
+
+        let domain = Domain::builder().threshold(usize::MAX).build();
+        let mut retires = RetiredSet::new(&domain);
+        let handle = unsafe { retires.retire_notify(Box::into_raw(Box::new(1))) };
+        let batch = unsafe { retires.seal() };
+        assert_eq!((batch.len(), retires.inner.len()), (1, 0));
+        thread::scope(|s| {
+            let _ = s.spawn(|| {
+                let mut adopter = RetiredSet::new(&domain);
+                adopter.adopt(batch);
+                adopter.collect();
+            });
+        });
+        assert!(handle.is_reclaimed());
+
+        let handle = unsafe { retires.retire_notify(Box::into_raw(Box::new(2))) };
+        let batch = unsafe { retires.seal() };
+        thread::scope(|s| drop(s.spawn(|| drop(batch))));
+        assert!(!handle.is_reclaimed());
+        domain.collect();
+        assert!(handle.is_reclaimed());
+    }
+
+    // pointers of the same type are freed together.
+    #[test]
+    fn collect_grouped_by_type() {
+        struct A(Rc<RefCell<Vec<char>>>);
+        impl Drop for A {
+            fn drop(&mut self) {
+                self.0.borrow_mut().push('a');
+            }
+        }
+        struct B(Rc<RefCell<Vec<char>>>);
+        impl Drop for B {
+            fn drop(&mut self) {
+                self.0.borrow_mut().push('b');
+            }
+        }
+        let domain = Domain::builder().threshold(usize::MAX).build();
+        let mut retires = RetiredSet::new(&domain);
+        let freed = Rc::new(RefCell::new(Vec::new()));
+        for _ in 0..8 {
+            unsafe { retires.retire(Box::into_raw(Box::new(A(freed.clone())))) };
+            unsafe { retires.retire(Box::into_raw(Box::new(B(freed.clone())))) };
+        }
+        retires.collect();
+        let freed = freed.borrow();
+        assert_eq!(freed.len(), 16);
+        assert_eq!(freed.windows(2).filter(|w| w[0] != w[1]).count(), 1);
+    }
+
+    // with many hazards, protected pointers are still kept and the others freed.
+    #[test]
+    fn collect_many_hazards() {
+        use core::sync::atomic::AtomicPtr;
+
+        use crate::Shield;
+
+        let domain = Domain::builder().threshold(usize::MAX).build();
+        let mut retires = RetiredSet::new(&domain);
+        let pointers = (0..128)
+            .map(|i| Box::into_raw(Box::new(i)))
+            .collect::<Vec<_>>();
+        let shields = pointers
+            .iter()
+            .step_by(2)
+            .map(|&p| {
+                let shield = Shield::new(domain.hazards());
+                let _ = shield.protect(&AtomicPtr::new(p));
+                shield
+            })
+            .collect::<Vec<_>>();
+        for &p in &pointers {
+            unsafe { retires.retire(p) };
+        }
+        retires.collect();
+        assert_eq!(retires.inner.len(), shields.len());
+
+        drop(shields);
+        retires.collect();
+        assert!(retires.inner.is_empty());
+    }
+
+    // with `DropPolicy::HandOff`, the pointers still protected when the list is dropped are freed
+    // by the domain later.
+    #[test]
+    fn drop_hand_off() {
+        use core::sync::atomic::AtomicPtr;
+
+        use crate::{DropPolicy, Shield};
+
+        let domain = Domain::builder().drop_policy(DropPolicy::HandOff).build();
+        let mut retires = RetiredSet::new(&domain);
+        let pointer = Box::into_raw(Box::new(0usize));
+        let shield = Shield::new(domain.hazards());
+        let _ = shield.protect(&AtomicPtr::new(pointer));
+        let handle = unsafe { retires.retire_notify(pointer) };
+        drop(retires);
+        assert_eq!(domain.pending_objects(), 1);
+        drop(shield);
+        domain.collect();
+        assert!(handle.is_reclaimed());
+
+        let mut retires = RetiredSet::new(&domain);
+        retires.set_drop_policy(DropPolicy::Wait);
+        unsafe { retires.retire(Box::into_raw(Box::new(1usize))) };
+        drop(retires);
+        assert_eq!(domain.pending_objects(), 0);
+    }
+
+    // dropping a list with `DropPolicy::Leak` leaks the protected pointers, which are no longer
+    // pending.
+    #[test]
+    fn drop_leak() {
+        use core::sync::atomic::AtomicPtr;
+
+        use crate::{DropPolicy, Shield};
+
+        let domain = Domain::builder().drop_policy(DropPolicy::Leak).build();
+        let mut retires = RetiredSet::new(&domain);
+        let pointer = Box::into_raw(Box::new(0usize));
+        let shield = Shield::new(domain.hazards());
+        let _ = shield.protect(&AtomicPtr::new(pointer));
+        unsafe { retires.retire(pointer) };
+        unsafe { retires.retire(Box::into_raw(Box::new(1usize))) };
+        assert_eq!(domain.pending_objects(), 2);
+        drop(retires);
+        assert_eq!((domain.pending_objects(), domain.pending_bytes()), (0, 0));
+        drop(shield);
+        drop(unsafe { Box::from_raw(pointer) });
+    }
+
+    // a collection triggered by `retire` frees at most the budget of the list.
+    #[test]
+    fn collect_budget() {
+        let domain = Domain::builder().threshold(4).build();
+        let mut retires = RetiredSet::new(&domain);
+        retires.set_collect_budget(Some(1));
+        for i in 0..5 {
+            unsafe { retires.retire(Box::into_raw(Box::new(i))) };
+        }
+        assert_eq!(domain.pending_objects(), 3);
+        assert_eq!(retires.collect_budgeted(usize::MAX), 3);
+        assert_eq!(domain.pending_objects(), 0);
+    }
+
+    // dropping a list whose pointers stay protected reports them and follows the policy.
+    #[test]
+    fn stall_leak() {
+        use core::sync::atomic::AtomicPtr;
+        use core::time::Duration;
+
+        use crate::{Shield, StallPolicy};
+
+        let domain = Domain::builder()
+            .threshold(usize::MAX)
+            .stall_timeout(Some(Duration::from_millis(10)))
+            .stall_policy(StallPolicy::Leak)
+            .build();
+        let mut retires = RetiredSet::new(&domain);
+        let pointer = Box::into_raw(Box::new(0usize));
+        let shield = Shield::new(domain.hazards());
+        let _ = shield.protect(&AtomicPtr::new(pointer));
+        unsafe { retires.retire(pointer) };
+        let report = retires.stall_report(Duration::ZERO);
+        assert!(report.contains(&format!("{pointer:p} protected by slot")));
+
+        drop(retires);
+        assert_eq!((domain.pending_objects(), domain.pending_bytes()), (0, 0));
+        drop(shield);
+        drop(unsafe { Box::from_raw(pointer) });
+    }
+
+    // retired pointers are poisoned until freed, unless they are protected.
+    #[cfg(feature = "asan")]
+    #[test]
+    fn retire_poison() {
+        use core::sync::atomic::AtomicPtr;
+
+        use crate::{Shield, asan};
+
+        let domain = Domain::builder().threshold(usize::MAX).build();
+        let mut retires = RetiredSet::new(&domain);
+        let unprotected = Box::into_raw(Box::new(0usize));
+        let protected = Box::into_raw(Box::new(0usize));
+        let shield = Shield::new(domain.hazards());
+        let _ = shield.protect(&AtomicPtr::new(protected));
+        unsafe { retires.retire(unprotected) };
+        unsafe { retires.retire(protected) };
+        assert!(asan::is_poisoned(unprotected.cast()));
+        assert!(!asan::is_poisoned(protected.cast()));
+
+        drop(shield);
+        retires.collect();
+        assert!(retires.inner.is_empty());
+    }
+
+    // client requests are no-ops when not running under valgrind.
+    #[cfg(feature = "valgrind")]
+    #[test]
+    fn retire_valgrind() {
+        use crate::valgrind;
+
+        if valgrind::running_on_valgrind() != 0 {
+            return;
+        }
+        let domain = Domain::builder().threshold(usize::MAX).build();
+        let mut retires = RetiredSet::new(&domain);
+        let pointer = Box::into_raw(Box::new(123usize));
+        unsafe { retires.retire(pointer) };
+        assert_eq!(unsafe { *pointer }, 123);
+        retires.collect();
+        assert!(retires.inner.is_empty());
+    }
+
+    // in debug builds, a pointer validated after the scan panics.
+    #[cfg(all(debug_assertions, not(feature = "double-scan")))]
+    #[test]
+    #[should_panic(expected = "validated after it was retired")]
+    fn validated_after_scan() {
+        use crate::Shield;
+        use crate::hooks::{HookPoint, set_hook};
+
+        let domain: &'static Domain = Box::leak(Box::new(Domain::new()));
+        let pointer = Box::into_raw(Box::new(0));
+        let shield = Shield::new(domain.hazards());
+        let address = pointer as usize;
+        let _ = set_hook(move |point| {
+            if point == HookPoint::Scanned {
+                let src = std::sync::atomic::AtomicPtr::new(address as *mut i32);
+                let _ = shield.set(address as *mut i32).validate(&src).unwrap();
+            }
+        });
+        unsafe { domain.retire(pointer) };
+        domain.collect();
+    }
+
+    // in debug builds, a pointer published without validation after the scan is freed silently,
+    // as its reader is about to fail the validation.
+    #[cfg(all(debug_assertions, not(feature = "double-scan")))]
+    #[test]
+    fn protected_after_scan() {
+        use crate::Shield;
+        use crate::hooks::{HookPoint, set_hook, take_hook};
+
+        let domain: &'static Domain = Box::leak(Box::new(Domain::new()));
+        let pointer = Box::into_raw(Box::new(0));
+        let shield = Shield::new(domain.hazards());
+        let address = pointer as usize;
+        let _ = set_hook(move |point| {
+            if point == HookPoint::Scanned {
+                let _ = shield.set(address as *mut i32);
+            }
+        });
+        unsafe { domain.retire(pointer) };
+        domain.collect();
+        drop(take_hook());
+        assert_eq!(domain.pending_objects(), 0);
+    }
+
+    // a pointer protected between the two scans of `double-scan` is kept.
+    #[cfg(feature = "double-scan")]
+    #[test]
+    fn double_scan() {
+        use crate::Shield;
+        use crate::hooks::{HookPoint, set_hook, take_hook};
+
+        let domain: &'static Domain = Box::leak(Box::new(Domain::new()));
+        let pointer = Box::into_raw(Box::new(0));
+        let shield = Shield::new(domain.hazards());
+        let address = pointer as usize;
+        let _ = set_hook(move |point| {
+            if point == HookPoint::Scanned {
+                let _ = shield.set(address as *mut i32);
+            }
+        });
+        unsafe { domain.retire(pointer) };
+        domain.collect();
+        assert_eq!(domain.pending_objects(), 1);
+        drop(take_hook());
+        domain.collect();
+        assert_eq!(domain.pending_objects(), 0);
     }
 }
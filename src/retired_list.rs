@@ -0,0 +1,231 @@
+//! Growable lists of retired pointers allocated by a `SlotAllocator`.
+
+use core::alloc::Layout;
+use core::fmt;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+use core::slice;
+use std::alloc;
+
+use super::SlotAllocator;
+use super::retire::Retired;
+
+/// A growable array of retired pointers like `Vec<Retired>`, whose storage is allocated by the
+/// `SlotAllocator` of the domain instead of the global allocator.
+pub(crate) struct RetiredList {
+    // `pointer[..len]` are initialized, and `pointer` is dangling if `capacity` is 0.
+    pointer: NonNull<Retired>,
+    len: usize,
+    capacity: usize,
+    allocator: SlotAllocator,
+}
+
+impl RetiredList {
+    /// The capacity of the first allocation.
+    const MIN_CAPACITY: usize = 8;
+
+    /// Creates an empty list allocated by `allocator`. Does not allocate until the first push.
+    pub(crate) const fn new(allocator: SlotAllocator) -> Self {
+        Self {
+            pointer: NonNull::dangling(),
+            len: 0,
+            capacity: 0,
+            allocator,
+        }
+    }
+
+    /// Appends `retired` to the list.
+    pub(crate) fn push(&mut self, retired: Retired) {
+        self.reserve(1);
+        unsafe { self.pointer.as_ptr().add(self.len).write(retired) };
+        self.len += 1;
+    }
+
+    /// Appends the pointers in `retired` to the list.
+    pub(crate) fn extend_from_slice(&mut self, retired: &[Retired]) {
+        self.reserve(retired.len());
+        unsafe {
+            ptr::copy_nonoverlapping(
+                retired.as_ptr(),
+                self.pointer.as_ptr().add(self.len),
+                retired.len(),
+            )
+        };
+        self.len += retired.len();
+    }
+
+    /// Moves the pointers of `other` to the end of the list, leaving `other` empty.
+    pub(crate) fn append(&mut self, other: &mut Self) {
+        self.extend_from_slice(other);
+        other.clear();
+    }
+
+    /// Keeps the first `len` pointers of the list, keeping the allocated capacity.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    /// Removes all the pointers from the list, keeping the allocated capacity.
+    pub(crate) fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Keeps only the pointers for which `f` returns `true`, in order. If `f` panics, the
+    /// pointers it has not seen yet are kept.
+    pub(crate) fn retain(&mut self, mut f: impl FnMut(&Retired) -> bool) {
+        /// Moves the pointers not seen yet after the kept ones, also when `f` panics.
+        struct Guard<'l> {
+            list: &'l mut RetiredList,
+            seen: usize,
+            kept: usize,
+        }
+
+        impl Drop for Guard<'_> {
+            fn drop(&mut self) {
+                let rest = self.list.len - self.seen;
+                let pointer = self.list.pointer.as_ptr();
+                unsafe { ptr::copy(pointer.add(self.seen), pointer.add(self.kept), rest) };
+                self.list.len = self.kept + rest;
+            }
+        }
+
+        let len = self.len;
+        let mut guard = Guard {
+            list: self,
+            seen: 0,
+            kept: 0,
+        };
+        while guard.seen < len {
+            let retired = guard.list[guard.seen];
+            let keep = f(&retired);
+            guard.seen += 1;
+            if keep {
+                guard.list[guard.kept] = retired;
+                guard.kept += 1;
+            }
+        }
+    }
+
+    /// Takes the pointers out of the list, leaving it empty with the same allocator.
+    pub(crate) fn take(&mut self) -> Self {
+        mem::replace(self, Self::new(self.allocator))
+    }
+
+    /// Makes room for `additional` more pointers, growing the allocation at least twice.
+    fn reserve(&mut self, additional: usize) {
+        let needed = self.len.checked_add(additional).expect("capacity overflow");
+        if needed <= self.capacity {
+            return;
+        }
+        let capacity = needed.max(self.capacity * 2).max(Self::MIN_CAPACITY);
+        let layout = Self::layout(capacity);
+        let Some(pointer) = NonNull::new(unsafe { (self.allocator.alloc)(layout) }) else {
+            alloc::handle_alloc_error(layout)
+        };
+        let pointer = pointer.cast::<Retired>();
+        unsafe { ptr::copy_nonoverlapping(self.pointer.as_ptr(), pointer.as_ptr(), self.len) };
+        self.free_storage();
+        self.pointer = pointer;
+        self.capacity = capacity;
+    }
+
+    /// Frees the allocation, if any.
+    fn free_storage(&mut self) {
+        if self.capacity != 0 {
+            let layout = Self::layout(self.capacity);
+            unsafe { (self.allocator.dealloc)(self.pointer.as_ptr().cast(), layout) };
+        }
+    }
+
+    /// Returns the layout of the allocation of `capacity` pointers.
+    fn layout(capacity: usize) -> Layout {
+        Layout::array::<Retired>(capacity).expect("capacity overflow")
+    }
+}
+
+impl Deref for RetiredList {
+    type Target = [Retired];
+
+    fn deref(&self) -> &[Retired] {
+        unsafe { slice::from_raw_parts(self.pointer.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for RetiredList {
+    fn deref_mut(&mut self) -> &mut [Retired] {
+        unsafe { slice::from_raw_parts_mut(self.pointer.as_ptr(), self.len) }
+    }
+}
+
+impl<'l> IntoIterator for &'l RetiredList {
+    type Item = &'l Retired;
+    type IntoIter = slice::Iter<'l, Retired>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Drop for RetiredList {
+    fn drop(&mut self) {
+        self.free_storage();
+    }
+}
+
+impl fmt::Debug for RetiredList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use core::ptr;
+
+    use super::RetiredList;
+    use crate::SlotAllocator;
+    use crate::retire::Retired;
+
+    fn retired(index: usize) -> Retired {
+        unsafe fn forget(_: *mut (), _: *const ()) {}
+        Retired::with_deleter(ptr::without_provenance_mut(index), forget, ptr::null())
+    }
+
+    fn pointers(list: &RetiredList) -> Vec<usize> {
+        list.iter().map(|retired| retired.pointer.addr()).collect()
+    }
+
+    // the list grows past its first allocation, and appending empties the other list.
+    #[test]
+    fn push_append() {
+        let mut list = RetiredList::new(SlotAllocator::GLOBAL);
+        let mut other = RetiredList::new(SlotAllocator::GLOBAL);
+        for index in 1..=20 {
+            list.push(retired(index));
+            other.push(retired(index + 20));
+        }
+        list.append(&mut other);
+        assert!(other.is_empty());
+        assert_eq!(pointers(&list), (1..=40).collect::<Vec<_>>());
+    }
+
+    // `retain` keeps the order of the kept pointers, and keeps the unseen ones if it panics.
+    #[test]
+    fn retain() {
+        let mut list = RetiredList::new(SlotAllocator::GLOBAL);
+        for index in 1..=10 {
+            list.push(retired(index));
+        }
+        list.retain(|retired| retired.pointer.addr() % 2 == 0);
+        assert_eq!(pointers(&list), [2, 4, 6, 8, 10]);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            list.retain(|retired| {
+                assert_ne!(retired.pointer.addr(), 6);
+                false
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(pointers(&list), [6, 8, 10]);
+    }
+}
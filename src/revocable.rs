@@ -0,0 +1,137 @@
+//! Revocable references, in the style of `Weak` without reference counts.
+
+use core::fmt;
+use core::ops::Deref;
+use core::ptr::{self, NonNull};
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::AtomicPtr;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicPtr;
+
+use super::ordering::Ordering;
+use super::{Domain, Shield};
+
+/// A cell holding `T` until it is revoked, e.g. a registered observer or callback.
+///
+/// Readers `upgrade` to a guard protecting the value if it is not revoked yet. Revoking retires
+/// the value to `domain`, so it is freed once no guard protects it. As that is at a later
+/// collection of `domain`, possibly after anything the value borrows, `T` must be `'static`.
+///
+/// ```
+/// use hazard::{Domain, Revocable};
+///
+/// let domain = Domain::new();
+/// let cell = Revocable::new(&domain, String::from("observer"));
+/// let guard = cell.upgrade().unwrap();
+/// assert!(cell.revoke());
+/// assert_eq!(*guard, "observer");
+/// assert!(cell.upgrade().is_none());
+/// ```
+pub struct Revocable<'d, T: Send + Sync + 'static> {
+    domain: &'d Domain,
+    pointer: AtomicPtr<T>,
+}
+
+/// A guard protecting the value of a `Revocable` from being freed, even if it is revoked.
+#[must_use = "the value may be freed as soon as the guard is dropped"]
+pub struct RevocableGuard<'d, T> {
+    _shield: Shield<'d>,
+    pointer: NonNull<T>,
+}
+
+unsafe impl<T: Send + Sync + 'static> Send for Revocable<'_, T> {}
+unsafe impl<T: Send + Sync + 'static> Sync for Revocable<'_, T> {}
+
+impl<'d, T: Send + Sync + 'static> Revocable<'d, T> {
+    /// Creates a new cell holding `value`, which is retired to `domain` when revoked.
+    pub fn new(domain: &'d Domain, value: T) -> Self {
+        Self {
+            domain,
+            pointer: AtomicPtr::new(Box::into_raw(Box::new(value))),
+        }
+    }
+
+    /// Returns a guard protecting the value, or `None` if it is already revoked.
+    pub fn upgrade(&self) -> Option<RevocableGuard<'d, T>> {
+        let shield = Shield::new(self.domain.hazards());
+        let pointer = NonNull::new(shield.protect(&self.pointer))?;
+        Some(RevocableGuard {
+            _shield: shield,
+            pointer,
+        })
+    }
+
+    /// Revokes the value, so that later `upgrade`s fail. Returns `false` if it is already revoked.
+    pub fn revoke(&self) -> bool {
+        let pointer = self.pointer.swap(ptr::null_mut(), Ordering::AcqRel);
+        if pointer.is_null() {
+            return false;
+        }
+        // # Safety
+        // `pointer` is unlinked by the swap above, so it is retired only once.
+        unsafe { self.domain.retire(pointer) };
+        true
+    }
+
+    /// Returns `true` if the value is revoked.
+    pub fn is_revoked(&self) -> bool {
+        self.pointer.load(Ordering::Relaxed).is_null()
+    }
+}
+
+impl<T: Send + Sync + 'static> Drop for Revocable<'_, T> {
+    /// Revokes the value, as guards may still protect it.
+    fn drop(&mut self) {
+        let _ = self.revoke();
+    }
+}
+
+impl<T: Send + Sync + fmt::Debug + 'static> fmt::Debug for Revocable<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Revocable").field(&self.upgrade()).finish()
+    }
+}
+
+impl<T> Deref for RevocableGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // # Safety
+        // the value is protected, and it is retired before being freed.
+        unsafe { self.pointer.as_ref() }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RevocableGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RevocableGuard").field(&**self).finish()
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+    use super::Revocable;
+    use crate::Domain;
+    use crate::test::common::Tester;
+
+    // a revoked value is freed only once the guards upgraded before are dropped.
+    #[test]
+    fn revoke_while_upgraded() {
+        let freed = Arc::new(AtomicUsize::new(0));
+        let domain = Domain::new();
+        let cell = Revocable::new(&domain, Tester(freed.clone()));
+        let guard = cell.upgrade().unwrap();
+        assert!(cell.revoke());
+        assert!(!cell.revoke());
+        assert!(cell.is_revoked() && cell.upgrade().is_none());
+        domain.collect();
+        assert_eq!(freed.load(Relaxed), 0);
+        drop(guard);
+        domain.collect();
+        assert_eq!(freed.load(Relaxed), 1);
+    }
+}
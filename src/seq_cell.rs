@@ -0,0 +1,201 @@
+//! Cells of large `Copy` values, read with a seqlock and a hazard-protected fallback.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, fence};
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, fence};
+
+use super::backoff::Backoff;
+use super::ordering::Ordering;
+use super::{Domain, Shield};
+
+/// The number of failed seqlock reads before a reader falls back to the boxed copy.
+const ATTEMPTS: usize = 4;
+
+/// A cell holding a large `Copy` value inline, e.g. a struct of statistics, updated without
+/// allocating.
+///
+/// Readers copy the value under a seqlock. A reader that keeps racing with writers marks the cell
+/// contended, after which writers also publish each value boxed, and readers copy it from the box
+/// they protect instead. Superseded boxes are retired to `domain`.
+///
+/// ```
+/// use hazard::{Domain, SeqCell};
+///
+/// let domain = Domain::new();
+/// let cell = SeqCell::new(&domain, [0u64; 16]);
+/// cell.store([1; 16]);
+/// assert_eq!(cell.load(), [1; 16]);
+/// ```
+pub struct SeqCell<'d, T: Copy> {
+    domain: &'d Domain,
+    // Odd while a writer is writing `value`.
+    sequence: AtomicUsize,
+    value: UnsafeCell<T>,
+    contended: AtomicBool,
+    // The last value written while contended, with the odd sequence of its write.
+    boxed: AtomicPtr<(usize, T)>,
+}
+
+unsafe impl<T: Copy + Send> Send for SeqCell<'_, T> {}
+unsafe impl<T: Copy + Send> Sync for SeqCell<'_, T> {}
+
+impl<'d, T: Copy> SeqCell<'d, T> {
+    /// Creates a new cell holding `value`, whose boxed copies are retired to `domain`.
+    pub fn new(domain: &'d Domain, value: T) -> Self {
+        Self {
+            domain,
+            sequence: AtomicUsize::new(0),
+            value: UnsafeCell::new(value),
+            contended: AtomicBool::new(false),
+            boxed: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns a copy of the value.
+    pub fn load(&self) -> T {
+        let mut backoff = Backoff::new();
+        let mut attempts = 0;
+        loop {
+            let sequence = self.sequence.load(Ordering::Acquire);
+            if sequence.is_multiple_of(2) {
+                // # Safety
+                // a racing write is detected by the sequence below, and the torn copy discarded.
+                let value = unsafe { ptr::read_volatile(self.value.get()) };
+                fence(Ordering::Acquire);
+                if self.sequence.load(Ordering::Relaxed) == sequence {
+                    return value;
+                }
+            }
+            attempts += 1;
+            if attempts >= ATTEMPTS
+                && let Some(value) = self.load_boxed(sequence)
+            {
+                return value;
+            }
+            backoff.snooze();
+        }
+    }
+
+    /// Returns a copy of the boxed value if it is written by the last write completed before
+    /// `sequence`, marking the cell contended otherwise.
+    fn load_boxed(&self, sequence: usize) -> Option<T> {
+        let shield = Shield::new(self.domain.hazards());
+        let boxed = shield.protect(&self.boxed);
+        // # Safety
+        // `boxed` is protected, and retired only after it is replaced.
+        match unsafe { boxed.as_ref() } {
+            Some(&(written, value)) if written + 1 >= sequence & !1 => Some(value),
+            _ => {
+                self.contended.store(true, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Stores `value`, waiting for the other writers.
+    pub fn store(&self, value: T) {
+        let mut backoff = Backoff::new();
+        let sequence = loop {
+            let sequence = self.sequence.load(Ordering::Relaxed);
+            if sequence.is_multiple_of(2)
+                && self
+                    .sequence
+                    .compare_exchange_weak(
+                        sequence,
+                        sequence + 1,
+                        Ordering::Acquire,
+                        Ordering::Relaxed,
+                    )
+                    .is_ok()
+            {
+                break sequence + 1;
+            }
+            backoff.snooze();
+        };
+        fence(Ordering::Release);
+        // # Safety
+        // the odd sequence excludes the other writers, and readers discard their racing copies.
+        unsafe { ptr::write_volatile(self.value.get(), value) };
+        if self.contended.load(Ordering::Relaxed) {
+            let new = Box::into_raw(Box::new((sequence, value)));
+            let old = self.boxed.swap(new, Ordering::AcqRel);
+            if !old.is_null() {
+                // # Safety
+                // `old` is unlinked by the swap above, and holds only `Copy` data.
+                unsafe { self.domain.retire(old) };
+            }
+        }
+        self.sequence.store(sequence + 1, Ordering::Release);
+    }
+
+    /// Returns `true` if a reader has fallen back to the boxed copy, so writers allocate.
+    pub fn is_contended(&self) -> bool {
+        self.contended.load(Ordering::Relaxed)
+    }
+}
+
+impl<T: Copy> Drop for SeqCell<'_, T> {
+    fn drop(&mut self) {
+        let boxed = self.boxed.load(Ordering::Relaxed);
+        if !boxed.is_null() {
+            drop(unsafe { Box::from_raw(boxed) });
+        }
+    }
+}
+
+impl<T: Copy + fmt::Debug> fmt::Debug for SeqCell<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SeqCell").field(&self.load()).finish()
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::sync::atomic::Ordering::Relaxed;
+    use std::thread::scope;
+
+    use super::SeqCell;
+    use crate::Domain;
+
+    // readers racing with writers never see a torn value.
+    #[test]
+    fn no_torn_reads() {
+        let domain = Domain::builder().threshold(8).build();
+        let cell = SeqCell::new(&domain, [0usize; 32]);
+        scope(|s| {
+            for _ in 0..4 {
+                let _ = s.spawn(|| {
+                    for _ in 0..4096 {
+                        let value = cell.load();
+                        assert!(value.iter().all(|&v| v == value[0]));
+                    }
+                });
+            }
+            for i in 0..4096 {
+                cell.store([i; 32]);
+            }
+        });
+        assert_eq!(cell.load(), [4095; 32]);
+    }
+
+    // a reader failing to read a write in progress falls back to the boxed copy.
+    #[test]
+    fn boxed_fallback() {
+        let domain = Domain::new();
+        let cell = SeqCell::new(&domain, 0);
+        cell.contended.store(true, Relaxed);
+        cell.store(1);
+        cell.store(2);
+        assert_eq!(domain.pending_objects(), 1);
+        // pretend a write is in progress, which makes the seqlock reads fail.
+        let _ = cell.sequence.fetch_add(1, Relaxed);
+        assert_eq!(cell.load(), 2);
+        let _ = cell.sequence.fetch_add(1, Relaxed);
+        assert!(cell.is_contended());
+    }
+}
@@ -0,0 +1,151 @@
+//! Growable arrays of shields.
+
+use core::ops::Index;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::AtomicPtr;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicPtr;
+
+use super::{HazardBag, Shield};
+
+/// A growable array of shields, e.g. one per level of a tree of unknown depth.
+///
+/// Clearing keeps the slots acquired, so that they are reused by the next operation without
+/// touching the bag. Use `shrink` to release the slots that are not in use.
+///
+/// ```
+/// use std::sync::atomic::AtomicPtr;
+/// use hazard::{HazardBag, ShieldVec};
+///
+/// let hazards = HazardBag::new();
+/// let (mut one, mut two) = (1, 2);
+/// let (first, second) = (AtomicPtr::new(&mut one), AtomicPtr::new(&mut two));
+/// let mut shields = ShieldVec::new(&hazards);
+/// assert_eq!(shields.protect(&first), first.load(std::sync::atomic::Ordering::Relaxed));
+/// let _ = shields.protect(&second);
+/// assert_eq!(shields.len(), 2);
+/// shields.clear();
+/// assert_eq!((shields.len(), shields.capacity()), (0, 2));
+/// ```
+#[derive(Debug)]
+pub struct ShieldVec<'domain> {
+    hazards: &'domain HazardBag,
+    // `shields[..len]` are in use, and the others are cleared.
+    shields: Vec<Shield<'domain>>,
+    len: usize,
+}
+
+impl<'domain> ShieldVec<'domain> {
+    /// Creates an empty array of shields of `hazards`.
+    pub fn new(hazards: &'domain HazardBag) -> Self {
+        Self {
+            hazards,
+            shields: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Returns the number of shields in use.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no shield is in use.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of acquired slots, including the cleared ones kept for reuse.
+    pub fn capacity(&self) -> usize {
+        self.shields.len()
+    }
+
+    /// Returns the `index`-th shield in use, if any.
+    pub fn get(&self, index: usize) -> Option<&Shield<'domain>> {
+        self.shields[..self.len].get(index)
+    }
+
+    /// Returns an iterator over the shields in use.
+    pub fn iter(&self) -> impl Iterator<Item = &Shield<'domain>> {
+        self.shields[..self.len].iter()
+    }
+
+    /// Appends a cleared shield, reusing a kept slot if any, and returns it.
+    pub fn push(&mut self) -> &Shield<'domain> {
+        if self.len == self.shields.len() {
+            self.shields.push(Shield::new(self.hazards));
+        }
+        self.len += 1;
+        &self.shields[self.len - 1]
+    }
+
+    /// Protects the pointer loaded from `src` with a new shield appended to the array.
+    pub fn protect<T>(&mut self, src: &AtomicPtr<T>) -> *mut T {
+        self.push().protect(src)
+    }
+
+    /// Clears the shields from `len` on, keeping their slots for reuse.
+    pub fn truncate(&mut self, len: usize) {
+        for shield in &self.shields[len.min(self.len)..self.len] {
+            shield.clear();
+        }
+        self.len = self.len.min(len);
+    }
+
+    /// Clears all the shields, keeping their slots for reuse.
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Releases the slots kept for reuse.
+    pub fn shrink(&mut self) {
+        self.shields.truncate(self.len);
+    }
+}
+
+impl<'domain> Index<usize> for ShieldVec<'domain> {
+    type Output = Shield<'domain>;
+
+    fn index(&self, index: usize) -> &Shield<'domain> {
+        &self.shields[..self.len][index]
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::sync::atomic::AtomicPtr;
+    use std::sync::atomic::Ordering::Relaxed;
+
+    use super::ShieldVec;
+    use crate::HazardBag;
+
+    // cleared shields keep their slots, which are reused before acquiring new ones.
+    #[test]
+    fn reuse_slots() {
+        let hazard_bag = HazardBag::new();
+        let mut values = [0, 1, 2];
+        let srcs = values.each_mut().map(|value| AtomicPtr::new(value));
+        let mut shields = ShieldVec::new(&hazard_bag);
+        for src in &srcs {
+            let _ = shields.protect(src);
+        }
+        let active = || hazard_bag.slots().filter(|&(_, active, _)| active).count();
+        assert_eq!((shields.len(), active()), (3, 3));
+
+        shields.truncate(1);
+        assert_eq!(hazard_bag.all_hazards().len(), 1);
+        assert_eq!(shields[0].protect(&srcs[2]), srcs[2].load(Relaxed));
+        assert!(shields.get(1).is_none());
+        shields.clear();
+        assert!(hazard_bag.all_hazards().is_empty());
+        for src in &srcs {
+            let _ = shields.protect(src);
+        }
+        assert_eq!((shields.capacity(), active()), (3, 3));
+
+        shields.truncate(1);
+        shields.shrink();
+        assert_eq!((shields.capacity(), active()), (1, 1));
+    }
+}
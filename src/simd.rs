@@ -0,0 +1,57 @@
+//! Membership tests of retired pointers among a few hazards with SIMD compares.
+
+/// The most hazards for which a linear scan of them beats probing a `HazardTable`.
+pub(crate) const MAX_HAZARDS: usize = 64;
+
+/// Returns `true` if `hazards` contains `pointer`, comparing 4 hazards per instruction on the
+/// CPUs that support AVX2.
+pub(crate) fn contains(hazards: &[*mut ()], pointer: *mut ()) -> bool {
+    #[cfg(target_arch = "x86_64")]
+    if std::is_x86_feature_detected!("avx2") {
+        // # Safety
+        // the CPU supports AVX2.
+        return unsafe { contains_avx2(hazards, pointer) };
+    }
+    hazards.contains(&pointer)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+/// `contains` with AVX2.
+unsafe fn contains_avx2(hazards: &[*mut ()], pointer: *mut ()) -> bool {
+    use core::arch::x86_64::*;
+
+    let needle = _mm256_set1_epi64x(pointer as i64);
+    let mut chunks = hazards.chunks_exact(4);
+    for chunk in &mut chunks {
+        // # Safety
+        // `chunk` is 4 pointers, i.e. 256 bits.
+        let lanes = unsafe { _mm256_loadu_si256(chunk.as_ptr().cast()) };
+        if _mm256_movemask_epi8(_mm256_cmpeq_epi64(lanes, needle)) != 0 {
+            return true;
+        }
+    }
+    chunks.remainder().contains(&pointer)
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use super::contains;
+
+    // the scan agrees with a plain search, including the hazards past the last full vector.
+    #[test]
+    fn contains_all() {
+        let hazards = (1..=11usize)
+            .map(|i| (i * 8) as *mut ())
+            .collect::<Vec<_>>();
+        for len in 0..=hazards.len() {
+            for i in 0..=12usize {
+                let pointer = (i * 8) as *mut ();
+                assert_eq!(
+                    contains(&hazards[..len], pointer),
+                    hazards[..len].contains(&pointer)
+                );
+            }
+        }
+    }
+}
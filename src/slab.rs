@@ -0,0 +1,97 @@
+//! Retiring slots of slab-style allocators by handle.
+
+/// A slab-style allocator, whose slots are identified by index.
+///
+/// A slot retired with [`retire_slot`] is returned to the slab with `release` once no shield
+/// protects its address, so high-churn structures can recycle slots without `malloc`/`free`.
+///
+/// # Safety
+///
+/// `index_of` must be the inverse of `slot`.
+pub unsafe trait Slab {
+    /// Returns the address of slot `index`, which is what shields protect.
+    fn slot(&self, index: usize) -> *mut ();
+
+    /// Returns the index of the slot at `pointer`.
+    fn index_of(&self, pointer: *mut ()) -> usize;
+
+    /// Returns slot `index` to the slab.
+    fn release(&self, index: usize);
+}
+
+#[cfg(feature = "global")]
+/// Retires slot `index` of `slab` to the default domain. It is `release`d once no longer
+/// protected.
+///
+/// # Safety
+///
+/// * The slot must be removed from shared memory before calling this function.
+/// * The same slot should only be retired once until it is released.
+/// * `slab` must outlive the retired slot, i.e. until it is released.
+pub unsafe fn retire_slot<S: Slab>(slab: &S, index: usize) {
+    let slab_ptr: *const S = slab;
+    unsafe { super::retire_with(slab.slot(index), release::<S>, slab_ptr.cast()) };
+}
+
+#[cfg(feature = "global")]
+/// Returns the slot at `pointer` to the slab at `slab`.
+unsafe fn release<S: Slab>(pointer: *mut (), slab: *const ()) {
+    let slab = unsafe { &*slab.cast::<S>() };
+    slab.release(slab.index_of(pointer));
+}
+
+#[cfg(all(test, feature = "global", not(feature = "check-loom")))]
+mod tests {
+    use core::cell::UnsafeCell;
+    use std::sync::Mutex;
+    use std::sync::atomic::AtomicPtr;
+    use std::thread;
+
+    use super::{Slab, retire_slot};
+    use crate::Shield;
+
+    /// A fixed-size slab of `usize`s.
+    struct Arena {
+        slots: Box<[UnsafeCell<usize>]>,
+        free: Mutex<Vec<usize>>,
+    }
+
+    unsafe impl Sync for Arena {}
+
+    unsafe impl Slab for Arena {
+        fn slot(&self, index: usize) -> *mut () {
+            self.slots[index].get().cast()
+        }
+
+        fn index_of(&self, pointer: *mut ()) -> usize {
+            (pointer as usize - self.slots.as_ptr() as usize) / size_of::<usize>()
+        }
+
+        fn release(&self, index: usize) {
+            self.free.lock().unwrap().push(index);
+        }
+    }
+
+    // a retired slot returns to the slab once no longer protected.
+    #[test]
+    fn release_unprotected() {
+        thread::spawn(|| {
+            let arena = Arena {
+                slots: (0..4).map(UnsafeCell::new).collect(),
+                free: Mutex::new(Vec::new()),
+            };
+            let shield = Shield::default();
+            let _ = shield.protect(&AtomicPtr::new(arena.slot(1)));
+            unsafe { retire_slot(&arena, 1) };
+            unsafe { retire_slot(&arena, 2) };
+            crate::collect();
+            assert_eq!(*arena.free.lock().unwrap(), [2]);
+
+            drop(shield);
+            crate::collect();
+            assert_eq!(*arena.free.lock().unwrap(), [2, 1]);
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -0,0 +1,133 @@
+//! Open-addressing hash set of hazards that is reused across scans.
+
+use super::bloom;
+
+/// Hash set of non-null pointers with linear probing. Clearing is O(1): entries stamped with an
+/// old generation are considered empty.
+#[derive(Debug)]
+pub(crate) struct HazardTable {
+    entries: Vec<(u32, *mut ())>,
+    // the generation of the current entries, never 0.
+    stamp: u32,
+    len: usize,
+}
+
+impl HazardTable {
+    /// The minimum capacity of the table.
+    const MIN_CAPACITY: usize = 16;
+
+    /// Creates a new empty table. Does not allocate until the first insertion.
+    pub(crate) const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            stamp: 1,
+            len: 0,
+        }
+    }
+
+    /// Returns the number of pointers in the table.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Removes all pointers from the table, keeping the allocated capacity.
+    pub(crate) fn clear(&mut self) {
+        self.len = 0;
+        self.stamp = self.stamp.wrapping_add(1);
+        if self.stamp == 0 {
+            self.entries.fill((0, core::ptr::null_mut()));
+            self.stamp = 1;
+        }
+    }
+
+    /// Inserts `pointer` to the table.
+    pub(crate) fn insert(&mut self, pointer: *mut ()) {
+        // keep the load factor at most 1/2.
+        if (self.len + 1) * 2 > self.entries.len() {
+            self.grow();
+        }
+        let index = self.probe(pointer);
+        if self.entries[index].0 != self.stamp {
+            self.entries[index] = (self.stamp, pointer);
+            self.len += 1;
+        }
+    }
+
+    /// Returns `true` if the table contains `pointer`.
+    pub(crate) fn contains(&self, pointer: *mut ()) -> bool {
+        !self.entries.is_empty() && self.entries[self.probe(pointer)].0 == self.stamp
+    }
+
+    /// Returns an iterator over the pointers in the table.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = *mut ()> + '_ {
+        self.entries
+            .iter()
+            .filter(|(stamp, _)| *stamp == self.stamp)
+            .map(|(_, pointer)| *pointer)
+    }
+
+    /// Returns the index of the entry of `pointer`, or of the empty entry where it should be
+    /// inserted.
+    fn probe(&self, pointer: *mut ()) -> usize {
+        let mask = self.entries.len() - 1;
+        let mut index = bloom::hash(pointer) as usize & mask;
+        loop {
+            let (stamp, entry) = self.entries[index];
+            if stamp != self.stamp || entry == pointer {
+                return index;
+            }
+            index = (index + 1) & mask;
+        }
+    }
+
+    /// Doubles the capacity of the table, rehashing the current pointers.
+    fn grow(&mut self) {
+        let capacity = (self.entries.len() * 2).max(Self::MIN_CAPACITY);
+        let old = core::mem::replace(
+            &mut self.entries,
+            vec![(0, core::ptr::null_mut()); capacity],
+        );
+        let old_stamp = self.stamp;
+        self.stamp = 1;
+        self.len = 0;
+        for (stamp, pointer) in old {
+            if stamp == old_stamp {
+                self.insert(pointer);
+            }
+        }
+    }
+}
+
+impl Default for HazardTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use super::HazardTable;
+
+    // the table should contain exactly the pointers inserted since the last clear.
+    #[test]
+    fn insert_clear() {
+        let mut table = HazardTable::new();
+        assert!(!table.contains(8 as *mut ()));
+        for i in 1..100usize {
+            table.insert((i * 8) as *mut ());
+            table.insert((i * 8) as *mut ());
+        }
+        assert_eq!(table.len(), 99);
+        assert!((1..100usize).all(|i| table.contains((i * 8) as *mut ())));
+        assert!(!table.contains(800 as *mut ()));
+        assert_eq!(table.iter().count(), 99);
+
+        let capacity = table.entries.len();
+        table.clear();
+        assert_eq!(table.len(), 0);
+        assert!((1..100usize).all(|i| !table.contains((i * 8) as *mut ())));
+        table.insert(8 as *mut ());
+        assert!(table.contains(8 as *mut ()));
+        assert_eq!(table.entries.len(), capacity);
+    }
+}
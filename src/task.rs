@@ -0,0 +1,227 @@
+//! Task-local shields for async code, independent of the executor.
+//!
+//! A task runs in a `scope`, which keeps the shields of the task across polls and publishes them
+//! to the thread polling it. `protected_load` takes a shield from the task instead of the current
+//! thread, so the protection stays valid across `.await` points even if the task resumes on
+//! another worker thread, and its shield is reused by the next load of the task.
+//!
+//! ```
+//! use std::future::Future;
+//! use std::pin::pin;
+//! use std::sync::atomic::AtomicPtr;
+//! use std::task::{Context, Waker};
+//! use hazard::HazardBag;
+//! use hazard::task::{protected_load, scope};
+//!
+//! static HAZARDS: HazardBag = HazardBag::new();
+//!
+//! let src = AtomicPtr::new(Box::into_raw(Box::new(1)));
+//! let task = pin!(scope(&HAZARDS, async {
+//!     let protected = protected_load(&src).await;
+//!     unsafe { *protected.as_ptr() }
+//! }));
+//! assert!(task.poll(&mut Context::from_waker(Waker::noop())).is_ready());
+//! # drop(unsafe { Box::from_raw(src.load(std::sync::atomic::Ordering::Relaxed)) });
+//! ```
+
+use core::cell::RefCell;
+use core::fmt;
+use core::future::Future;
+use core::ops::Deref;
+use core::pin::Pin;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::AtomicPtr;
+use core::task::{Context, Poll};
+#[cfg(not(feature = "check-loom"))]
+use std::sync::{Arc, Mutex};
+#[cfg(not(feature = "check-loom"))]
+use std::thread_local;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicPtr;
+#[cfg(feature = "check-loom")]
+use loom::sync::{Arc, Mutex};
+#[cfg(feature = "check-loom")]
+use loom::thread_local;
+
+use super::{HazardBag, Protected, Shield};
+
+/// The max number of shields kept by a task for its next loads.
+const TASK_SHIELDS: usize = 8;
+
+/// The shields of a task, kept across polls to be reused by its protected loads.
+#[derive(Debug)]
+struct TaskLocal {
+    hazards: &'static HazardBag,
+    shields: Mutex<Vec<Shield<'static>>>,
+}
+
+thread_local! {
+    /// The storage of the task being polled by the current thread, if any.
+    static CURRENT: RefCell<Option<Arc<TaskLocal>>> = const { RefCell::new(None) };
+}
+
+/// Runs `future` as a task whose protected loads take shields of `hazards`. See `protected_load`.
+pub fn scope<F: Future>(hazards: &'static HazardBag, future: F) -> TaskShields<F> {
+    TaskShields {
+        future,
+        local: Arc::new(TaskLocal {
+            hazards,
+            shields: Mutex::new(Vec::new()),
+        }),
+    }
+}
+
+/// A future running an inner future with task-local shields. See `scope`.
+#[must_use = "futures do nothing unless polled"]
+pub struct TaskShields<F> {
+    future: F,
+    local: Arc<TaskLocal>,
+}
+
+impl<F: Future> Future for TaskShields<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        /// Restores the task polled before, even if the inner future panics.
+        struct Restore(Option<Arc<TaskLocal>>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                let previous = self.0.take();
+                let _ = CURRENT.try_with(|current| *current.borrow_mut() = previous);
+            }
+        }
+
+        // # Safety
+        // `future` is pinned structurally and never moved, while `local` is not pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let _restore = Restore(CURRENT.with(|current| current.replace(Some(this.local.clone()))));
+        unsafe { Pin::new_unchecked(&mut this.future) }.poll(cx)
+    }
+}
+
+impl<F> fmt::Debug for TaskShields<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskShields")
+            .field("local", &self.local)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Protects the pointer loaded from `src` with a shield of the current task, which stays valid
+/// across `.await` points until the returned guard is dropped.
+///
+/// # Panics
+///
+/// Panics if the current task is not run in a `scope`.
+pub async fn protected_load<T>(src: &AtomicPtr<T>) -> TaskProtected<T> {
+    let local = CURRENT
+        .with(|current| current.borrow().clone())
+        .expect("`protected_load` outside of `task::scope`");
+    let shield = local
+        .shields
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .pop()
+        .unwrap_or_else(|| Shield::new(local.hazards));
+    TaskProtected {
+        protected: Some(Protected::new(shield, src)),
+        local,
+    }
+}
+
+/// A pointer protected by a shield of a task, which is given back to the task when dropped. See
+/// `protected_load`.
+#[must_use = "the pointer is unprotected as soon as the guard is dropped"]
+pub struct TaskProtected<T> {
+    protected: Option<Protected<'static, T>>,
+    local: Arc<TaskLocal>,
+}
+
+impl<T> Deref for TaskProtected<T> {
+    type Target = Protected<'static, T>;
+
+    fn deref(&self) -> &Self::Target {
+        self.protected.as_ref().unwrap()
+    }
+}
+
+impl<T> Drop for TaskProtected<T> {
+    /// Clears the shield, and keeps it in the task if there is room.
+    fn drop(&mut self) {
+        let shield = self.protected.take().unwrap().into_shield();
+        shield.clear();
+        let mut shields = self.local.shields.lock().unwrap_or_else(|e| e.into_inner());
+        if shields.len() < TASK_SHIELDS {
+            shields.push(shield);
+        }
+    }
+}
+
+impl<T> fmt::Debug for TaskProtected<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TaskProtected")
+            .field(&self.as_ptr())
+            .finish()
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::future::Future;
+    use std::pin::{Pin, pin};
+    use std::ptr;
+    use std::sync::atomic::{AtomicPtr, Ordering};
+    use std::task::{Context, Poll, Waker};
+
+    use super::{protected_load, scope};
+    use crate::HazardBag;
+
+    /// A future that is pending on the first poll.
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            Poll::Pending
+        }
+    }
+
+    // a load stays protected across an await, and its shield is reused by the next load of the
+    // task.
+    #[test]
+    fn protect_across_await() {
+        static HAZARDS: HazardBag = HazardBag::new();
+        let src = AtomicPtr::new(Box::into_raw(Box::new(7)));
+        let pointer = src.load(Ordering::Relaxed);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut task = pin!(scope(&HAZARDS, async {
+            let protected = protected_load(&src).await;
+            YieldOnce(false).await;
+            assert!(HAZARDS.all_hazards().contains(&pointer.cast()));
+            drop(protected);
+            let protected = protected_load(&src).await;
+            assert_eq!(HAZARDS.active_slots(), 1);
+            unsafe { *protected.as_ptr() }
+        }));
+        assert!(task.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(task.as_mut().poll(&mut cx), Poll::Ready(7));
+        assert!(HAZARDS.all_hazards().is_empty());
+        drop(unsafe { Box::from_raw(pointer) });
+    }
+
+    // loads outside of a task scope panic.
+    #[test]
+    #[should_panic = "outside of `task::scope`"]
+    fn load_outside_scope() {
+        let src = AtomicPtr::<i32>::new(ptr::null_mut());
+        let mut load = pin!(protected_load(&src));
+        let _ = load.as_mut().poll(&mut Context::from_waker(Waker::noop()));
+    }
+}
@@ -0,0 +1,55 @@
+//! Helpers shared by the unit tests of the crate.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::scope;
+
+use crate::Reclaimer;
+use crate::collections::{Queue, RingBuffer, Stack};
+
+/// An object counting its drops in a shared counter.
+pub(crate) struct Tester(pub(crate) Arc<AtomicUsize>);
+
+impl Drop for Tester {
+    fn drop(&mut self) {
+        let _ = self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Pushes and pops concurrently on the collections reclaimed by `reclaimer`, and checks that
+/// every item is dropped once the collections are, including the items retired by the ring,
+/// which only `reclaimer` frees.
+pub(crate) fn exercise_collections<R: Reclaimer + Sync>(reclaimer: &R) {
+    const THREADS: usize = 8;
+    const ITER: usize = 1024;
+
+    let freed = Arc::new(AtomicUsize::new(0));
+    let stack = Stack::with_reclaimer(reclaimer);
+    let queue = Queue::with_reclaimer(reclaimer);
+    let ring = RingBuffer::with_reclaimer(THREADS, reclaimer);
+    scope(|s| {
+        for _ in 0..THREADS {
+            let _ = s.spawn(|| {
+                for _ in 0..ITER {
+                    stack.push(Tester(freed.clone()));
+                    queue.push(Tester(freed.clone()));
+                    let mut item = Tester(freed.clone());
+                    while let Err(rejected) = ring.push(item) {
+                        item = rejected;
+                    }
+                    assert!(stack.try_pop().is_some());
+                    assert!(queue.try_pop().is_some());
+                    while ring.pop_with(|_| ()).is_none() {}
+                }
+            });
+        }
+    });
+    assert!(stack.is_empty());
+    assert!(queue.try_pop().is_none());
+    assert!(ring.pop_with(|_| ()).is_none());
+    drop((stack, queue, ring));
+    for _ in 0..4 {
+        reclaimer.collect();
+    }
+    assert_eq!(freed.load(Ordering::Relaxed), 3 * THREADS * ITER);
+}
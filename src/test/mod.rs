@@ -1 +1,3 @@
+#[cfg(all(test, not(feature = "check-loom")))]
+pub(crate) mod common;
 pub mod loom;
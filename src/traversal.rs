@@ -0,0 +1,169 @@
+//! Optimistic traversal of linked structures, restarting from the last valid ancestor.
+
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::AtomicPtr;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicPtr;
+
+use super::ordering::Ordering;
+use super::{HazardBag, Shield};
+
+/// The bit of a link marking the node containing it as removed, e.g. in Harris's list.
+pub const MARK: usize = 1;
+
+/// A path from a root link down a linked structure, e.g. a list or a tree, where each node is
+/// protected by a shield of its own.
+///
+/// Each step records the link it was loaded from. When a step observes a marked link, or fails
+/// later, `recover` truncates the path to the deepest node that is still reachable from the root,
+/// instead of restarting from the root.
+#[derive(Debug)]
+pub struct Traversal<'domain, 'src, T> {
+    hazards: &'domain HazardBag,
+    root: &'src AtomicPtr<T>,
+    shields: Vec<Shield<'domain>>,
+    // The link each node was loaded from paired with the node. The first link is `root`, and the
+    // others are in the previous nodes.
+    path: Vec<(*const AtomicPtr<T>, *mut T)>,
+}
+
+impl<'domain, 'src, T> Traversal<'domain, 'src, T> {
+    /// Creates a traversal from `root` with shields of `hazards`, positioned at `root`.
+    pub fn new(hazards: &'domain HazardBag, root: &'src AtomicPtr<T>) -> Self {
+        Self {
+            hazards,
+            root,
+            shields: Vec::new(),
+            path: Vec::new(),
+        }
+    }
+
+    /// Returns the current node, or null at `root` or past the last node.
+    pub fn current(&self) -> *mut T {
+        self.path.last().map_or(ptr::null_mut(), |&(_, node)| node)
+    }
+
+    /// Returns the protected nodes from the root down to the current node.
+    pub fn path(&self) -> impl Iterator<Item = *mut T> + '_ {
+        self.path.iter().map(|&(_, node)| node)
+    }
+
+    /// Restarts from `root`, and steps to the first node.
+    pub fn start(&mut self) -> *mut T {
+        self.truncate(0);
+        // # Safety
+        // `root` is the first link.
+        unsafe { self.advance(self.root) }.expect("the root link is marked")
+    }
+
+    /// Steps to the node loaded from `link` and protects it, or returns `None` if `link` is
+    /// marked, i.e. the current node is being removed and the traversal should `recover`.
+    ///
+    /// # Safety
+    ///
+    /// `link` must be `root` when the path is empty, and a link in the current node otherwise.
+    pub unsafe fn advance(&mut self, link: &AtomicPtr<T>) -> Option<*mut T> {
+        let depth = self.path.len();
+        if depth == self.shields.len() {
+            self.shields.push(Shield::new(self.hazards));
+        }
+        let node = self.shields[depth].protect(link);
+        if node as usize & MARK != 0 {
+            self.shields[depth].clear();
+            return None;
+        }
+        self.path.push((link, node));
+        Some(node)
+    }
+
+    /// Truncates the path to the deepest node whose links from the root still point down to it,
+    /// and returns it. Returns null if even the first node is no longer linked from `root`.
+    pub fn recover(&mut self) -> *mut T {
+        let valid = self
+            .path
+            .iter()
+            // # Safety
+            // each link is `root` or in the previous node, which is protected.
+            .position(|&(link, node)| unsafe { &*link }.load(Ordering::Acquire) != node)
+            .unwrap_or(self.path.len());
+        self.truncate(valid);
+        self.current()
+    }
+
+    /// Truncates the path to `depth` nodes, clearing the shields of the others.
+    fn truncate(&mut self, depth: usize) {
+        for shield in &self.shields[depth..self.path.len()] {
+            shield.clear();
+        }
+        self.path.truncate(depth);
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::ptr;
+    use std::sync::atomic::{AtomicPtr, Ordering::Relaxed};
+
+    use super::{MARK, Traversal};
+    use crate::HazardBag;
+
+    struct Node {
+        next: AtomicPtr<Node>,
+    }
+
+    /// Returns a list of `len` nodes, linked from the returned head.
+    fn list(len: usize) -> (AtomicPtr<Node>, Vec<*mut Node>) {
+        let nodes = (0..len)
+            .map(|_| {
+                Box::into_raw(Box::new(Node {
+                    next: AtomicPtr::new(ptr::null_mut()),
+                }))
+            })
+            .collect::<Vec<_>>();
+        for pair in nodes.windows(2) {
+            unsafe { (*pair[0]).next.store(pair[1], Relaxed) };
+        }
+        (AtomicPtr::new(nodes[0]), nodes)
+    }
+
+    // after unlinking a node on the path, the traversal recovers to its predecessor.
+    #[test]
+    fn recover_to_ancestor() {
+        let hazards = HazardBag::new();
+        let (head, nodes) = list(3);
+        let mut traversal = Traversal::new(&hazards, &head);
+        assert_eq!(traversal.start(), nodes[0]);
+        for &node in &nodes[1..] {
+            let current = traversal.current();
+            assert_eq!(unsafe { traversal.advance(&(*current).next) }, Some(node));
+        }
+        assert_eq!(traversal.path().collect::<Vec<_>>(), nodes);
+
+        unsafe { (*nodes[0]).next.store(nodes[2], Relaxed) };
+        assert_eq!(traversal.recover(), nodes[0]);
+        assert!(!hazards.all_hazards().contains(&nodes[1].cast()));
+        drop(traversal);
+        for node in nodes {
+            drop(unsafe { Box::from_raw(node) });
+        }
+    }
+
+    // a marked link stops the traversal at the node being removed.
+    #[test]
+    fn marked_link() {
+        let hazards = HazardBag::new();
+        let (head, nodes) = list(2);
+        let mut traversal = Traversal::new(&hazards, &head);
+        let first = traversal.start();
+        let marked = (nodes[1] as usize | MARK) as *mut Node;
+        unsafe { (*first).next.store(marked, Relaxed) };
+        assert_eq!(unsafe { traversal.advance(&(*first).next) }, None);
+        assert_eq!(traversal.recover(), first);
+        drop(traversal);
+        for node in nodes {
+            drop(unsafe { Box::from_raw(node) });
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Valgrind client requests, enabled by the `valgrind` feature.
+//!
+//! Client requests are no-ops when the program is not running under Valgrind. They are only
+//! implemented for x86_64 and aarch64, and do nothing on the other targets.
+
+/// Base of the Memcheck requests: `VG_USERREQ_TOOL_BASE('M', 'C')`.
+const MEMCHECK_BASE: usize = ((b'M' as usize) << 24) | ((b'C' as usize) << 16);
+const MAKE_MEM_NOACCESS: usize = MEMCHECK_BASE;
+const MAKE_MEM_DEFINED: usize = MEMCHECK_BASE + 2;
+#[cfg(test)]
+const RUNNING_ON_VALGRIND: usize = 0x1001;
+
+/// Issues a client request and returns its result, or `default` if not running under Valgrind.
+#[allow(unused_variables)]
+fn client_request(default: usize, request: usize, arg1: usize, arg2: usize) -> usize {
+    let args: [usize; 6] = [request, arg1, arg2, 0, 0, 0];
+    #[allow(unused_mut)]
+    let mut result = default;
+    // # Safety
+    // The magic sequence does not change any register when not running under Valgrind.
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        core::arch::asm!(
+            "rol rdi, 3",
+            "rol rdi, 13",
+            "rol rdi, 61",
+            "rol rdi, 51",
+            "xchg rbx, rbx",
+            in("rax") args.as_ptr(),
+            inout("rdx") result,
+            options(nostack),
+        );
+    }
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        core::arch::asm!(
+            "ror x12, x12, #3",
+            "ror x12, x12, #13",
+            "ror x12, x12, #51",
+            "ror x12, x12, #61",
+            "orr x10, x10, x10",
+            in("x4") args.as_ptr(),
+            inout("x3") result,
+            options(nostack),
+        );
+    }
+    result
+}
+
+/// Marks the `size` bytes at `addr` as unaddressable. Memcheck reports any access before
+/// `make_defined`.
+pub(crate) fn make_noaccess(addr: *const (), size: usize) {
+    let _ = client_request(0, MAKE_MEM_NOACCESS, addr as usize, size);
+}
+
+/// Marks the `size` bytes at `addr` as addressable and defined again.
+pub(crate) fn make_defined(addr: *const (), size: usize) {
+    let _ = client_request(0, MAKE_MEM_DEFINED, addr as usize, size);
+}
+
+/// Returns the nesting level of Valgrind, i.e. 0 if not running under Valgrind.
+#[cfg(test)]
+pub(crate) fn running_on_valgrind() -> usize {
+    client_request(0, RUNNING_ON_VALGRIND, 0, 0)
+}
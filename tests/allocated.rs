@@ -0,0 +1,86 @@
+//! End-to-end tests with genuinely allocated nodes that track their drops, instead of hazards
+//! synthesized from integers, so that Miri, AddressSanitizer and loom observe real frees.
+
+use core::ptr;
+
+use hazard::test::loom::sync::Arc;
+use hazard::test::loom::sync::atomic::Ordering::*;
+use hazard::test::loom::sync::atomic::{AtomicPtr, AtomicUsize};
+use hazard::test::loom::{model, thread};
+use hazard::{Domain, DomainHandle};
+
+/// A node counting the drops of its generation in `drops`.
+struct Node {
+    value: usize,
+    drops: Arc<AtomicUsize>,
+}
+
+impl Node {
+    fn new(value: usize, drops: &Arc<AtomicUsize>) -> *mut Self {
+        Box::into_raw(Box::new(Self {
+            value,
+            drops: drops.clone(),
+        }))
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        let _ = self.drops.fetch_add(1, Relaxed);
+    }
+}
+
+// a reader never observes a freed node while a writer replaces, retires and collects it.
+#[test]
+fn replace_retire_collect() {
+    model(|| {
+        let domain = DomainHandle::new(Domain::builder().threshold(1).build());
+        let drops = Arc::new(AtomicUsize::new(0));
+        let src = Arc::new(AtomicPtr::new(Node::new(0, &drops)));
+
+        let reader = {
+            let (domain, src) = (domain.clone(), src.clone());
+            thread::spawn(move || {
+                let shield = domain.shield();
+                let node = shield.protect(&src);
+                // the node is accessed through its memory, which is still allocated.
+                assert!(unsafe { (*node).value } <= 1);
+            })
+        };
+
+        let old = src.swap(Node::new(1, &drops), AcqRel);
+        unsafe { domain.retire(old) };
+        domain.collect();
+        reader.join().unwrap();
+        domain.collect();
+        assert_eq!(drops.load(Relaxed), 1);
+
+        unsafe { domain.retire(src.swap(ptr::null_mut(), AcqRel)) };
+        drop(domain);
+        assert_eq!(drops.load(Relaxed), 2);
+    })
+}
+
+// a protected node is found among the hazards by its own address, and freed exactly once after
+// the shield is dropped.
+#[test]
+fn protected_until_dropped() {
+    model(|| {
+        let domain = DomainHandle::new(Domain::builder().threshold(usize::MAX).build());
+        let drops = Arc::new(AtomicUsize::new(0));
+        let node = Node::new(7, &drops);
+        let src = AtomicPtr::new(node);
+        let shield = domain.shield();
+        assert_eq!(shield.protect(&src), node);
+        assert!(domain.hazards().all_hazards().contains(&node.cast()));
+
+        src.store(ptr::null_mut(), Release);
+        unsafe { domain.retire(node) };
+        domain.collect();
+        assert_eq!(drops.load(Relaxed), 0);
+        assert_eq!(unsafe { (*node).value }, 7);
+        drop(shield);
+        domain.collect();
+        assert_eq!(drops.load(Relaxed), 1);
+    })
+}
@@ -1,3 +1,5 @@
+#![cfg(feature = "global")]
+
 #[cfg(not(feature = "check-loom"))]
 use core::sync::atomic::{AtomicPtr, Ordering::*};
 use std::thread::{scope, sleep};
@@ -62,9 +64,9 @@ fn counter_sleep() {
                     loop {
                         let cur_ptr = {
                             let mut cur = count.load(Relaxed);
-                            while let Err(new) = shield.try_protect(cur, &count) {
+                            while let Err(err) = shield.try_protect(cur, &count) {
                                 sleep(Duration::from_micros(1));
-                                cur = new;
+                                cur = err.observed();
                             }
                             cur
                         };
@@ -238,7 +240,7 @@ mod sync {
             let th = {
                 thread::spawn(move || {
                     let local = atomic.load(Relaxed);
-                    if !HAZARDS.all_hazards().contains(&(obj as *mut ())) {
+                    if !HAZARDS.hazards().all_hazards().contains(&(obj as *mut ())) {
                         assert_eq!(unsafe { (*local).load(Relaxed) }, 123);
                     }
                 })
@@ -409,8 +411,8 @@ mod queue {
             let next_shield = Shield::default();
             let mut head = self.head.load(Acquire);
             loop {
-                if let Err(new) = head_shield.try_protect(head, &self.head) {
-                    head = new;
+                if let Err(err) = head_shield.try_protect(head, &self.head) {
+                    head = err.observed();
                     continue;
                 }
                 // SAFETY:
@@ -422,7 +424,7 @@ mod queue {
                 if next.is_null() {
                     return None;
                 }
-                next_shield.set(next);
+                let _ = next_shield.set(next);
                 let next_ref = match Shield::validate(head, &self.head) {
                     Ok(_) => {
                         // SAFETY:
@@ -432,8 +434,8 @@ mod queue {
                         //    re-validating `head` also validates `next.
                         unsafe { &*next }
                     }
-                    Err(new) => {
-                        head = new;
+                    Err(err) => {
+                        head = err.observed();
                         continue;
                     }
                 };
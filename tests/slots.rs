@@ -0,0 +1,60 @@
+//! Models of recycling hazard slots, where acquiring an inactive slot races with the release of
+//! its previous shield.
+
+use hazard::test::loom::sync::Arc;
+use hazard::test::loom::sync::atomic::AtomicPtr;
+use hazard::test::loom::sync::atomic::Ordering::*;
+use hazard::test::loom::{model, thread};
+use hazard::{HazardBag, Shield, SlotHandle};
+
+// a recycled slot never exposes the hazard of the shield that released it.
+#[test]
+fn recycle_cleared() {
+    model(|| {
+        let hazards = Arc::new(HazardBag::new());
+        let node = Box::into_raw(Box::new(1));
+        let dropper = {
+            let hazards = hazards.clone();
+            let node = node as usize;
+            thread::spawn(move || {
+                let shield = Shield::new(&hazards);
+                let _ = shield.protect(&AtomicPtr::new(node as *mut i32));
+                drop(shield);
+            })
+        };
+
+        let handle = SlotHandle::acquire(&hazards);
+        assert!(handle.hazard(Relaxed).is_null());
+        unsafe { handle.release() };
+        dropper.join().unwrap();
+        drop(unsafe { Box::from_raw(node) });
+    })
+}
+
+// once a slot is recycled and republished, scanning the hazards finds only the new pointer.
+#[test]
+fn recycle_republished() {
+    model(|| {
+        let hazards = Arc::new(HazardBag::new());
+        let (old, new) = (Box::into_raw(Box::new(1)), Box::into_raw(Box::new(2)));
+        let shield = Shield::new(&hazards);
+        let _ = shield.protect(&AtomicPtr::new(old));
+        let recycler = {
+            let hazards = hazards.clone();
+            let new = new as usize;
+            thread::spawn(move || {
+                let handle = SlotHandle::acquire(&hazards);
+                assert!(handle.hazard(Relaxed).is_null());
+                // keep the slot acquired, so that it is released with the bag.
+                handle.publish(new as *mut i32, SeqCst);
+            })
+        };
+        drop(shield);
+
+        recycler.join().unwrap();
+        let all = hazards.all_hazards();
+        assert!(all.contains(&new.cast()) && !all.contains(&old.cast()));
+        drop(unsafe { Box::from_raw(old) });
+        drop(unsafe { Box::from_raw(new) });
+    })
+}
@@ -0,0 +1,120 @@
+//! Long-running soak test of protect/retire churn across many threads, with invariant checks.
+//!
+//! Enabled with `feature = "soak"` and meant to run under sanitizers in CI, e.g.
+//!
+//! ```text
+//! HAZARD_SOAK_SECS=7200 RUSTFLAGS=-Zsanitizer=address \
+//!     cargo +nightly test --release --features soak,asan --test soak -- --nocapture
+//! ```
+//!
+//! `HAZARD_SOAK_SECS` sets the duration, one hour by default.
+
+#![cfg(all(feature = "soak", feature = "global", not(feature = "check-loom")))]
+
+use std::env;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering::*};
+use std::thread::{scope, sleep};
+use std::time::{Duration, Instant};
+
+use hazard::{DomainConfig, Shield, collect, retire};
+
+const THREADS: usize = 32;
+const SOURCES: usize = 64;
+const SHIELDS: usize = 4;
+const LIVE: u64 = 0x1111_1111_1111_1111;
+const DEAD: u64 = 0xdead_dead_dead_dead;
+
+/// The number of objects retired and not freed yet.
+static RETIRED: AtomicUsize = AtomicUsize::new(0);
+
+/// An object whose canary tells if it has been dropped.
+struct Object {
+    canary: u64,
+    retired: bool,
+}
+
+impl Object {
+    fn new() -> *mut Object {
+        Box::into_raw(Box::new(Object {
+            canary: LIVE,
+            retired: false,
+        }))
+    }
+}
+
+impl Drop for Object {
+    fn drop(&mut self) {
+        assert_eq!(self.canary, LIVE, "object dropped twice");
+        self.canary = DEAD;
+        if self.retired {
+            let _ = RETIRED.fetch_sub(1, Relaxed);
+        }
+    }
+}
+
+/// A cheap per-thread pseudo-random generator.
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+#[test]
+fn soak() {
+    let secs = env::var("HAZARD_SOAK_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(3600);
+    let deadline = Instant::now() + Duration::from_secs(secs);
+    let config = DomainConfig::default();
+    // Each thread keeps at most `threshold` pointers in its list plus the ones protected by the
+    // other threads, which are at most all the shields.
+    let bound = THREADS * (config.threshold * config.collect_every + THREADS * SHIELDS);
+
+    let sources: [AtomicPtr<Object>; SOURCES] =
+        std::array::from_fn(|_| AtomicPtr::new(Object::new()));
+    let done = AtomicBool::new(false);
+    let ops = AtomicUsize::new(0);
+    scope(|s| {
+        for t in 0..THREADS {
+            let (sources, done, ops) = (&sources, &done, &ops);
+            let _ = s.spawn(move || {
+                let mut state = (t as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+                let shields: [Shield; SHIELDS] = std::array::from_fn(|_| Shield::default());
+                let mut local_ops = 0;
+                while !done.load(Relaxed) {
+                    let r = xorshift(&mut state);
+                    let source = &sources[r as usize % SOURCES];
+                    let shield = &shields[(r >> 8) as usize % SHIELDS];
+                    if (r >> 16).is_multiple_of(4) {
+                        let old = source.swap(Object::new(), AcqRel);
+                        unsafe { (*old).retired = true };
+                        let _ = RETIRED.fetch_add(1, Relaxed);
+                        unsafe { retire(old) };
+                    } else {
+                        let pointer = shield.protect(source);
+                        assert_eq!(unsafe { (*pointer).canary }, LIVE, "protected object freed");
+                    }
+                    if (r >> 24).is_multiple_of(1024) {
+                        collect();
+                    }
+                    local_ops += 1;
+                }
+                let _ = ops.fetch_add(local_ops, Relaxed);
+            });
+        }
+
+        while Instant::now() < deadline {
+            sleep(Duration::from_millis(100).min(deadline - Instant::now()));
+            let retired = RETIRED.load(Relaxed);
+            assert!(retired <= bound, "{retired} retired objects exceed {bound}");
+        }
+        done.store(true, Relaxed);
+    });
+    println!("{} operations in {secs}s", ops.load(Relaxed));
+
+    for source in &sources {
+        drop(unsafe { Box::from_raw(source.load(Relaxed)) });
+    }
+}